@@ -15,6 +15,7 @@ extern crate zeroize;
 pub mod confidential;
 pub mod genesis;
 pub mod parity;
+pub mod proof;
 pub mod storage;
 
 /// Block gas limit.
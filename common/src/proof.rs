@@ -0,0 +1,152 @@
+//! MKVS proof verification.
+use std::any::Any;
+
+use anyhow::{anyhow, ensure, Result};
+use io_context::Context;
+use oasis_core_runtime::{
+    common::crypto::hash::Hash,
+    storage::mkvs::{
+        sync::{GetPrefixesRequest, GetRequest, IterateRequest, Proof, ProofResponse, ReadSync},
+        Root, RootType, Tree,
+    },
+};
+
+/// Verify that a proof attests to the given value of a key under the given
+/// state root.
+///
+/// A value of `None` verifies that the key does not exist.
+pub fn verify_proof(
+    state_root: Hash,
+    key: &[u8],
+    value: Option<&[u8]>,
+    proof: &Proof,
+) -> Result<()> {
+    // Look up the key in a tree that can only fetch nodes from the proof. The
+    // tree checks the proof against the root before using any of its nodes.
+    let tree = Tree::make()
+        .with_root(Root {
+            root_type: RootType::State,
+            hash: state_root,
+            ..Default::default()
+        })
+        .new(Box::new(ProofReadSyncer(Some(proof.clone()))));
+
+    let proven = tree.get(Context::background(), key)?;
+    ensure!(
+        proven.as_ref().map(|v| v.as_slice()) == value,
+        "proof does not match the value of the key"
+    );
+
+    Ok(())
+}
+
+/// Read syncer that serves nodes from a single proof.
+struct ProofReadSyncer(Option<Proof>);
+
+impl ReadSync for ProofReadSyncer {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn sync_get(&mut self, _ctx: Context, _request: GetRequest) -> Result<ProofResponse> {
+        match self.0.take() {
+            Some(proof) => Ok(ProofResponse { proof }),
+            None => Err(anyhow!("proof is incomplete")),
+        }
+    }
+
+    fn sync_get_prefixes(
+        &mut self,
+        _ctx: Context,
+        _request: GetPrefixesRequest,
+    ) -> Result<ProofResponse> {
+        Err(anyhow!("prefix lookups are not supported"))
+    }
+
+    fn sync_iterate(&mut self, _ctx: Context, _request: IterateRequest) -> Result<ProofResponse> {
+        Err(anyhow!("iteration is not supported"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use oasis_core_runtime::storage::mkvs::sync::{NoopReadSyncer, TreeID};
+
+    /// Build a tree with the given entries and return it with its root hash.
+    fn make_tree(entries: &[(&str, &str)]) -> (Tree, Hash) {
+        let mut tree = Tree::make()
+            .with_root_type(RootType::State)
+            .new(Box::new(NoopReadSyncer {}));
+        for (key, value) in entries {
+            tree.insert(Context::background(), key.as_bytes(), value.as_bytes())
+                .unwrap();
+        }
+        let (_, root_hash) = tree
+            .commit(Context::background(), Default::default(), 0)
+            .unwrap();
+
+        (tree, root_hash)
+    }
+
+    /// Produce a proof of a key, as returned by a storage node.
+    fn prove(tree: &mut Tree, root_hash: Hash, key: &[u8]) -> Proof {
+        let root = Root {
+            root_type: RootType::State,
+            hash: root_hash,
+            ..Default::default()
+        };
+        let request = GetRequest {
+            tree: TreeID {
+                root,
+                position: root_hash,
+            },
+            key: key.to_vec(),
+            include_siblings: false,
+        };
+
+        tree.sync_get(Context::background(), request).unwrap().proof
+    }
+
+    #[test]
+    fn test_verify_proof() {
+        let (mut tree, root_hash) = make_tree(&[("key", "value"), ("other key", "other")]);
+
+        let proof = prove(&mut tree, root_hash, b"key");
+        verify_proof(root_hash, b"key", Some(b"value"), &proof).unwrap();
+
+        let proof = prove(&mut tree, root_hash, b"missing key");
+        verify_proof(root_hash, b"missing key", None, &proof).unwrap();
+    }
+
+    #[test]
+    fn test_verify_proof_tampered() {
+        let (mut tree, root_hash) = make_tree(&[("key", "value"), ("other key", "other")]);
+        let proof = prove(&mut tree, root_hash, b"key");
+
+        // The proof does not attest to other values or roots.
+        assert!(verify_proof(root_hash, b"key", Some(b"forged"), &proof).is_err());
+        assert!(verify_proof(root_hash, b"key", None, &proof).is_err());
+        let (_, other_root_hash) = make_tree(&[("key", "forged")]);
+        assert!(verify_proof(other_root_hash, b"key", Some(b"value"), &proof).is_err());
+
+        // Tampered nodes do not hash to the root.
+        let mut tampered = proof.clone();
+        for entry in tampered.entries.iter_mut() {
+            if let Some(entry) = entry {
+                let last = entry.len() - 1;
+                entry[last] ^= 0xff;
+            }
+        }
+        assert!(verify_proof(root_hash, b"key", Some(b"value"), &tampered).is_err());
+    }
+
+    #[test]
+    fn test_verify_proof_incomplete() {
+        let state_root = Hash::digest_bytes(b"state root");
+
+        assert!(verify_proof(state_root, b"key", None, &Proof::default()).is_err());
+        assert!(verify_proof(state_root, b"key", Some(b"value"), &Proof::default()).is_err());
+    }
+}
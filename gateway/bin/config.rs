@@ -0,0 +1,329 @@
+//! Gateway configuration.
+//!
+//! Options are set by command line flags or by a TOML config file given with
+//! `--config`, flags taking precedence over the file. The file groups options
+//! in sections, e.g.:
+//!
+//! ```toml
+//! runtime-id = "0000000000000000000000000000000000000000000000000000000000000000"
+//! node-address = "unix:/node/internal.sock"
+//!
+//! [http]
+//! port = 8545
+//! cors = ["https://example.com"]
+//!
+//! [rpc.method-costs]
+//! eth_getLogs = 50
+//! ```
+//!
+//! See `KEYS` for the config file key of each flag.
+use std::{collections::HashMap, fmt::Display, fs, path::Path, str::FromStr};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ArgMatches;
+use toml::Value;
+
+/// Config file keys and the flags they correspond to.
+const KEYS: &[(&str, &str)] = &[
+    ("runtime-id", "runtime-id"),
+    ("node-address", "node-address"),
+    ("interface", "interface"),
+    ("pubsub-interval", "pubsub-interval"),
+    ("gas-price", "gas-price"),
+    ("trusted-proxies", "trusted-proxies"),
+    ("api-keys", "api-keys"),
+    ("verbosity", "v"),
+    ("http.port", "http-port"),
+    ("http.threads", "threads"),
+    ("http.apis", "http-apis"),
+    ("http.max-connections", "http-max-connections"),
+    ("http.idle-timeout", "http-idle-timeout"),
+    ("http.cors", "http-cors"),
+    ("http.hosts", "http-hosts"),
+    ("http.client-rate-limit", "http-client-rate-limit"),
+    ("ws.port", "ws-port"),
+    ("ws.apis", "ws-apis"),
+    ("ws.max-connections", "ws-max-connections"),
+    ("ws.rate-limit", "ws-rate-limit"),
+    ("ws.idle-timeout", "ws-idle-timeout"),
+    ("ws.ping-interval", "ws-ping-interval"),
+    ("ws.max-subscriptions", "ws-max-subscriptions"),
+    ("ws.max-queued-notifications", "ws-max-queued-notifications"),
+    ("ws.max-logs-replay-depth", "ws-max-logs-replay-depth"),
+    ("ws.max-logs-replays", "ws-max-logs-replays"),
+    ("ws.origins", "ws-origins"),
+    ("ws.hosts", "ws-hosts"),
+    ("ws.client-rate-limit", "ws-client-rate-limit"),
+    ("ws.client-max-connections", "ws-client-max-connections"),
+    ("ipc.path", "ipc-path"),
+    ("ipc.apis", "ipc-apis"),
+    ("ipc.permissions", "ipc-permissions"),
+    ("tls.cert", "tls-cert"),
+    ("tls.key", "tls-key"),
+    ("tls.client-ca", "tls-client-ca"),
+    ("rpc.max-batch", "jsonrpc-max-batch"),
+    ("rpc.timeout", "rpc-timeout"),
+    ("rpc.gas-cap", "rpc-gas-cap"),
+    ("rpc.client-budget", "rpc-client-budget"),
+    ("rpc.default-cost", "rpc-default-cost"),
+    ("rpc.method-costs", "rpc-method-cost"),
+    ("simulator.timeout", "simulation-timeout"),
+    ("simulator.max-queue", "simulator-max-queue"),
+    ("simulator.max-bundle", "simulator-max-bundle"),
+    ("cache.blocks", "block-cache-size"),
+    ("cache.transactions", "transaction-cache-size"),
+    ("cache.receipts", "receipt-cache-size"),
+    ("filter.lifetime", "filter-lifetime"),
+    ("filter.max-per-client", "filter-max-per-client"),
+    ("filter.max-total", "filter-max-total"),
+    ("filter.max-catch-up", "filter-max-catch-up"),
+    ("health.max-block-age", "health-max-block-age"),
+    ("metrics.mode", "prometheus-mode"),
+    ("metrics.address", "prometheus-metrics-addr"),
+    ("metrics.push-interval", "prometheus-push-interval"),
+    ("metrics.push-job-name", "prometheus-push-job-name"),
+    (
+        "metrics.push-instance-label",
+        "prometheus-push-instance-label",
+    ),
+];
+
+/// Value of an option set in the config file.
+#[derive(Debug, PartialEq)]
+struct FileValue {
+    key: &'static str,
+    values: Vec<String>,
+}
+
+/// Gateway options, from the command line and the config file.
+pub struct Config<'a> {
+    args: ArgMatches<'a>,
+    file: HashMap<&'static str, FileValue>,
+}
+
+impl<'a> Config<'a> {
+    /// Create the configuration from the command line, loading the config
+    /// file if one is given.
+    pub fn new(args: ArgMatches<'a>) -> Result<Self> {
+        let file = match args.value_of("config") {
+            Some(path) => load_file(Path::new(path))?,
+            None => HashMap::new(),
+        };
+
+        Ok(Config { args, file })
+    }
+
+    /// Returns the value of an option, failing if it is not set.
+    pub fn value<T>(&self, flag: &str) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.value_opt(flag)?
+            .ok_or_else(|| anyhow!("missing value for {}", self.describe(flag)))
+    }
+
+    /// Returns the value of an option, if set.
+    pub fn value_opt<T>(&self, flag: &str) -> Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let value = match (self.args.occurrences_of(flag), self.file.get(flag)) {
+            (0, Some(file_value)) => Some(file_value.values.join(",")),
+            _ => self.args.value_of(flag).map(str::to_owned),
+        };
+
+        value
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|err| anyhow!("invalid value for {}: {}", self.source(flag), err))
+            })
+            .transpose()
+    }
+
+    /// Returns all values of an option that may be given multiple times.
+    pub fn values(&self, flag: &str) -> Vec<String> {
+        match (self.args.occurrences_of(flag), self.file.get(flag)) {
+            (0, Some(file_value)) => file_value.values.clone(),
+            _ => self
+                .args
+                .values_of(flag)
+                .into_iter()
+                .flatten()
+                .map(str::to_owned)
+                .collect(),
+        }
+    }
+
+    /// Returns the number of times a switch is given on the command line, or
+    /// its count in the config file.
+    pub fn occurrences(&self, flag: &str) -> Result<u64> {
+        match (self.args.occurrences_of(flag), self.file.get(flag)) {
+            (0, Some(_)) => self.value(flag),
+            (occurrences, _) => Ok(occurrences),
+        }
+    }
+
+    /// Returns where the value of an option was taken from.
+    pub fn source(&self, flag: &str) -> String {
+        match (self.args.occurrences_of(flag), self.file.get(flag)) {
+            (0, Some(file_value)) => format!("`{}` in the config file", file_value.key),
+            _ => format!("--{}", flag),
+        }
+    }
+
+    /// Returns how an option can be set.
+    pub fn describe(&self, flag: &str) -> String {
+        match KEYS.iter().find(|(_, key_flag)| *key_flag == flag) {
+            Some((key, _)) => format!("--{} (`{}` in the config file)", flag, key),
+            None => format!("--{}", flag),
+        }
+    }
+}
+
+fn load_file(path: &Path) -> Result<HashMap<&'static str, FileValue>> {
+    let data = fs::read_to_string(path)
+        .with_context(|| format!("failed to read config file {}", path.display()))?;
+    parse_file(&data).with_context(|| format!("invalid config file {}", path.display()))
+}
+
+fn parse_file(data: &str) -> Result<HashMap<&'static str, FileValue>> {
+    let table = match data.parse()? {
+        Value::Table(table) => table,
+        _ => bail!("expected a table"),
+    };
+
+    let mut options = HashMap::new();
+    flatten(None, table, &mut options)?;
+
+    Ok(options)
+}
+
+/// Collect the options set in a table of the config file.
+fn flatten(
+    section: Option<&str>,
+    table: toml::value::Table,
+    options: &mut HashMap<&'static str, FileValue>,
+) -> Result<()> {
+    for (name, value) in table {
+        let key = match section {
+            Some(section) => format!("{}.{}", section, name),
+            None => name,
+        };
+
+        match KEYS.iter().find(|(file_key, _)| *file_key == key) {
+            Some(&(key, flag)) => {
+                let values = match value {
+                    Value::Table(_) if flag != "rpc-method-cost" => None,
+                    ref value => to_strings(value),
+                };
+                let values = values.ok_or_else(|| {
+                    anyhow!(
+                        "invalid value for `{}`: unexpected {}",
+                        key,
+                        value.type_str()
+                    )
+                })?;
+                options.insert(flag, FileValue { key, values });
+            }
+            None => match value {
+                Value::Table(table) if section.is_none() => {
+                    flatten(Some(&key), table, options)?;
+                }
+                _ => bail!("unknown key `{}`", key),
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Convert an option value of the config file to the values of its flag.
+/// Arrays are converted to a value per element, and tables to a `KEY=VALUE`
+/// value per entry.
+fn to_strings(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::Array(array) => array.iter().map(to_string).collect(),
+        Value::Table(table) => table
+            .iter()
+            .map(|(name, value)| Some(format!("{}={}", name, to_string(value)?)))
+            .collect(),
+        value => Some(vec![to_string(value)?]),
+    }
+}
+
+fn to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Integer(value) => Some(value.to_string()),
+        Value::Float(value) => Some(value.to_string()),
+        Value::Boolean(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(options: &HashMap<&'static str, FileValue>, flag: &str) -> Vec<String> {
+        options[flag].values.clone()
+    }
+
+    #[test]
+    fn should_parse_config_file() {
+        let options = parse_file(
+            r#"
+            runtime-id = "00"
+            verbosity = 2
+
+            [http]
+            port = 8545
+            cors = ["https://a.example", "https://b.example"]
+
+            [ws]
+            origins = "all"
+
+            [rpc.method-costs]
+            eth_call = 5
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(values(&options, "runtime-id"), vec!["00"]);
+        assert_eq!(values(&options, "v"), vec!["2"]);
+        assert_eq!(values(&options, "http-port"), vec!["8545"]);
+        assert_eq!(
+            values(&options, "http-cors"),
+            vec!["https://a.example", "https://b.example"]
+        );
+        assert_eq!(values(&options, "ws-origins"), vec!["all"]);
+        assert_eq!(values(&options, "rpc-method-cost"), vec!["eth_call=5"]);
+        assert_eq!(options["http-port"].key, "http.port");
+    }
+
+    #[test]
+    fn should_name_invalid_keys() {
+        let err = parse_file("[http]\nprot = 8545").unwrap_err();
+        assert_eq!(err.to_string(), "unknown key `http.prot`");
+
+        let err = parse_file("[foo]\nport = 8545").unwrap_err();
+        assert_eq!(err.to_string(), "unknown key `foo.port`");
+
+        let err = parse_file("[http]\nport = { a = 1 }").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value for `http.port`: unexpected table"
+        );
+
+        let err = parse_file("[http]\nport = [[1]]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value for `http.port`: unexpected array"
+        );
+
+        assert!(parse_file("[http\nport = 1").is_err());
+    }
+}
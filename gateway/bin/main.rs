@@ -19,6 +19,7 @@
 #![deny(warnings)]
 
 extern crate fdlimit;
+extern crate hyper;
 extern crate signal_hook;
 #[macro_use]
 extern crate clap;
@@ -28,20 +29,26 @@ extern crate oasis_core_runtime;
 extern crate oasis_ethwasi_runtime_common;
 extern crate prometheus;
 extern crate slog;
+extern crate toml;
 extern crate web3_gateway;
 
+mod config;
 mod metrics;
 
-use std::{io::Read, net::SocketAddr, os::unix::net::UnixStream, time::Duration};
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use clap::{App, Arg};
 use fdlimit::raise_fd_limit;
-use slog::{error, info};
+use signal_hook::iterator::Signals;
+use slog::{error, info, warn};
 
-use oasis_core_runtime::common::logger::{get_logger, init_logger};
-use oasis_ethwasi_runtime_common::MIN_GAS_PRICE_GWEI;
-use web3_gateway::util;
+use oasis_core_runtime::common::{logger::init_logger, namespace::Namespace};
+use oasis_ethwasi_runtime_common::{BLOCK_GAS_LIMIT, MIN_GAS_PRICE_GWEI};
+use web3_gateway::{
+    logger::get_logger, util, AccessConfig, ApiSet, CacheConfig, FilterConfig, GatewayConfig,
+    QuotaConfig, RateLimitConfig, SimulatorConfig, TlsConfig, WsSessionConfig,
+};
 
 const METRICS_MODE_PULL: &str = "pull";
 const METRICS_MODE_PUSH: &str = "push";
@@ -52,21 +59,28 @@ fn main() -> Result<()> {
     raise_fd_limit();
 
     let gas_price = MIN_GAS_PRICE_GWEI.to_string();
+    let gas_cap = BLOCK_GAS_LIMIT.to_string();
 
     let args = App::new("Oasis Eth/WASI Runtime Web3 Gateway")
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .help("TOML config file setting any of the other options. Command line flags override the file.")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("runtime-id")
                 .long("runtime-id")
                 .help("Oasis Core runtime identifier for the runtime")
-                .takes_value(true)
-                .required(true),
+                .takes_value(true),
         )
         .arg(
             Arg::with_name("node-address")
                 .long("node-address")
-                .help("Oasis Core node address")
-                .takes_value(true)
-                .required(true),
+                .help("Oasis Core node address. May be given multiple times to fail over between nodes.")
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true),
         )
         .arg(
             Arg::with_name("http-port")
@@ -82,6 +96,27 @@ fn main() -> Result<()> {
                 .default_value("1")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("http-apis")
+                .long("http-apis")
+                .help("Comma-separated list of APIs served over HTTP: web3, net, eth, pubsub, oasis and admin, \"safe\" for all but admin or \"all\". The admin API is only available on a loopback interface or over IPC. An API can be removed with a leading \"-\", e.g. \"safe,-oasis\".")
+                .default_value("safe")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("http-max-connections")
+                .long("http-max-connections")
+                .help("Max number of concurrent HTTP connections.")
+                .default_value("10000")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("http-idle-timeout")
+                .long("http-idle-timeout")
+                .help("Time allowed for a client to send a request, including its headers, on an HTTP connection after it is opened or after its last response (in sec, 0 to disable).")
+                .default_value("60")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("ws-port")
                 .long("ws-port")
@@ -89,6 +124,13 @@ fn main() -> Result<()> {
                 .default_value("8546")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("ws-apis")
+                .long("ws-apis")
+                .help("Comma-separated list of APIs served over WebSocket, in the same format as --http-apis.")
+                .default_value("safe")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("ws-max-connections")
                 .long("ws-max-connections")
@@ -103,6 +145,170 @@ fn main() -> Result<()> {
                 .default_value("50")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("ws-idle-timeout")
+                .long("ws-idle-timeout")
+                .help("Time after which a WebSocket connection without requests or subscriptions is closed (in sec, 0 to disable).")
+                .default_value("300")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ws-ping-interval")
+                .long("ws-ping-interval")
+                .help("Interval between pings of a WebSocket connection, which is closed if it does not answer for two intervals (in sec, 0 to disable).")
+                .default_value("30")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ws-max-subscriptions")
+                .long("ws-max-subscriptions")
+                .help("Max number of subscriptions per WebSocket connection (0 for no limit).")
+                .default_value("100")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ws-max-queued-notifications")
+                .long("ws-max-queued-notifications")
+                .help("Max number of notifications queued for a subscription before its connection is closed (0 for no limit).")
+                .default_value("1024")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ws-max-logs-replay-depth")
+                .long("ws-max-logs-replay-depth")
+                .help("Max number of blocks behind the latest block a logs subscription can replay logs from (0 for no limit).")
+                .default_value("10000")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ws-max-logs-replays")
+                .long("ws-max-logs-replays")
+                .help("Max number of logs subscriptions replaying logs from a historical block at once (0 for no limit).")
+                .default_value("16")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ipc-path")
+                .long("ipc-path")
+                .help("Path of the Unix domain socket of the IPC server. If not set, the IPC server is disabled.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ipc-apis")
+                .long("ipc-apis")
+                .help("Comma-separated list of APIs served over IPC, in the same format as --http-apis.")
+                .default_value("safe")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ipc-permissions")
+                .long("ipc-permissions")
+                .help("Permissions of the IPC socket file, in octal.")
+                .default_value("600")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("http-client-rate-limit")
+                .long("http-client-rate-limit")
+                .help("Max HTTP requests/second allowed per client IP (0 for no limit).")
+                .default_value("0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ws-client-rate-limit")
+                .long("ws-client-rate-limit")
+                .help("Max WebSocket requests/second allowed per client IP, across all of its connections (0 for no limit).")
+                .default_value("0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ws-client-max-connections")
+                .long("ws-client-max-connections")
+                .help("Max number of concurrent WebSocket connections per client IP (0 for no limit).")
+                .default_value("0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("trusted-proxies")
+                .long("trusted-proxies")
+                .help("Number of trusted reverse proxies setting X-Forwarded-For, used to determine the client IP for per-client limits (0 to use the peer address).")
+                .default_value("0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rpc-client-budget")
+                .long("rpc-client-budget")
+                .help("Max JSON-RPC request cost/second allowed per client (0 for no limit).")
+                .default_value("0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rpc-default-cost")
+                .long("rpc-default-cost")
+                .help("Cost of a JSON-RPC call to a method without a configured cost.")
+                .default_value("1")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rpc-method-cost")
+                .long("rpc-method-cost")
+                .help("Cost of a JSON-RPC call to a method, as METHOD=COST.")
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("api-keys")
+                .long("api-keys")
+                .help("JSON file with the API keys required to access the gateway, reloaded on SIGHUP. If not set, no API key is required.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("http-cors")
+                .long("http-cors")
+                .help("Comma-separated list of origins allowed by CORS for the HTTP server, or \"all\" or \"none\".")
+                .default_value("all")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("http-hosts")
+                .long("http-hosts")
+                .help("Comma-separated list of Host headers allowed by the HTTP server in addition to its own address, or \"all\" or \"none\".")
+                .default_value("all")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ws-origins")
+                .long("ws-origins")
+                .help("Comma-separated list of origins allowed to open WebSocket connections, or \"all\" or \"none\". Connections without an Origin header are always allowed.")
+                .default_value("all")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ws-hosts")
+                .long("ws-hosts")
+                .help("Comma-separated list of Host headers allowed by the WebSocket server in addition to its own address, or \"all\" or \"none\".")
+                .default_value("all")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tls-cert")
+                .long("tls-cert")
+                .help("PEM file with the TLS certificate chain. If set together with --tls-key, the HTTP and WebSocket servers are served over TLS. Reloaded on SIGHUP.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tls-key")
+                .long("tls-key")
+                .help("PEM file with the TLS private key. Reloaded on SIGHUP.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tls-client-ca")
+                .long("tls-client-ca")
+                .help("PEM file with the CA certificates of TLS clients. If set, clients must present a certificate signed by one of them. Reloaded on SIGHUP.")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("pubsub-interval")
                 .long("pubsub-interval")
@@ -124,6 +330,97 @@ fn main() -> Result<()> {
                 .default_value("10")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("rpc-timeout")
+                .long("rpc-timeout")
+                .help("Max time allowed for a JSON-RPC request, after which its node reads are cancelled (in sec). Requests submitting transactions are not timed out, so that they respond with the result of the submission.")
+                .default_value("30")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rpc-gas-cap")
+                .long("rpc-gas-cap")
+                .help("Max gas allowed for eth_call and eth_estimateGas simulations.")
+                .default_value(&gas_cap)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("simulation-timeout")
+                .long("simulation-timeout")
+                .help("Max time allowed for a single transaction simulation (in sec).")
+                .default_value("5")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("simulator-max-queue")
+                .long("simulator-max-queue")
+                .help("Max number of queued and running transaction simulations.")
+                .default_value("128")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("simulator-max-bundle")
+                .long("simulator-max-bundle")
+                .help("Max number of calls in a single eth_callMany bundle.")
+                .default_value("32")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("block-cache-size")
+                .long("block-cache-size")
+                .help("Max estimated size of the blocks kept in the cache (in bytes).")
+                .default_value("16777216")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("transaction-cache-size")
+                .long("transaction-cache-size")
+                .help("Max estimated size of the decoded transactions kept in the cache (in bytes).")
+                .default_value("33554432")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("receipt-cache-size")
+                .long("receipt-cache-size")
+                .help("Max estimated size of the transaction receipts kept in the cache (in bytes).")
+                .default_value("33554432")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("filter-lifetime")
+                .long("filter-lifetime")
+                .help("Time after which a filter that is not polled is removed (in sec).")
+                .default_value("60")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("filter-max-per-client")
+                .long("filter-max-per-client")
+                .help("Max number of filters installed per client (0 for no limit).")
+                .default_value("100")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("filter-max-total")
+                .long("filter-max-total")
+                .help("Max number of filters installed in total, including those of clients with an unknown address (0 for no limit).")
+                .default_value("10000")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("filter-max-catch-up")
+                .long("filter-max-catch-up")
+                .help("Max number of blocks covered by a single poll of a filter (0 for no limit).")
+                .default_value("1000")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("health-max-block-age")
+                .long("health-max-block-age")
+                .help("Max age of the latest block for the gateway to be reported as ready (in sec).")
+                .default_value("300")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("interface")
                 .long("interface")
@@ -148,20 +445,17 @@ fn main() -> Result<()> {
             Arg::with_name("prometheus-push-job-name")
             .long("prometheus-push-job-name")
             .help("Prometheus `job` name used if using push mode.")
-            .required_if("prometheus-mode", METRICS_MODE_PUSH)
             .takes_value(true),
         )
         .arg(
             Arg::with_name("prometheus-push-instance-label")
             .long("prometheus-push-instance-label")
             .help("Prometheus `instance` label used if using push mode.")
-            .required_if("prometheus-mode", METRICS_MODE_PUSH)
             .takes_value(true),
         )
         .arg(
             Arg::with_name("prometheus-metrics-addr")
             .long("prometheus-metrics-addr")
-            .requires("prometheus-mode")
             .help("If pull mode: A SocketAddr (as a string) from which to serve metrics to Prometheus. If push mode: prometheus 'pushgateway' address.")
             .takes_value(true)
         )
@@ -174,7 +468,9 @@ fn main() -> Result<()> {
         )
         .get_matches();
 
-    let log_level = match args.occurrences_of("v") {
+    let config = config::Config::new(args)?;
+
+    let log_level = match config.occurrences("v")? {
         0 => log::Level::Error,
         1 => log::Level::Warn,
         2 => log::Level::Debug,
@@ -186,55 +482,213 @@ fn main() -> Result<()> {
     init_logger(log_level);
     let logger = get_logger("gateway/main");
 
-    let num_threads = value_t!(args, "threads", usize)?;
-    let interface = value_t!(args, "interface", String)?;
-    let http_port = value_t!(args, "http-port", u16)?;
-    let ws_port = value_t!(args, "ws-port", u16)?;
-    let ws_max_connections = value_t!(args, "ws-max-connections", usize)?;
-    let ws_rate_limit = value_t!(args, "ws-rate-limit", usize)?;
-    let pubsub_interval_secs = value_t!(args, "pubsub-interval", u64)?;
-    let gas_price = util::gwei_to_wei(value_t!(args, "gas-price", u64)?);
-    let jsonrpc_max_batch_size = value_t!(args, "jsonrpc-max-batch", usize)?;
-
-    // Metrics.
-    match args.value_of("prometheus-mode") {
-        Some(METRICS_MODE_PULL) => {
-            if let Ok(address) = value_t!(args, "prometheus-metrics-addr", SocketAddr) {
-                metrics::start(metrics::Config::Pull { address });
+    let runtime_id: Namespace = config.value("runtime-id")?;
+    let node_addresses = config.values("node-address");
+    if node_addresses.is_empty() {
+        bail!("missing value for {}", config.describe("node-address"));
+    }
+    let num_threads = config.value("threads")?;
+    let interface: String = config.value("interface")?;
+    let http_port = config.value("http-port")?;
+    let http_apis: ApiSet = config.value("http-apis")?;
+    let http_max_connections = config.value("http-max-connections")?;
+    let http_idle_timeout = Duration::from_secs(config.value("http-idle-timeout")?);
+    let ws_port = config.value("ws-port")?;
+    let ws_apis: ApiSet = config.value("ws-apis")?;
+    let ws_max_connections = config.value("ws-max-connections")?;
+    let ws_connection_rate_limit = config.value("ws-rate-limit")?;
+    let ws_session_config = WsSessionConfig {
+        idle_timeout: Duration::from_secs(config.value("ws-idle-timeout")?),
+        ping_interval: Duration::from_secs(config.value("ws-ping-interval")?),
+        max_subscriptions: config.value("ws-max-subscriptions")?,
+        max_queued_notifications: config.value("ws-max-queued-notifications")?,
+        max_logs_replay_depth: config.value("ws-max-logs-replay-depth")?,
+        max_logs_replays: config.value("ws-max-logs-replays")?,
+    };
+    let ipc_path: Option<PathBuf> = config.value_opt("ipc-path")?;
+    let ipc_apis: ApiSet = config.value("ipc-apis")?;
+    let ipc_permissions = u32::from_str_radix(&config.value::<String>("ipc-permissions")?, 8)
+        .map_err(|err| {
+            anyhow!(
+                "invalid value for {}: {}",
+                config.source("ipc-permissions"),
+                err
+            )
+        })?;
+    let rate_limit = RateLimitConfig {
+        http_max_req_per_sec: config.value("http-client-rate-limit")?,
+        ws_max_req_per_sec: config.value("ws-client-rate-limit")?,
+        ws_max_connections: config.value("ws-client-max-connections")?,
+        trusted_proxies: config.value("trusted-proxies")?,
+    };
+    let mut quota_config = QuotaConfig {
+        budget: config.value("rpc-client-budget")?,
+        default_cost: config.value("rpc-default-cost")?,
+        ..Default::default()
+    };
+    for method_cost in config.values("rpc-method-cost") {
+        let mut parts = method_cost.splitn(2, '=');
+        match (parts.next(), parts.next().map(str::parse)) {
+            (Some(method), Some(Ok(cost))) => {
+                quota_config.method_costs.insert(method.to_owned(), cost);
+            }
+            _ => {
+                return Err(anyhow!(
+                    "invalid value for {}: invalid method cost: {}",
+                    config.source("rpc-method-cost"),
+                    method_cost
+                ))
             }
         }
-        Some(METRICS_MODE_PUSH) => {
-            if let Ok(address) = value_t!(args, "prometheus-metrics-addr", String) {
-                let interval = value_t!(args, "prometheus-push-interval", u64).unwrap_or(5);
-                let job_name = value_t!(args, "prometheus-push-job-name", String).unwrap();
-                let instance_name =
-                    value_t!(args, "prometheus-push-instance-label", String).unwrap();
+    }
+    let api_keys_path: Option<PathBuf> = config.value_opt("api-keys")?;
+    let access = AccessConfig {
+        http_cors: AccessConfig::parse_list(&config.value::<String>("http-cors")?),
+        http_hosts: AccessConfig::parse_list(&config.value::<String>("http-hosts")?),
+        ws_origins: AccessConfig::parse_list(&config.value::<String>("ws-origins")?),
+        ws_hosts: AccessConfig::parse_list(&config.value::<String>("ws-hosts")?),
+    };
+    let unrestricted: Vec<_> = [
+        ("http-cors", &access.http_cors),
+        ("http-hosts", &access.http_hosts),
+        ("ws-origins", &access.ws_origins),
+        ("ws-hosts", &access.ws_hosts),
+    ]
+    .iter()
+    .filter(|&&(_, list)| list.is_none())
+    .map(|&(flag, _)| config.describe(flag))
+    .collect();
+    if !unrestricted.is_empty() {
+        warn!(logger, "The RPC servers allow all origins or hosts, so any website can send requests to the gateway from a browser";
+            "unrestricted" => unrestricted.join(", "),
+        );
+    }
+    let tls_config = match (config.value_opt("tls-cert")?, config.value_opt("tls-key")?) {
+        (Some(cert), Some(key)) => Some(TlsConfig {
+            cert,
+            key,
+            client_ca: config.value_opt("tls-client-ca")?,
+        }),
+        (None, None) => {
+            if config.value_opt::<String>("tls-client-ca")?.is_some() {
+                bail!(
+                    "{} requires {} and {}",
+                    config.source("tls-client-ca"),
+                    config.describe("tls-cert"),
+                    config.describe("tls-key")
+                );
+            }
+            None
+        }
+        (Some(_), None) => bail!(
+            "{} requires {}",
+            config.source("tls-cert"),
+            config.describe("tls-key")
+        ),
+        (None, Some(_)) => bail!(
+            "{} requires {}",
+            config.source("tls-key"),
+            config.describe("tls-cert")
+        ),
+    };
+    let pubsub_interval_secs = config.value("pubsub-interval")?;
+    let gas_price = util::gwei_to_wei(config.value("gas-price")?);
+    let jsonrpc_max_batch_size = config.value("jsonrpc-max-batch")?;
+    let rpc_timeout = Duration::from_secs(config.value("rpc-timeout")?);
+    let simulator_config = SimulatorConfig {
+        gas_cap: config.value::<u64>("rpc-gas-cap")?.into(),
+        timeout: Duration::from_secs(config.value("simulation-timeout")?),
+        max_queue_size: config.value("simulator-max-queue")?,
+        max_bundle_size: config.value("simulator-max-bundle")?,
+    };
+    let cache_config = CacheConfig {
+        blocks: config.value("block-cache-size")?,
+        transactions: config.value("transaction-cache-size")?,
+        receipts: config.value("receipt-cache-size")?,
+    };
+    let filter_config = FilterConfig {
+        lifetime: Duration::from_secs(config.value("filter-lifetime")?),
+        max_per_client: config.value("filter-max-per-client")?,
+        max_total: config.value("filter-max-total")?,
+        max_catch_up: config.value("filter-max-catch-up")?,
+    };
+    let max_block_age = Duration::from_secs(config.value("health-max-block-age")?);
 
-                metrics::start(metrics::Config::Push {
-                    address,
-                    period: Duration::from_secs(interval),
-                    job_name,
-                    instance_name,
-                });
+    // Metrics.
+    let metrics_config = match config.value_opt::<String>("prometheus-mode")?.as_deref() {
+        Some(METRICS_MODE_PULL) => config
+            .value_opt("prometheus-metrics-addr")?
+            .map(|address: SocketAddr| metrics::Config::Pull { address }),
+        Some(METRICS_MODE_PUSH) => match config.value_opt("prometheus-metrics-addr")? {
+            Some(address) => Some(metrics::Config::Push {
+                address,
+                period: Duration::from_secs(
+                    config.value_opt("prometheus-push-interval")?.unwrap_or(5),
+                ),
+                job_name: config.value("prometheus-push-job-name")?,
+                instance_name: config.value("prometheus-push-instance-label")?,
+            }),
+            None => None,
+        },
+        Some(mode) => bail!(
+            "invalid value for {}: expected {} or {}, got {}",
+            config.source("prometheus-mode"),
+            METRICS_MODE_PULL,
+            METRICS_MODE_PUSH,
+            mode
+        ),
+        None => {
+            if config
+                .value_opt::<String>("prometheus-metrics-addr")?
+                .is_some()
+            {
+                bail!(
+                    "{} requires {}",
+                    config.source("prometheus-metrics-addr"),
+                    config.describe("prometheus-mode")
+                );
             }
+            None
         }
-        _ => (),
+    };
+    metrics::register_build_info(&config.value::<String>("runtime-id")?);
+    if let Some(metrics_config) = metrics_config {
+        metrics::start(metrics_config);
     }
 
     info!(logger, "Starting the web3 gateway");
 
-    let client = web3_gateway::start(
-        args,
+    let client = web3_gateway::start(GatewayConfig {
+        node_addresses,
+        runtime_id,
         pubsub_interval_secs,
-        &interface,
+        interface,
         http_port,
         num_threads,
+        http_apis,
+        http_max_connections,
+        http_idle_timeout,
         ws_port,
+        ws_apis,
         ws_max_connections,
-        ws_rate_limit,
+        ws_connection_rate_limit,
+        ws_session_config,
+        ipc_path,
+        ipc_apis,
+        ipc_permissions,
+        rate_limit,
+        quota_config,
+        api_keys_path,
+        access,
+        tls_config,
         gas_price,
         jsonrpc_max_batch_size,
-    );
+        rpc_timeout,
+        simulator_config,
+        cache_config,
+        filter_config,
+        max_block_age,
+    });
 
     let client = match client {
         Ok(client) => client,
@@ -246,14 +700,20 @@ fn main() -> Result<()> {
 
     info!(logger, "Web3 gateway is running");
 
-    // Register a self-pipe for handing the SIGTERM and SIGINT signals.
-    let (mut read, write) = UnixStream::pair()?;
-    signal_hook::pipe::register(signal_hook::SIGINT, write.try_clone()?)?;
-    signal_hook::pipe::register(signal_hook::SIGTERM, write.try_clone()?)?;
+    // Reload on SIGHUP, and wait for the SIGTERM and SIGINT signals.
+    let signals = Signals::new(&[
+        signal_hook::SIGHUP,
+        signal_hook::SIGINT,
+        signal_hook::SIGTERM,
+    ])?;
+    for signal in signals.forever() {
+        if signal != signal_hook::SIGHUP {
+            break;
+        }
 
-    // Wait for signal.
-    let mut buff = [0];
-    read.read_exact(&mut buff)?;
+        info!(logger, "Reloading the web3 gateway configuration");
+        client.reload();
+    }
 
     info!(logger, "The web3 gateway is shutting down");
 
@@ -1,9 +1,15 @@
 //! Prometheus metrics.
 use std::{net, thread, time::Duration};
 
-use oasis_core_runtime::common::logger::get_logger;
-use prometheus::{self, labels};
+use hyper::{
+    header::CONTENT_TYPE, rt::Future, service::service_fn_ok, Body, Method, Request, Response,
+    Server, StatusCode,
+};
+use prometheus::{
+    self, labels, process_collector::ProcessCollector, register_int_gauge_vec, Encoder, TextEncoder,
+};
 use slog::{error, info, warn, Logger};
+use web3_gateway::logger::get_logger;
 
 /// Metrics service configuration.
 pub enum Config {
@@ -18,6 +24,55 @@ pub enum Config {
     },
 }
 
+/// Register process metrics and the build info gauge.
+pub fn register_build_info(runtime_id: &str) {
+    let logger = get_logger("gateway/metrics");
+
+    if let Err(err) = prometheus::register(Box::new(ProcessCollector::for_self())) {
+        warn!(logger, "Cannot register process metrics"; "err" => ?err);
+    }
+
+    let build_info = register_int_gauge_vec!(
+        "web3_gateway_build_info",
+        "Gateway build information",
+        &["version", "runtime_id"]
+    )
+    .unwrap();
+    let version = format!(
+        "{}-{}",
+        env!("CARGO_PKG_VERSION"),
+        env!("CARGO_PKG_GIT_REV") // set in build.rs
+    );
+    build_info.with_label_values(&[&version, runtime_id]).set(1);
+}
+
+/// Serves the metrics gathered from the default registry.
+fn serve_metrics(req: Request<Body>) -> Response<Body> {
+    if req.method() != Method::GET || req.uri().path() != "/metrics" {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        return response;
+    }
+
+    let encoder = TextEncoder::new();
+    let mut buffer = vec![];
+    if let Err(err) = encoder.encode(&prometheus::gather(), &mut buffer) {
+        let mut response = Response::new(Body::from(format!("{}", err)));
+        *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+        return response;
+    }
+
+    let mut response = Response::new(Body::from(buffer));
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        encoder
+            .format_type()
+            .parse()
+            .expect("format type must be a valid header value"),
+    );
+    response
+}
+
 /// Pushes metrics to Prometheus pushgateway.
 fn push_metrics(logger: Logger, address: &str, job_name: &str, instance_name: &str) {
     prometheus::push_metrics(
@@ -62,9 +117,20 @@ pub fn start(cfg: Config) {
                     push_metrics(logger.clone(), &address, &job_name, &instance_name);
                 }
             }
-            Config::Pull { .. } => {
-                // TODO: Pull.
-                error!(logger, "Pull mode not supported");
+            Config::Pull { address } => {
+                info!(logger, "Configured in pull mode"; "address" => ?address);
+
+                let server = match Server::try_bind(&address) {
+                    Ok(builder) => builder.serve(|| service_fn_ok(serve_metrics)),
+                    Err(err) => {
+                        error!(logger, "Cannot bind metrics server"; "err" => ?err);
+                        return;
+                    }
+                };
+
+                hyper::rt::run(server.map_err(move |err| {
+                    error!(logger, "Metrics server failed"; "err" => ?err);
+                }));
             }
         }
     });
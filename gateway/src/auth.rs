@@ -0,0 +1,240 @@
+//! API key authentication.
+//!
+//! Keys are loaded from a JSON file containing a list of keys:
+//!
+//! ```json
+//! [
+//!   { "name": "dapp", "key": "secret", "apis": ["eth", "net"], "rate_limit": 100 }
+//! ]
+//! ```
+//!
+//! `apis` restricts the API namespaces the key may call, and `rate_limit`
+//! limits the number of calls/second made with the key (0 for no limit). Both
+//! are optional.
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use parking_lot::RwLock;
+
+use crate::{ratelimit::RateLimiter, rpc_apis::Api};
+
+/// Header carrying the API key of HTTP and WebSocket requests.
+pub const API_KEY_HEADER: &str = "x-api-key";
+/// Query parameter carrying the API key of WebSocket connections.
+pub const API_KEY_QUERY_PARAM: &str = "apikey";
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ApiKeyEntry {
+    name: String,
+    key: String,
+    #[serde(default)]
+    apis: Option<Vec<String>>,
+    #[serde(default)]
+    rate_limit: Option<usize>,
+}
+
+/// API key.
+pub struct ApiKey {
+    /// Name of the key, used to refer to it without revealing the key.
+    pub name: String,
+    apis: Option<HashSet<Api>>,
+    limiter: Option<RateLimiter<()>>,
+}
+
+impl ApiKey {
+    /// Returns true if the key may call methods of the API.
+    pub fn allows(&self, api: &Api) -> bool {
+        self.apis.as_ref().map_or(true, |apis| apis.contains(api))
+    }
+
+    /// Count calls made with the key. Returns false if the key exceeded its
+    /// rate.
+    pub fn check_rate(&self, calls: usize) -> bool {
+        self.limiter
+            .as_ref()
+            .map_or(true, |limiter| limiter.take((), calls as f64))
+    }
+}
+
+#[derive(Default)]
+struct Keys {
+    by_key: HashMap<String, Arc<ApiKey>>,
+    by_name: HashMap<String, Arc<ApiKey>>,
+}
+
+/// API keys loaded from a file.
+pub struct ApiKeys {
+    path: PathBuf,
+    keys: RwLock<Keys>,
+}
+
+impl ApiKeys {
+    /// Load API keys from a file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let api_keys = ApiKeys {
+            path: path.to_owned(),
+            keys: RwLock::new(Keys::default()),
+        };
+        api_keys.reload()?;
+
+        Ok(api_keys)
+    }
+
+    /// Reload API keys from the file. The loaded keys are kept if the file is
+    /// invalid.
+    pub fn reload(&self) -> Result<()> {
+        let data = fs::read_to_string(&self.path)
+            .with_context(|| format!("failed to read API keys from {}", self.path.display()))?;
+        *self.keys.write() = parse_keys(&data)?;
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub fn from_json(data: &str) -> Result<Self> {
+        Ok(ApiKeys {
+            path: PathBuf::new(),
+            keys: RwLock::new(parse_keys(data)?),
+        })
+    }
+
+    /// Returns the number of loaded keys.
+    pub fn count(&self) -> usize {
+        self.keys.read().by_key.len()
+    }
+
+    /// Returns the API key with the given key.
+    pub fn authenticate(&self, key: &str) -> Option<Arc<ApiKey>> {
+        self.keys.read().by_key.get(key).cloned()
+    }
+
+    /// Returns the API key with the given name.
+    pub fn get(&self, name: &str) -> Option<Arc<ApiKey>> {
+        self.keys.read().by_name.get(name).cloned()
+    }
+}
+
+fn parse_keys(data: &str) -> Result<Keys> {
+    let entries: Vec<ApiKeyEntry> = serde_json::from_str(data).context("malformed API keys")?;
+
+    let mut keys = Keys::default();
+    for entry in entries {
+        ensure!(!entry.key.is_empty(), "empty API key {}", entry.name);
+        let apis = match entry.apis {
+            Some(apis) => Some(
+                apis.iter()
+                    .map(|api| api.parse())
+                    .collect::<Result<HashSet<Api>, String>>()
+                    .map_err(|err| anyhow!("API key {}: {}", entry.name, err))?,
+            ),
+            None => None,
+        };
+        let api_key = Arc::new(ApiKey {
+            name: entry.name.clone(),
+            apis,
+            limiter: entry
+                .rate_limit
+                .filter(|&rate_limit| rate_limit > 0)
+                .map(RateLimiter::new),
+        });
+
+        ensure!(
+            keys.by_name
+                .insert(entry.name.clone(), api_key.clone())
+                .is_none(),
+            "duplicate API key name {}",
+            entry.name
+        );
+        ensure!(
+            keys.by_key.insert(entry.key, api_key).is_none(),
+            "duplicate API key {}",
+            entry.name
+        );
+    }
+
+    Ok(keys)
+}
+
+/// Returns the API key of an HTTP request, given either in the API key header
+/// or as the only segment of the request path.
+pub fn http_request_key<'a>(header: Option<&'a str>, path: &'a str) -> Option<&'a str> {
+    header.or_else(|| {
+        let path = path.trim_start_matches('/');
+        if path.is_empty() || path.contains('/') {
+            None
+        } else {
+            Some(path)
+        }
+    })
+}
+
+/// Returns the API key of a WebSocket connection, given either in the API
+/// key header or as a query parameter of the requested resource.
+pub fn ws_request_key<'a>(header: Option<&'a str>, resource: &'a str) -> Option<&'a str> {
+    header.or_else(|| {
+        let query = resource.splitn(2, '?').nth(1)?;
+        query.split('&').find_map(|param| {
+            let mut parts = param.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(API_KEY_QUERY_PARAM), Some(key)) => Some(key),
+                _ => None,
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_keys() {
+        let keys = parse_keys(
+            r#"[
+                {"name": "all", "key": "secret1"},
+                {"name": "eth", "key": "secret2", "apis": ["eth", "pubsub"], "rate_limit": 1}
+            ]"#,
+        )
+        .unwrap();
+
+        let all = keys.by_key.get("secret1").unwrap();
+        assert_eq!(all.name, "all");
+        assert!(all.allows(&Api::Oasis));
+        assert!(all.check_rate(100));
+
+        let eth = keys.by_name.get("eth").unwrap();
+        assert!(eth.allows(&Api::Eth));
+        assert!(eth.allows(&Api::EthPubSub));
+        assert!(!eth.allows(&Api::Oasis));
+        assert!(eth.check_rate(1));
+        assert!(!eth.check_rate(1));
+    }
+
+    #[test]
+    fn should_reject_invalid_keys() {
+        assert!(parse_keys(r#"[{"name": "a", "key": ""}]"#).is_err());
+        assert!(parse_keys(r#"[{"name": "a", "key": "k", "apis": ["foo"]}]"#).is_err());
+        assert!(parse_keys(r#"[{"name": "a", "key": "k1"}, {"name": "a", "key": "k2"}]"#).is_err());
+        assert!(parse_keys(r#"[{"name": "a", "key": "k"}, {"name": "b", "key": "k"}]"#).is_err());
+        assert!(parse_keys(r#"[{"name": "a", "key": "k", "foo": 1}]"#).is_err());
+    }
+
+    #[test]
+    fn should_extract_request_keys() {
+        assert_eq!(http_request_key(Some("header"), "/path"), Some("header"));
+        assert_eq!(http_request_key(None, "/path"), Some("path"));
+        assert_eq!(http_request_key(None, "/"), None);
+        assert_eq!(http_request_key(None, "/a/b"), None);
+
+        assert_eq!(ws_request_key(Some("header"), "/?apikey=q"), Some("header"));
+        assert_eq!(ws_request_key(None, "/?foo=1&apikey=q"), Some("q"));
+        assert_eq!(ws_request_key(None, "/?foo=1"), None);
+        assert_eq!(ws_request_key(None, "/"), None);
+    }
+}
@@ -0,0 +1,284 @@
+//! Caching of node lookups.
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    hash::Hash,
+    mem,
+    sync::{Arc, Weak},
+};
+
+use anyhow::{anyhow, Error};
+use futures::{future::Shared, prelude::*};
+use lazy_static::lazy_static;
+use lru_cache::LruCache;
+use parking_lot::Mutex;
+use prometheus::{labels, register_int_counter_vec, IntCounterVec};
+
+// Metrics.
+lazy_static! {
+    pub static ref CACHE_HITS: IntCounterVec = register_int_counter_vec!(
+        "web3_gateway_cache_hits",
+        "Number of lookups served from a cache",
+        &["cache"]
+    )
+    .unwrap();
+    pub static ref CACHE_MISSES: IntCounterVec = register_int_counter_vec!(
+        "web3_gateway_cache_misses",
+        "Number of lookups not found in a cache",
+        &["cache"]
+    )
+    .unwrap();
+    static ref COALESCED_LOOKUPS: IntCounterVec = register_int_counter_vec!(
+        "web3_gateway_coalesced_lookups",
+        "Number of lookups merged into an identical in-flight lookup",
+        &["lookup"]
+    )
+    .unwrap();
+}
+
+/// Sizes of the caches of immutable chain data, in bytes.
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    /// Max estimated size of cached blocks.
+    pub blocks: usize,
+    /// Max estimated size of cached decoded transactions.
+    pub transactions: usize,
+    /// Max estimated size of cached decoded receipts.
+    pub receipts: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            blocks: 16 * 1024 * 1024,
+            transactions: 32 * 1024 * 1024,
+            receipts: 32 * 1024 * 1024,
+        }
+    }
+}
+
+/// Estimated memory used by a value, including the data it owns.
+pub trait ByteSize {
+    fn byte_size(&self) -> usize;
+}
+
+impl ByteSize for u64 {
+    fn byte_size(&self) -> usize {
+        mem::size_of::<Self>()
+    }
+}
+
+/// Bounded cache evicting the least recently used entries.
+pub struct Lru<K: Eq + Hash, V> {
+    name: &'static str,
+    capacity: usize,
+    entries: Mutex<SizedEntries<K, V>>,
+}
+
+struct SizedEntries<K: Eq + Hash, V> {
+    entries: LruCache<K, V>,
+    /// Estimated size of the cached entries, in bytes.
+    size: usize,
+}
+
+impl<K: Eq + Hash, V: Clone + ByteSize> Lru<K, V> {
+    /// Create a cache holding entries of an estimated size of up to
+    /// `capacity` bytes, whose metrics are labeled with `name`.
+    pub fn new(name: &'static str, capacity: usize) -> Self {
+        Lru {
+            name,
+            capacity,
+            entries: Mutex::new(SizedEntries {
+                entries: LruCache::new(usize::max_value()),
+                size: 0,
+            }),
+        }
+    }
+
+    /// Returns the cached value of `key`, if any.
+    pub fn get(&self, key: &K) -> Option<V> {
+        let value = self.entries.lock().entries.get_mut(key).cloned();
+        match value {
+            Some(_) => CACHE_HITS.with(&labels! {"cache" => self.name,}).inc(),
+            None => CACHE_MISSES.with(&labels! {"cache" => self.name,}).inc(),
+        }
+
+        value
+    }
+
+    /// Cache the value of `key`, evicting the least recently used entries
+    /// until the cache fits its capacity.
+    pub fn insert(&self, key: K, value: V) {
+        let size = Self::entry_size(&value);
+        if size > self.capacity {
+            return;
+        }
+
+        let mut cache = self.entries.lock();
+        if let Some(previous) = cache.entries.insert(key, value) {
+            cache.size -= Self::entry_size(&previous);
+        }
+        cache.size += size;
+        while cache.size > self.capacity {
+            match cache.entries.remove_lru() {
+                Some((_, evicted)) => cache.size -= Self::entry_size(&evicted),
+                None => break,
+            }
+        }
+    }
+
+    fn entry_size(value: &V) -> usize {
+        mem::size_of::<K>() + value.byte_size()
+    }
+}
+
+type SharedLookup<T> = Shared<Box<dyn Future<Item = T, Error = Arc<Error>> + Send>>;
+
+/// Merges concurrent identical lookups into a single request.
+pub struct Coalescer<K, T> {
+    name: &'static str,
+    in_flight: Arc<Mutex<HashMap<K, SharedLookup<T>>>>,
+}
+
+impl<K, T> Coalescer<K, T>
+where
+    K: Clone + Eq + Hash + Send + 'static,
+    T: Clone + Send + Sync + 'static,
+{
+    /// Create a coalescer, whose metrics are labeled with `name`.
+    pub fn new(name: &'static str) -> Self {
+        Coalescer {
+            name,
+            in_flight: Default::default(),
+        }
+    }
+
+    /// Look up `key`, joining the in-flight lookup of the same key if there is
+    /// one, or starting one with `lookup` otherwise.
+    pub fn get<L, F>(&self, key: K, lookup: L) -> impl Future<Item = T, Error = Error>
+    where
+        L: FnOnce() -> F,
+        F: Future<Item = T, Error = Error> + Send + 'static,
+    {
+        let shared = match self.in_flight.lock().entry(key) {
+            Entry::Occupied(entry) => {
+                COALESCED_LOOKUPS
+                    .with(&labels! {"lookup" => self.name,})
+                    .inc();
+                entry.get().clone()
+            }
+            Entry::Vacant(entry) => {
+                // The lookup is no longer in flight once it completes.
+                let in_flight = Arc::downgrade(&self.in_flight);
+                let key = entry.key().clone();
+                let lookup: Box<dyn Future<Item = T, Error = Arc<Error>> + Send> =
+                    Box::new(lookup().then(move |result| {
+                        if let Some(in_flight) = Weak::upgrade(&in_flight) {
+                            in_flight.lock().remove(&key);
+                        }
+                        result.map_err(Arc::new)
+                    }));
+                entry.insert(lookup.shared()).clone()
+            }
+        };
+
+        shared
+            .map(|item| (*item).clone())
+            .map_err(|err| anyhow!("{:#}", **err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::{future, sync::oneshot};
+
+    use super::*;
+
+    /// Value whose estimated size is its length.
+    #[derive(Clone, Debug, PartialEq)]
+    struct Text(&'static str);
+
+    impl ByteSize for Text {
+        fn byte_size(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    #[test]
+    fn should_evict_least_recently_used() {
+        // Entries take the size of their u8 key and their value.
+        let cache = Lru::new("test", 12);
+        cache.insert(1u8, Text("one"));
+        cache.insert(2, Text("two"));
+        cache.insert(3, Text("six"));
+        assert_eq!(cache.get(&1), Some(Text("one")));
+
+        cache.insert(4, Text("four"));
+        assert_eq!(cache.get(&1), Some(Text("one")));
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&3), None);
+        assert_eq!(cache.get(&4), Some(Text("four")));
+
+        // Replaced entries no longer count towards the size.
+        cache.insert(4, Text("five"));
+        cache.insert(5, Text("v"));
+        assert_eq!(cache.get(&1), Some(Text("one")));
+        assert_eq!(cache.get(&4), Some(Text("five")));
+        assert_eq!(cache.get(&5), Some(Text("v")));
+
+        // Entries larger than the cache are not cached.
+        cache.insert(6, Text("larger than the cache"));
+        assert_eq!(cache.get(&6), None);
+        assert_eq!(cache.get(&1), Some(Text("one")));
+
+        let disabled = Lru::new("test", 0);
+        disabled.insert(1u8, Text("one"));
+        assert_eq!(disabled.get(&1), None);
+    }
+
+    #[test]
+    fn should_coalesce_in_flight_lookups() {
+        let coalescer = Coalescer::new("test");
+        let lookups = AtomicUsize::new(0);
+        let (sender, receiver) = oneshot::channel();
+
+        let first = coalescer.get(1, || {
+            lookups.fetch_add(1, Ordering::SeqCst);
+            receiver.map_err(Error::from)
+        });
+        let second = coalescer.get(1, || {
+            lookups.fetch_add(1, Ordering::SeqCst);
+            future::ok(0)
+        });
+        let other = coalescer.get(2, || {
+            lookups.fetch_add(1, Ordering::SeqCst);
+            future::ok(2)
+        });
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+
+        sender.send(1).unwrap();
+        assert_eq!(first.join3(second, other).wait().unwrap(), (1, 1, 2));
+
+        // the completed lookup is no longer joined
+        let third = coalescer.get(1, || {
+            lookups.fetch_add(1, Ordering::SeqCst);
+            future::ok(3)
+        });
+        assert_eq!(third.wait().unwrap(), 3);
+        assert_eq!(lookups.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn should_share_lookup_errors() {
+        let coalescer: Coalescer<u64, u64> = Coalescer::new("test");
+        let (sender, receiver) = oneshot::channel::<u64>();
+
+        let first = coalescer.get(1, || receiver.map_err(Error::from));
+        let second = coalescer.get(1, || future::ok(0));
+        drop(sender);
+
+        assert!(first.wait().is_err());
+        assert!(second.wait().is_err());
+    }
+}
@@ -0,0 +1,121 @@
+//! I/O contexts of JSON-RPC requests.
+//!
+//! The RPC handlers do not receive the request metadata, so the middleware
+//! makes the context of the request being dispatched available to them
+//! through `current`, and handlers pass it on to the node and storage reads
+//! they start. The client of the request is made available the same way
+//! through `client`.
+use std::{
+    cell::RefCell,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use grpcio::CallOption;
+use io_context::{CancelFunc, Context};
+use parking_lot::Mutex;
+
+use crate::ratelimit::Client;
+
+thread_local! {
+    /// Context of the JSON-RPC request being dispatched on this thread.
+    static CURRENT: RefCell<Option<Arc<Context>>> = RefCell::new(None);
+    /// Client of the JSON-RPC request being dispatched on this thread.
+    static CLIENT: RefCell<Option<Client>> = RefCell::new(None);
+}
+
+/// Cancels a request context when dropped, unless it was cancelled before.
+pub struct CancelGuard(Mutex<Option<CancelFunc>>);
+
+impl CancelGuard {
+    /// Cancel the context.
+    pub fn cancel(&self) {
+        if let Some(cancel) = self.0.lock().take() {
+            cancel.cancel();
+        }
+    }
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Deadline of a request context, kept as a context value as the deadline of
+/// a context cannot be read back.
+struct Deadline(Instant);
+
+/// Create the context of a JSON-RPC request, whose deadline is `timeout` from
+/// now, if any.
+pub fn new_request(timeout: Option<Duration>) -> (Arc<Context>, Arc<CancelGuard>) {
+    let mut ctx = Context::background();
+    if let Some(timeout) = timeout {
+        ctx.add_timeout(timeout);
+        ctx.add_value(Deadline(Instant::now() + timeout));
+    }
+    let cancel = ctx.add_cancelfunc();
+
+    (
+        ctx.freeze(),
+        Arc::new(CancelGuard(Mutex::new(Some(cancel)))),
+    )
+}
+
+/// Returns the deadline of a request context, if it has one.
+pub fn deadline(ctx: &Context) -> Option<Instant> {
+    ctx.get_value::<Deadline>().map(|deadline| deadline.0)
+}
+
+/// Returns gRPC call options which time out at the deadline of a request
+/// context, if it has one.
+pub fn call_option(ctx: &Context) -> CallOption {
+    match deadline(ctx) {
+        Some(deadline) => {
+            CallOption::default().timeout(deadline.saturating_duration_since(Instant::now()))
+        }
+        None => CallOption::default(),
+    }
+}
+
+/// Returns the context of the JSON-RPC request being dispatched, or a
+/// background context outside of a request.
+pub fn current() -> Arc<Context> {
+    CURRENT
+        .with(|current| current.borrow().clone())
+        .unwrap_or_else(|| Context::background().freeze())
+}
+
+/// Returns the client of the JSON-RPC request being dispatched, if it is
+/// known.
+pub fn client() -> Option<Client> {
+    CLIENT.with(|client| client.borrow().clone())
+}
+
+/// Dispatch a request of `client` with `ctx` as the current context.
+pub fn enter<R, F: FnOnce() -> R>(ctx: Arc<Context>, client: Option<Client>, dispatch: F) -> R {
+    let previous = CURRENT.with(|current| current.replace(Some(ctx)));
+    let previous_client = CLIENT.with(|current| current.replace(client));
+    let result = dispatch();
+    CURRENT.with(|current| *current.borrow_mut() = previous);
+    CLIENT.with(|current| *current.borrow_mut() = previous_client);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_keep_request_deadline() {
+        let before = Instant::now();
+        let (ctx, _cancel) = new_request(Some(Duration::from_secs(30)));
+        let at = deadline(&ctx).expect("request must have a deadline");
+        assert!(at >= before + Duration::from_secs(30));
+        assert!(at <= Instant::now() + Duration::from_secs(30));
+
+        let (ctx, _cancel) = new_request(None);
+        assert!(deadline(&ctx).is_none());
+    }
+}
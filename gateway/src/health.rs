@@ -0,0 +1,257 @@
+//! Health and readiness checks.
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use futures::{
+    future::{self, Either},
+    prelude::*,
+};
+use io_context::Context;
+use oasis_core_keymanager_client::{KeyManagerClient, KeyPairId};
+use parking_lot::Mutex;
+use tokio::timer::Timeout;
+
+use crate::{pubsub::Broker, translator::Translator};
+
+/// Time after which a single check is considered failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time for which the result of the readiness checks is reused.
+const READINESS_CACHE_TTL: Duration = Duration::from_secs(1);
+
+/// Result of a single readiness check.
+#[derive(Clone, Debug, Serialize)]
+pub struct Check {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Check {
+    fn ok() -> Self {
+        Self {
+            ok: true,
+            error: None,
+        }
+    }
+
+    fn failed(error: String) -> Self {
+        Self {
+            ok: false,
+            error: Some(error),
+        }
+    }
+}
+
+/// Result of the latest block age check.
+#[derive(Clone, Debug, Serialize)]
+pub struct BlockCheck {
+    pub ok: bool,
+    /// Latest block number, if it could be retrieved.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<u64>,
+    /// Age of the latest block (in sec), if it could be retrieved.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age: Option<u64>,
+    /// Maximum allowed age of the latest block (in sec).
+    pub max_age: u64,
+}
+
+/// Gateway readiness report.
+#[derive(Clone, Debug, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    /// Whether the Oasis Core node is reachable.
+    pub node: Check,
+    /// Whether the latest block is recent enough.
+    pub latest_block: BlockCheck,
+    /// Whether the pub/sub broker is polling for new blocks.
+    pub broker: Check,
+    /// Whether the key manager is reachable.
+    pub key_manager: Check,
+}
+
+/// Checks whether the gateway is able to serve requests.
+pub struct HealthChecker {
+    translator: Arc<Translator>,
+    broker: Arc<Broker>,
+    max_block_age: Duration,
+    max_broker_delay: Duration,
+    cache: Arc<ReadinessCache>,
+}
+
+impl HealthChecker {
+    /// Create a new health checker.
+    ///
+    /// The broker is considered unhealthy if it has not polled for new blocks
+    /// for more than `max_broker_delay`.
+    pub fn new(
+        translator: Arc<Translator>,
+        broker: Arc<Broker>,
+        max_block_age: Duration,
+        max_broker_delay: Duration,
+    ) -> Self {
+        Self {
+            translator,
+            broker,
+            max_block_age,
+            max_broker_delay,
+            cache: Arc::new(ReadinessCache::new(READINESS_CACHE_TTL)),
+        }
+    }
+
+    /// Get the readiness report, running the checks unless a recent report
+    /// is cached.
+    pub fn readiness(&self) -> impl Future<Item = Readiness, Error = ()> {
+        if let Some(readiness) = self.cache.get(Instant::now()) {
+            return Either::A(future::ok(readiness));
+        }
+
+        let cache = self.cache.clone();
+        Either::B(self.check().map(move |readiness| {
+            cache.set(Instant::now(), readiness.clone());
+            readiness
+        }))
+    }
+
+    /// Run all readiness checks.
+    fn check(&self) -> impl Future<Item = Readiness, Error = ()> {
+        let max_age = self.max_block_age.as_secs();
+        let latest_block = Timeout::new(self.translator.get_latest_block(), CHECK_TIMEOUT)
+            .then(|result| Ok::<_, ()>(result.map_err(timeout_error)));
+        let key_manager = Timeout::new(
+            self.translator
+                .nodes()
+                .select()
+                .km_client
+                .get_public_key(Context::background(), KeyPairId::default()),
+            CHECK_TIMEOUT,
+        )
+        .then(|result| Ok::<_, ()>(result.map_err(timeout_error)));
+
+        let broker = if self.broker.last_poll_elapsed() <= self.max_broker_delay {
+            Check::ok()
+        } else {
+            Check::failed("broker is not polling for new blocks".to_owned())
+        };
+
+        latest_block
+            .join(key_manager)
+            .map(move |(latest_block, key_manager)| {
+                let (node, latest_block) = match latest_block {
+                    Ok(blk) => {
+                        let now = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_secs();
+                        let age = now.saturating_sub(blk.timestamp());
+
+                        (
+                            Check::ok(),
+                            BlockCheck {
+                                ok: age <= max_age,
+                                number: Some(blk.number_u64()),
+                                age: Some(age),
+                                max_age,
+                            },
+                        )
+                    }
+                    Err(err) => (
+                        Check::failed(err),
+                        BlockCheck {
+                            ok: false,
+                            number: None,
+                            age: None,
+                            max_age,
+                        },
+                    ),
+                };
+                let key_manager = match key_manager {
+                    Ok(_) => Check::ok(),
+                    Err(err) => Check::failed(err),
+                };
+
+                Readiness {
+                    ready: node.ok && latest_block.ok && broker.ok && key_manager.ok,
+                    node,
+                    latest_block,
+                    broker,
+                    key_manager,
+                }
+            })
+    }
+}
+
+/// Readiness report reused for a short time, so that frequent probes do not
+/// query the node and the key manager on every request.
+struct ReadinessCache {
+    ttl: Duration,
+    entry: Mutex<Option<(Instant, Readiness)>>,
+}
+
+impl ReadinessCache {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entry: Mutex::new(None),
+        }
+    }
+
+    /// Returns the cached report, unless it is older than the TTL at `now`.
+    fn get(&self, now: Instant) -> Option<Readiness> {
+        match *self.entry.lock() {
+            Some((checked_at, ref readiness)) if now.duration_since(checked_at) < self.ttl => {
+                Some(readiness.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn set(&self, now: Instant, readiness: Readiness) {
+        *self.entry.lock() = Some((now, readiness));
+    }
+}
+
+/// Formats the error of a timed out check.
+fn timeout_error<E: ToString>(err: tokio::timer::timeout::Error<E>) -> String {
+    match err.into_inner() {
+        Some(err) => err.to_string(),
+        None => "timed out".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn readiness(ready: bool) -> Readiness {
+        Readiness {
+            ready,
+            node: Check::ok(),
+            latest_block: BlockCheck {
+                ok: true,
+                number: Some(1),
+                age: Some(0),
+                max_age: 60,
+            },
+            broker: Check::ok(),
+            key_manager: Check::ok(),
+        }
+    }
+
+    #[test]
+    fn should_cache_readiness_until_ttl() {
+        let cache = ReadinessCache::new(Duration::from_secs(1));
+        let now = Instant::now();
+        assert!(cache.get(now).is_none());
+
+        cache.set(now, readiness(false));
+        assert!(!cache.get(now).unwrap().ready);
+        assert!(cache.get(now + Duration::from_millis(999)).is_some());
+        assert!(cache.get(now + Duration::from_secs(1)).is_none());
+
+        cache.set(now + Duration::from_secs(1), readiness(true));
+        assert!(cache.get(now + Duration::from_secs(1)).unwrap().ready);
+    }
+}
@@ -0,0 +1,161 @@
+//! Admin RPC implementation.
+use std::{
+    str::FromStr,
+    sync::{Arc, Weak},
+};
+
+use jsonrpc_core::Result;
+use lazy_static::lazy_static;
+use parity_rpc::v1::{helpers::errors, types::H256};
+use parking_lot::{Mutex, RwLock};
+use prometheus::{labels, register_int_counter_vec, IntCounterVec};
+use slog::{info, Logger};
+
+use crate::{
+    auth::ApiKeys,
+    impls::{
+        eth_filter::{filter_kind, Filters},
+        eth_pubsub::ChainNotificationHandler,
+    },
+    informant::RpcStats,
+    logger::{self, get_logger},
+    ratelimit::WsClients,
+    traits::admin::{Admin, RpcFilter, RpcSession, RpcStatsReport, RpcSubscriptions},
+    util::jsonrpc_error,
+};
+
+// Metrics.
+lazy_static! {
+    static ref ADMIN_RPC_CALLS: IntCounterVec = register_int_counter_vec!(
+        "web3_gateway_admin_rpc_calls",
+        "Number of admin API RPC calls",
+        &["call"]
+    )
+    .unwrap();
+}
+
+/// Admin rpc implementation.
+pub struct AdminClient {
+    logger: Logger,
+    stats: Arc<RpcStats>,
+    ws_clients: Arc<WsClients>,
+    filters: Arc<Mutex<Filters>>,
+    pubsub_handlers: Arc<RwLock<Vec<Weak<ChainNotificationHandler>>>>,
+    api_keys: Option<Arc<ApiKeys>>,
+}
+
+impl AdminClient {
+    /// Creates new AdminClient.
+    pub fn new(
+        stats: Arc<RpcStats>,
+        ws_clients: Arc<WsClients>,
+        filters: Arc<Mutex<Filters>>,
+        pubsub_handlers: Arc<RwLock<Vec<Weak<ChainNotificationHandler>>>>,
+        api_keys: Option<Arc<ApiKeys>>,
+    ) -> Self {
+        AdminClient {
+            logger: get_logger("gateway/impls/admin"),
+            stats,
+            ws_clients,
+            filters,
+            pubsub_handlers,
+            api_keys,
+        }
+    }
+}
+
+impl Admin for AdminClient {
+    fn sessions(&self) -> Result<Vec<RpcSession>> {
+        ADMIN_RPC_CALLS.with(&labels! {"call" => "sessions",}).inc();
+
+        Ok(self
+            .ws_clients
+            .sessions()
+            .into_iter()
+            .map(|(id, client)| RpcSession {
+                requests_rate: self.stats.requests_rate(&id),
+                id,
+                ip: client.ip,
+                api_key: client.key,
+            })
+            .collect())
+    }
+
+    fn rpc_stats(&self) -> Result<RpcStatsReport> {
+        ADMIN_RPC_CALLS.with(&labels! {"call" => "rpcStats",}).inc();
+
+        Ok(RpcStatsReport {
+            sessions: self.stats.sessions(),
+            requests_rate: self.stats.rate(),
+        })
+    }
+
+    fn filters(&self) -> Result<Vec<RpcFilter>> {
+        ADMIN_RPC_CALLS.with(&labels! {"call" => "filters",}).inc();
+
+        Ok(self
+            .filters
+            .lock()
+            .list()
+            .into_iter()
+            .map(|(id, filter, idle)| RpcFilter {
+                id: id.into(),
+                kind: filter_kind(filter).to_owned(),
+                idle_seconds: idle.as_secs(),
+            })
+            .collect())
+    }
+
+    fn subscriptions(&self) -> Result<RpcSubscriptions> {
+        ADMIN_RPC_CALLS
+            .with(&labels! {"call" => "subscriptions",})
+            .inc();
+
+        let mut subscriptions = RpcSubscriptions::default();
+        for handler in self.pubsub_handlers.read().iter().filter_map(Weak::upgrade) {
+            let handler_subscriptions = handler.subscriptions();
+            subscriptions.new_heads += handler_subscriptions.new_heads;
+            subscriptions.logs += handler_subscriptions.logs;
+            subscriptions.completed_transaction += handler_subscriptions.completed_transaction;
+            subscriptions.syncing += handler_subscriptions.syncing;
+        }
+
+        Ok(subscriptions)
+    }
+
+    fn close_session(&self, id: H256) -> Result<bool> {
+        ADMIN_RPC_CALLS
+            .with(&labels! {"call" => "closeSession",})
+            .inc();
+        info!(self.logger, "admin_closeSession"; "session" => ?id);
+
+        Ok(self.ws_clients.disconnect(&id))
+    }
+
+    fn set_log_level(&self, level: String) -> Result<bool> {
+        ADMIN_RPC_CALLS
+            .with(&labels! {"call" => "setLogLevel",})
+            .inc();
+
+        let level = log::LevelFilter::from_str(&level).map_err(|_| {
+            errors::invalid_params("level", "Expected off, error, warn, info, debug or trace.")
+        })?;
+        info!(self.logger, "admin_setLogLevel"; "level" => ?level);
+        logger::set_max_level(level);
+
+        Ok(true)
+    }
+
+    fn reload(&self) -> Result<bool> {
+        ADMIN_RPC_CALLS.with(&labels! {"call" => "reload",}).inc();
+
+        match self.api_keys {
+            Some(ref api_keys) => {
+                api_keys.reload().map_err(jsonrpc_error)?;
+                info!(self.logger, "Reloaded API keys"; "count" => api_keys.count());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
@@ -19,18 +19,20 @@
 use std::{collections::BTreeMap, sync::Arc};
 
 use anyhow::Error;
-use ethcore::{filter::Filter as EthcoreFilter, ids::BlockId};
+use ethcore::{
+    error::CallError, executive::Executed, filter::Filter as EthcoreFilter, ids::BlockId,
+    vm::Error as VmError,
+};
 use ethereum_types::{Address, H256, H64, U256};
 use jsonrpc_core::{
     futures::{future, Future},
-    BoxFuture, Result,
+    BoxFuture, Error as RpcError, Params, Result, Value,
 };
-use jsonrpc_macros::Trailing;
+use jsonrpc_macros::{IoDelegate, Trailing};
 use lazy_static::lazy_static;
-use oasis_core_runtime::common::logger::get_logger;
+use oasis_core_runtime::common::cbor;
 use parity_rpc::v1::{
     helpers::{errors, fake_sign},
-    metadata::Metadata,
     traits::Eth,
     types::{
         BlockNumber, Bytes, CallRequest, Filter, Index, Log as RpcLog, Receipt as RpcReceipt,
@@ -41,11 +43,23 @@ use parity_rpc::v1::{
 use prometheus::{
     labels, register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec,
 };
+use serde_bytes::ByteBuf;
+use serde_json::to_value;
 use slog::{debug, info, Logger};
 
 use crate::{
-    translator::Translator,
-    util::{block_number_to_id, execution_error, jsonrpc_error},
+    context,
+    logger::get_logger,
+    metadata::Metadata,
+    traits::eth::{
+        EthCallMany, EthProof, RpcCallResult, RpcEthAccountProof, RpcEthStorageProof,
+        RpcSyncStatus, StateOverride,
+    },
+    translator::{ProofEntry, Translator},
+    util::{
+        block_number_to_id, decode_revert_reason, jsonrpc_error, send_transaction_error,
+        simulation_error,
+    },
 };
 
 // Metrics.
@@ -118,6 +132,288 @@ impl EthClient {
             translator,
         }
     }
+
+    /// Creates an I/O delegate for the `Eth` API, whose `eth_call` and
+    /// `eth_estimateGas` accept an optional state override set as the third
+    /// parameter, and whose `eth_syncing` returns the sync state of the nodes
+    /// rather than a boolean.
+    ///
+    /// The methods in `Eth` have a fixed signature, so they are replaced in
+    /// the delegate.
+    pub fn eth_delegate(self) -> IoDelegate<Self, Metadata> {
+        let mut delegate = Eth::to_delegate(self);
+
+        delegate.add_method_with_meta("eth_call", |client, params, meta| {
+            let (request, num, state_override) = try_bf!(parse_call_params(params));
+            Box::new(
+                client
+                    .call_with_override(meta, request, num, state_override)
+                    .map(|output| to_value(output).expect("Bytes serialization cannot fail")),
+            )
+        });
+        delegate.add_method_with_meta("eth_estimateGas", |client, params, meta| {
+            let (request, num, state_override) = try_bf!(parse_call_params(params));
+            Box::new(
+                client
+                    .estimate_gas_with_override(meta, request, num, state_override)
+                    .map(|gas| to_value(gas).expect("U256 serialization cannot fail")),
+            )
+        });
+        delegate.add_method("eth_syncing", |client, params| -> Result<Value> {
+            params.expect_no_params()?;
+            Ok(to_value(client.sync_status()).expect("RpcSyncStatus serialization cannot fail"))
+        });
+
+        delegate
+    }
+
+    /// Returns the sync state of the nodes, with the current and highest
+    /// rounds while syncing.
+    pub fn sync_status(&self) -> RpcSyncStatus {
+        ETH_RPC_CALLS.with(&labels! {"call" => "syncing",}).inc();
+        self.translator.nodes().sync_status().into()
+    }
+
+    /// Executes a new message call immediately without creating a transaction
+    /// on chain, optionally applying a state override set first.
+    pub fn call_with_override(
+        &self,
+        meta: Metadata,
+        request: CallRequest,
+        num: BlockNumber,
+        state_override: Option<StateOverride>,
+    ) -> BoxFuture<Bytes> {
+        ETH_RPC_CALLS.with(&labels! {"call" => "call",}).inc();
+        let timer = ETH_RPC_CALL_TIME
+            .with(&labels! {"call" => "call",})
+            .start_timer();
+
+        info!(self.logger, "eth_call";
+            "request" => ?request,
+            "num" => ?num,
+            "state_override" => ?state_override
+        );
+
+        let signed = try_bf!(fake_sign::sign_call(request.into(), meta.is_dapp()));
+
+        Box::new(
+            self.translator
+                .simulate_transaction(
+                    context::current(),
+                    signed,
+                    block_number_to_id(num),
+                    state_override,
+                )
+                .map_err(simulation_error)
+                .and_then(|executed| match executed.exception {
+                    Some(ref exception) => Err(errors::vm(exception, &executed.output)),
+                    None => Ok(executed),
+                })
+                .map(|executed| executed.output.into())
+                .then(move |result| {
+                    drop(timer);
+                    result
+                }),
+        )
+    }
+
+    /// Estimates the gas required for a transaction, optionally applying a
+    /// state override set first.
+    pub fn estimate_gas_with_override(
+        &self,
+        meta: Metadata,
+        request: CallRequest,
+        num: BlockNumber,
+        state_override: Option<StateOverride>,
+    ) -> BoxFuture<RpcU256> {
+        ETH_RPC_CALLS
+            .with(&labels! {"call" => "estimateGas",})
+            .inc();
+        let timer = ETH_RPC_CALL_TIME
+            .with(&labels! {"call" => "estimateGas",})
+            .start_timer();
+
+        info!(self.logger, "eth_estimateGas";
+            "request" => ?request,
+            "num" => ?num,
+            "state_override" => ?state_override
+        );
+
+        let signed = try_bf!(fake_sign::sign_call(request.into(), meta.is_dapp()));
+
+        Box::new(
+            self.translator
+                .estimate_gas(
+                    context::current(),
+                    signed,
+                    block_number_to_id(num),
+                    state_override,
+                )
+                .map_err(simulation_error)
+                .map(Into::into)
+                .then(move |result| {
+                    drop(timer);
+                    result
+                }),
+        )
+    }
+}
+
+impl EthCallMany for EthClient {
+    type Metadata = Metadata;
+
+    fn call_many(
+        &self,
+        meta: Metadata,
+        requests: Vec<CallRequest>,
+        num: Trailing<BlockNumber>,
+    ) -> BoxFuture<Vec<RpcCallResult>> {
+        ETH_RPC_CALLS.with(&labels! {"call" => "callMany",}).inc();
+        let timer = ETH_RPC_CALL_TIME
+            .with(&labels! {"call" => "callMany",})
+            .start_timer();
+
+        let num = num.unwrap_or_default();
+
+        info!(self.logger, "eth_callMany"; "requests" => ?requests, "num" => ?num);
+
+        let signed = try_bf!(requests
+            .into_iter()
+            .map(|request| fake_sign::sign_call(request.into(), meta.is_dapp()))
+            .collect::<Result<Vec<_>>>());
+
+        Box::new(
+            self.translator
+                .simulate_transactions(context::current(), signed, block_number_to_id(num), None)
+                .map_err(simulation_error)
+                .map(|results| results.into_iter().map(call_result).collect())
+                .then(move |result| {
+                    drop(timer);
+                    result
+                }),
+        )
+    }
+}
+
+impl EthProof for EthClient {
+    type Metadata = Metadata;
+
+    fn proof(
+        &self,
+        address: RpcH160,
+        storage_keys: Vec<RpcH256>,
+        num: Trailing<BlockNumber>,
+    ) -> BoxFuture<RpcEthAccountProof> {
+        ETH_RPC_CALLS.with(&labels! {"call" => "getProof",}).inc();
+        let timer = ETH_RPC_CALL_TIME
+            .with(&labels! {"call" => "getProof",})
+            .start_timer();
+
+        let address: Address = RpcH160::into(address);
+        let storage_keys: Vec<H256> = storage_keys.into_iter().map(Into::into).collect();
+        let num = num.unwrap_or_default();
+
+        info!(
+            self.logger,
+            "eth_getProof";
+                "address" => ?address,
+                "storage_keys" => ?storage_keys,
+                "num" => ?num
+        );
+
+        Box::new(
+            self.translator
+                .get_proof(
+                    context::current(),
+                    address,
+                    storage_keys,
+                    block_number_to_id(num),
+                )
+                .map_err(jsonrpc_error)
+                .then(move |maybe_proof| {
+                    drop(timer);
+
+                    maybe_proof.map(|proof| RpcEthAccountProof {
+                        address: proof.address.into(),
+                        account_proof: proof.account_proof.into_iter().map(proof_item).collect(),
+                        balance: proof.balance.into(),
+                        code_hash: proof.code_hash.into(),
+                        nonce: proof.nonce.into(),
+                        storage_hash: proof.state_root.into(),
+                        storage_proof: proof
+                            .storage_proof
+                            .into_iter()
+                            .map(|(key, value, entries)| RpcEthStorageProof {
+                                key: key.into(),
+                                value: value.into(),
+                                proof: entries.into_iter().map(proof_item).collect(),
+                            })
+                            .collect(),
+                    })
+                }),
+        )
+    }
+}
+
+/// Encodes an MKVS proof entry as a CBOR proof item, in place of the RLP trie
+/// nodes of EIP-1186.
+fn proof_item(entry: ProofEntry) -> Bytes {
+    let ProofEntry { key, value, proof } = entry;
+    cbor::to_vec(&(ByteBuf::from(key), value.map(ByteBuf::from), proof)).into()
+}
+
+/// Converts the outcome of a simulated call into an RPC call result.
+fn call_result(result: ::std::result::Result<Executed, CallError>) -> RpcCallResult {
+    match result {
+        Ok(executed) => {
+            let (revert_reason, error) = match executed.exception {
+                Some(VmError::Reverted) => (
+                    decode_revert_reason(&executed.output),
+                    Some("execution reverted".to_owned()),
+                ),
+                Some(ref exception) => (None, Some(format!("{}", exception))),
+                None => (None, None),
+            };
+
+            RpcCallResult {
+                output: executed.output.into(),
+                gas_used: executed.gas_used.into(),
+                logs: executed.logs.into_iter().map(Into::into).collect(),
+                revert_reason,
+                error,
+            }
+        }
+        Err(err) => RpcCallResult {
+            output: Default::default(),
+            gas_used: Default::default(),
+            logs: vec![],
+            revert_reason: None,
+            error: Some(format!("{}", err)),
+        },
+    }
+}
+
+/// Parses `eth_call`-style parameters: a call request, an optional block
+/// number and an optional state override set.
+fn parse_call_params(params: Params) -> Result<(CallRequest, BlockNumber, Option<StateOverride>)> {
+    let len = match params {
+        Params::Array(ref values) => values.len(),
+        Params::None => 0,
+        Params::Map(_) => return Err(RpcError::invalid_params("Expected an array.")),
+    };
+
+    match len {
+        1 => params
+            .parse::<(CallRequest,)>()
+            .map(|(request,)| (request, BlockNumber::default(), None)),
+        2 => params
+            .parse::<(CallRequest, BlockNumber)>()
+            .map(|(request, num)| (request, num, None)),
+        3 => params
+            .parse::<(CallRequest, BlockNumber, StateOverride)>()
+            .map(|(request, num, state_override)| (request, num, Some(state_override))),
+        _ => Err(RpcError::invalid_params("Expected 1 to 3 parameters.")),
+    }
 }
 
 impl Eth for EthClient {
@@ -132,8 +428,11 @@ impl Eth for EthClient {
     }
 
     fn syncing(&self) -> Result<bool> {
-        ETH_RPC_CALLS.with(&labels! {"call" => "syncing",}).inc();
-        Ok(false)
+        // Shadowed by the `eth_syncing` of `eth_delegate`, which returns the
+        // sync status rather than a boolean and counts the call.
+        Err(errors::unimplemented(Some(
+            "eth_syncing is served by EthClient::eth_delegate".to_owned(),
+        )))
     }
 
     fn author(&self, _meta: Metadata) -> Result<RpcH160> {
@@ -182,10 +481,11 @@ impl Eth for EthClient {
 
         info!(self.logger, "eth_getBalance"; "address" => ?address, "num" => ?num);
 
+        let ctx = context::current();
         Box::new(
             self.translator
                 .get_block_unwrap(block_number_to_id(num))
-                .and_then(move |blk| Ok(blk.state()?.balance(&address)?.into()))
+                .and_then(move |blk| Ok(blk.state(ctx)?.balance(&address)?.into()))
                 .map_err(jsonrpc_error),
         )
     }
@@ -214,10 +514,11 @@ impl Eth for EthClient {
 
         let pos = pos.into();
 
+        let ctx = context::current();
         Box::new(
             self.translator
                 .get_block_unwrap(block_number_to_id(num))
-                .and_then(move |blk| Ok(blk.state()?.storage_at(&address, &pos)?.into()))
+                .and_then(move |blk| Ok(blk.state(ctx)?.storage_at(&address, &pos)?.into()))
                 .map_err(jsonrpc_error),
         )
     }
@@ -241,10 +542,11 @@ impl Eth for EthClient {
                 "num" => ?num
         );
 
+        let ctx = context::current();
         Box::new(
             self.translator
                 .get_block_unwrap(block_number_to_id(num))
-                .and_then(move |blk| Ok(blk.state()?.nonce(&address)?.into()))
+                .and_then(move |blk| Ok(blk.state(ctx)?.nonce(&address)?.into()))
                 .map_err(jsonrpc_error),
         )
     }
@@ -322,12 +624,13 @@ impl Eth for EthClient {
 
         info!(self.logger, "eth_getCode"; "address" => ?address, "num" => ?num);
 
+        let ctx = context::current();
         Box::new(
             self.translator
                 .get_block_unwrap(block_number_to_id(num))
                 .and_then(move |blk| {
                     Ok(blk
-                        .state()?
+                        .state(ctx)?
                         .code(&address)?
                         .map_or_else(Bytes::default, |b| Bytes::new((&*b).clone())))
                 })
@@ -397,11 +700,8 @@ impl Eth for EthClient {
 
         Box::new(
             self.translator
-                .get_txn_by_hash(hash)
-                .and_then(move |txn| {
-                    txn.map(|txn| Ok(RpcTransaction::from_localized(txn.transaction()?)))
-                        .transpose()
-                })
+                .get_transaction(hash)
+                .map(|transaction| transaction.map(RpcTransaction::from_localized))
                 .map_err(jsonrpc_error),
         )
     }
@@ -475,8 +775,8 @@ impl Eth for EthClient {
 
         Box::new(
             self.translator
-                .get_txn_by_hash(hash)
-                .and_then(|txn| txn.map(|txn| Ok(txn.receipt()?.into())).transpose())
+                .get_receipt(hash)
+                .map(|receipt| receipt.map(Into::into))
                 .map_err(jsonrpc_error),
         )
     }
@@ -564,9 +864,9 @@ impl Eth for EthClient {
 
         Box::new(
             self.translator
-                .send_raw_transaction(raw.into())
+                .send_raw_transaction(context::current(), raw.into())
                 .map(|(hash, _result)| hash.into())
-                .map_err(execution_error)
+                .map_err(send_transaction_error)
                 .then(move |result| {
                     drop(timer);
                     result
@@ -589,31 +889,7 @@ impl Eth for EthClient {
         request: CallRequest,
         num: Trailing<BlockNumber>,
     ) -> BoxFuture<Bytes> {
-        ETH_RPC_CALLS.with(&labels! {"call" => "call",}).inc();
-        let timer = ETH_RPC_CALL_TIME
-            .with(&labels! {"call" => "call",})
-            .start_timer();
-
-        let num = num.unwrap_or_default();
-
-        info!(self.logger, "eth_call"; "request" => ?request, "num" => ?num);
-
-        let signed = try_bf!(fake_sign::sign_call(request.into(), meta.is_dapp()));
-
-        Box::new(
-            self.translator
-                .simulate_transaction(signed, block_number_to_id(num))
-                .map_err(errors::call)
-                .and_then(|executed| match executed.exception {
-                    Some(ref exception) => Err(errors::vm(exception, &executed.output)),
-                    None => Ok(executed),
-                })
-                .map(|executed| executed.output.into())
-                .then(move |result| {
-                    drop(timer);
-                    result
-                }),
-        )
+        self.call_with_override(meta, request, num.unwrap_or_default(), None)
     }
 
     fn estimate_gas(
@@ -622,29 +898,7 @@ impl Eth for EthClient {
         request: CallRequest,
         num: Trailing<BlockNumber>,
     ) -> BoxFuture<RpcU256> {
-        ETH_RPC_CALLS
-            .with(&labels! {"call" => "estimateGas",})
-            .inc();
-        let timer = ETH_RPC_CALL_TIME
-            .with(&labels! {"call" => "estimateGas",})
-            .start_timer();
-
-        let num = num.unwrap_or_default();
-
-        info!(self.logger, "eth_estimateGas"; "request" => ?request, "num" => ?num);
-
-        let signed = try_bf!(fake_sign::sign_call(request.into(), meta.is_dapp()));
-
-        Box::new(
-            self.translator
-                .estimate_gas(signed, block_number_to_id(num))
-                .map_err(execution_error)
-                .map(Into::into)
-                .then(move |result| {
-                    drop(timer);
-                    result
-                }),
-        )
+        self.estimate_gas_with_override(meta, request, num.unwrap_or_default(), None)
     }
 
     fn compile_lll(&self, _: String) -> Result<Bytes> {
@@ -672,3 +926,69 @@ impl Eth for EthClient {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn executed(exception: Option<VmError>, output: Vec<u8>) -> Executed {
+        Executed {
+            exception,
+            gas: 100_000.into(),
+            gas_used: 21_000.into(),
+            refunded: Default::default(),
+            cumulative_gas_used: 21_000.into(),
+            logs: vec![],
+            contracts_created: vec![],
+            output,
+            trace: vec![],
+            vm_trace: None,
+            state_diff: None,
+        }
+    }
+
+    /// Returns `Error(string)`-encoded call output.
+    fn revert_reason_output(reason: &str) -> Vec<u8> {
+        let mut output = vec![0x08, 0xc3, 0x79, 0xa0];
+        for word in &[32, reason.len()] {
+            output.extend_from_slice(&[0u8; 24]);
+            output.extend_from_slice(&(*word as u64).to_be_bytes());
+        }
+        output.extend_from_slice(reason.as_bytes());
+        output
+    }
+
+    #[test]
+    fn should_return_output_of_successful_calls() {
+        let result = call_result(Ok(executed(None, vec![1, 2, 3])));
+        assert_eq!(result.output, vec![1, 2, 3].into());
+        assert_eq!(result.gas_used, U256::from(21_000).into());
+        assert_eq!(result.revert_reason, None);
+        assert_eq!(result.error, None);
+    }
+
+    #[test]
+    fn should_mark_reverted_calls_with_reason() {
+        let output = revert_reason_output("boom");
+        let result = call_result(Ok(executed(Some(VmError::Reverted), output.clone())));
+        assert_eq!(result.output, output.into());
+        assert_eq!(result.revert_reason, Some("boom".to_owned()));
+        assert_eq!(result.error, Some("execution reverted".to_owned()));
+    }
+
+    #[test]
+    fn should_mark_reverted_calls_without_reason() {
+        let result = call_result(Ok(executed(Some(VmError::Reverted), vec![])));
+        assert_eq!(result.revert_reason, None);
+        assert_eq!(result.error, Some("execution reverted".to_owned()));
+    }
+
+    #[test]
+    fn should_report_call_errors() {
+        let result = call_result(Err(CallError::StatePruned));
+        assert_eq!(result.output, Bytes::default());
+        assert_eq!(result.gas_used, RpcU256::default());
+        assert_eq!(result.revert_reason, None);
+        assert_eq!(result.error, Some(format!("{}", CallError::StatePruned)));
+    }
+}
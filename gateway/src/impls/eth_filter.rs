@@ -16,26 +16,38 @@
 
 //! Eth Filter RPC implementation
 
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::anyhow;
 use ethcore::{filter::Filter as EthcoreFilter, ids::BlockId};
 use jsonrpc_core::{
     futures::{future, prelude::*, stream},
-    BoxFuture, Result,
+    BoxFuture, Error, ErrorCode, Result,
 };
 use lazy_static::lazy_static;
-use oasis_core_runtime::common::logger::get_logger;
 use parity_rpc::v1::{
-    helpers::{errors, limit_logs, PollFilter, PollManager},
+    helpers::{errors, limit_logs, PollFilter},
     traits::EthFilter,
     types::{Filter, FilterChanges, Index, Log, H256 as RpcH256, U256 as RpcU256},
 };
 use parking_lot::Mutex;
-use prometheus::{labels, register_int_counter_vec, IntCounterVec};
+use prometheus::{
+    labels, register_int_counter, register_int_counter_vec, register_int_gauge_vec, IntCounter,
+    IntCounterVec, IntGaugeVec,
+};
 use slog::{info, Logger};
 
-use crate::{translator::Translator, util::jsonrpc_error};
+use crate::{
+    context,
+    logger::get_logger,
+    ratelimit::Client,
+    translator::Translator,
+    util::{jsonrpc_error, ERROR_LIMIT_EXCEEDED},
+};
 
 // Metrics.
 lazy_static! {
@@ -45,22 +57,218 @@ lazy_static! {
         &["call"]
     )
     .unwrap();
+    static ref ETH_FILTERS: IntGaugeVec = register_int_gauge_vec!(
+        "web3_gateway_eth_filters",
+        "Number of installed filters",
+        &["kind"]
+    )
+    .unwrap();
+    static ref ETH_FILTERS_EXPIRED: IntCounter = register_int_counter!(
+        "web3_gateway_eth_filters_expired",
+        "Number of filters removed after not being polled"
+    )
+    .unwrap();
+    static ref ETH_FILTERS_REJECTED: IntCounter = register_int_counter!(
+        "web3_gateway_eth_filters_rejected",
+        "Number of filters rejected over the per-client or total limit"
+    )
+    .unwrap();
+}
+
+/// Filter limits.
+#[derive(Debug, Clone)]
+pub struct FilterConfig {
+    /// Time after which a filter that is not polled is removed.
+    pub lifetime: Duration,
+    /// Max filters installed per client (0 for no limit).
+    pub max_per_client: usize,
+    /// Max filters installed in total (0 for no limit), which also bounds
+    /// the filters of clients with an unknown address.
+    pub max_total: usize,
+    /// Max number of blocks covered by a single poll of a filter (0 for no
+    /// limit). Filters further behind catch up over several polls.
+    pub max_catch_up: u64,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            lifetime: Duration::from_secs(60),
+            max_per_client: 100,
+            max_total: 10000,
+            max_catch_up: 1000,
+        }
+    }
+}
+
+/// Returns the kind of a filter.
+pub fn filter_kind(filter: &PollFilter) -> &'static str {
+    match *filter {
+        PollFilter::Block(_) => "block",
+        PollFilter::PendingTransaction(_) => "pendingTransaction",
+        PollFilter::Logs(..) => "logs",
+    }
+}
+
+/// Returns the range of rounds a poll of a filter covers, starting with the
+/// `next` round the filter did not cover yet, or `None` if there are no new
+/// rounds.
+fn catch_up_range(next: u64, latest: u64, max_catch_up: u64) -> Option<(u64, u64)> {
+    if next > latest {
+        return None;
+    }
+
+    match max_catch_up {
+        0 => Some((next, latest)),
+        max_catch_up => Some((next, latest.min(next + max_catch_up - 1))),
+    }
+}
+
+fn error_filter_limit(max: usize) -> Error {
+    Error {
+        code: ErrorCode::ServerError(ERROR_LIMIT_EXCEEDED),
+        message: format!("Too many installed filters (max {})", max),
+        data: None,
+    }
+}
+
+struct Poll {
+    filter: PollFilter,
+    last_poll: Instant,
+    /// Client which installed the filter, if known.
+    owner: Option<Client>,
+}
+
+/// Installed filters, removed once they are not polled for the configured
+/// lifetime.
+pub struct Filters {
+    config: FilterConfig,
+    next_id: usize,
+    polls: HashMap<usize, Poll>,
+}
+
+impl Filters {
+    /// Create an empty filter store.
+    pub fn new(config: FilterConfig) -> Self {
+        Self {
+            config,
+            next_id: 0,
+            polls: HashMap::new(),
+        }
+    }
+
+    fn prune(&mut self) {
+        let now = Instant::now();
+        let lifetime = self.config.lifetime;
+        self.polls.retain(|_, poll| {
+            let alive = now.duration_since(poll.last_poll) < lifetime;
+            if !alive {
+                ETH_FILTERS_EXPIRED.inc();
+                ETH_FILTERS
+                    .with(&labels! {"kind" => filter_kind(&poll.filter),})
+                    .dec();
+            }
+            alive
+        });
+    }
+
+    /// Install a filter of `owner`, returning its id, or an error if the
+    /// owner already has the max number of installed filters, or if the max
+    /// total number of filters is installed.
+    pub fn create_poll(&mut self, filter: PollFilter, owner: Option<Client>) -> Result<usize> {
+        self.prune();
+
+        let max_total = self.config.max_total;
+        if max_total != 0 && self.polls.len() >= max_total {
+            ETH_FILTERS_REJECTED.inc();
+            return Err(error_filter_limit(max_total));
+        }
+
+        let max_per_client = self.config.max_per_client;
+        if owner.is_some() && max_per_client != 0 {
+            let installed = self
+                .polls
+                .values()
+                .filter(|poll| poll.owner == owner)
+                .count();
+            if installed >= max_per_client {
+                ETH_FILTERS_REJECTED.inc();
+                return Err(error_filter_limit(max_per_client));
+            }
+        }
+
+        ETH_FILTERS
+            .with(&labels! {"kind" => filter_kind(&filter),})
+            .inc();
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.polls.insert(
+            id,
+            Poll {
+                filter,
+                last_poll: Instant::now(),
+                owner,
+            },
+        );
+        Ok(id)
+    }
+
+    /// Returns a filter, marking it as polled.
+    pub fn poll(&mut self, id: &usize) -> Option<&PollFilter> {
+        self.poll_mut(id).map(|filter| &*filter)
+    }
+
+    /// Returns a filter, marking it as polled.
+    pub fn poll_mut(&mut self, id: &usize) -> Option<&mut PollFilter> {
+        self.prune();
+
+        self.polls.get_mut(id).map(|poll| {
+            poll.last_poll = Instant::now();
+            &mut poll.filter
+        })
+    }
+
+    /// Uninstall a filter. Returns false if the filter does not exist.
+    pub fn remove_poll(&mut self, id: &usize) -> bool {
+        match self.polls.remove(id) {
+            Some(poll) => {
+                ETH_FILTERS
+                    .with(&labels! {"kind" => filter_kind(&poll.filter),})
+                    .dec();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the installed filters, with the time since they were last
+    /// polled.
+    pub fn list(&mut self) -> Vec<(usize, &PollFilter, Duration)> {
+        self.prune();
+
+        let now = Instant::now();
+        self.polls
+            .iter()
+            .map(|(&id, poll)| (id, &poll.filter, now.duration_since(poll.last_poll)))
+            .collect()
+    }
 }
 
 /// Eth filter rpc implementation for a full node.
 pub struct EthFilterClient {
     logger: Logger,
     translator: Arc<Translator>,
-    polls: Arc<Mutex<PollManager<PollFilter>>>,
+    polls: Arc<Mutex<Filters>>,
 }
 
 impl EthFilterClient {
-    /// Creates new Eth filter client.
-    pub fn new(translator: Arc<Translator>) -> Self {
+    /// Creates new Eth filter client, installing filters in `polls`.
+    pub fn new(translator: Arc<Translator>, polls: Arc<Mutex<Filters>>) -> Self {
         EthFilterClient {
             logger: get_logger("gateway/impls/eth_filter"),
             translator,
-            polls: Arc::new(Mutex::new(PollManager::new())),
+            polls,
         }
     }
 }
@@ -72,19 +280,16 @@ impl EthFilter for EthFilterClient {
             .inc();
 
         let polls = self.polls.clone();
+        let owner = context::client();
         Box::new(
             self.translator
                 .get_latest_block()
                 .map_err(jsonrpc_error)
-                .map(move |blk| {
+                .and_then(move |blk| {
                     let mut polls = polls.lock();
-                    let id = polls.create_poll(PollFilter::Logs(
-                        blk.number_u64(),
-                        Default::default(),
-                        filter,
-                    ));
+                    let filter = PollFilter::Logs(blk.number_u64(), Default::default(), filter);
 
-                    id.into()
+                    polls.create_poll(filter, owner).map(Into::into)
                 }),
         )
     }
@@ -95,16 +300,17 @@ impl EthFilter for EthFilterClient {
             .inc();
 
         let polls = self.polls.clone();
+        let owner = context::client();
         Box::new(
             self.translator
                 .get_latest_block()
                 .map_err(jsonrpc_error)
-                .map(move |blk| {
+                .and_then(move |blk| {
                     let mut polls = polls.lock();
                     // +1, since we don't want to include the current block.
-                    let id = polls.create_poll(PollFilter::Block(blk.number_u64() + 1));
+                    let filter = PollFilter::Block(blk.number_u64() + 1);
 
-                    id.into()
+                    polls.create_poll(filter, owner).map(Into::into)
                 }),
         )
     }
@@ -115,9 +321,10 @@ impl EthFilter for EthFilterClient {
             .inc();
 
         // We don't have pending transactions, so this is a no-op filter.
-        let mut polls = self.polls.lock();
-        let id = polls.create_poll(PollFilter::PendingTransaction(vec![]));
-        Ok(id.into())
+        self.polls
+            .lock()
+            .create_poll(PollFilter::PendingTransaction(vec![]), context::client())
+            .map(Into::into)
     }
 
     fn filter_changes(&self, index: Index) -> BoxFuture<FilterChanges> {
@@ -134,12 +341,22 @@ impl EthFilter for EthFilterClient {
                 .map_err(jsonrpc_error)
                 .and_then(move |blk| -> BoxFuture<FilterChanges> {
                     let mut polls = polls.lock();
+                    let latest = blk.number_u64();
+                    let max_catch_up = polls.config.max_catch_up;
                     match polls.poll_mut(&index.value()) {
                         None => Box::new(future::err(errors::filter_not_found())),
-                        Some(PollFilter::Block(ref mut number)) => {
+                        Some(PollFilter::Block(ref mut next)) => {
+                            let (from, to) = match catch_up_range(*next, latest, max_catch_up) {
+                                Some(range) => range,
+                                None => return Box::new(future::ok(FilterChanges::Hashes(vec![]))),
+                            };
+
+                            // Save the number of the next block to report.
+                            *next = to + 1;
+
                             // TODO: Should we support block range fetch?
-                            let updates = Box::new(
-                                stream::iter_ok(*number..=blk.number_u64())
+                            Box::new(
+                                stream::iter_ok(from..=to)
                                     .and_then(move |round| translator.get_block_by_round(round))
                                     .and_then(|blk| match blk {
                                         Some(blk) => Ok(blk),
@@ -149,24 +366,26 @@ impl EthFilter for EthFilterClient {
                                     .collect()
                                     .map_err(jsonrpc_error)
                                     .map(FilterChanges::Hashes),
-                            );
-
-                            *number = blk.number_u64();
-                            updates
+                            )
                         }
                         Some(PollFilter::PendingTransaction(_)) => {
                             // We don't have pending transactions, so this is a no-op filter.
                             Box::new(future::ok(FilterChanges::Hashes(vec![])))
                         }
-                        Some(PollFilter::Logs(ref mut block_number, _, ref filter)) => {
+                        Some(PollFilter::Logs(ref mut next, _, ref filter)) => {
+                            let (from, to) = match catch_up_range(*next, latest, max_catch_up) {
+                                Some(range) => range,
+                                None => return Box::new(future::ok(FilterChanges::Logs(vec![]))),
+                            };
+
                             // Build appropriate filter.
                             let mut filter: EthcoreFilter = filter.clone().into();
-                            filter.from_block = BlockId::Number(*block_number);
-                            filter.to_block = BlockId::Latest;
+                            filter.from_block = BlockId::Number(from);
+                            filter.to_block = BlockId::Number(to);
 
                             // Save the number of the next block as a first block from which
                             // we want to get logs.
-                            *block_number = blk.number_u64() + 1;
+                            *next = to + 1;
 
                             let limit = filter.limit;
                             Box::new(
@@ -222,3 +441,87 @@ impl EthFilter for EthFilterClient {
         Ok(self.polls.lock().remove_poll(&index.value()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(n: u8) -> Option<Client> {
+        Some(Client::Ip([10, 0, 0, n].into()))
+    }
+
+    #[test]
+    fn should_limit_catch_up_range() {
+        assert_eq!(catch_up_range(5, 4, 10), None);
+        assert_eq!(catch_up_range(5, 5, 10), Some((5, 5)));
+        assert_eq!(catch_up_range(5, 8, 10), Some((5, 8)));
+        assert_eq!(catch_up_range(5, 100, 10), Some((5, 14)));
+        assert_eq!(catch_up_range(5, 100, 0), Some((5, 100)));
+    }
+
+    #[test]
+    fn should_expire_filters() {
+        let mut filters = Filters::new(FilterConfig {
+            lifetime: Duration::from_secs(60),
+            ..Default::default()
+        });
+        let id = filters.create_poll(PollFilter::Block(0), None).unwrap();
+        assert!(filters.poll(&id).is_some());
+
+        let mut filters = Filters::new(FilterConfig {
+            lifetime: Duration::from_secs(0),
+            ..Default::default()
+        });
+        let id = filters.create_poll(PollFilter::Block(0), None).unwrap();
+        assert!(filters.poll(&id).is_none());
+        assert!(filters.list().is_empty());
+    }
+
+    #[test]
+    fn should_limit_filters_per_client() {
+        let mut filters = Filters::new(FilterConfig {
+            max_per_client: 2,
+            ..Default::default()
+        });
+
+        let first = filters
+            .create_poll(PollFilter::Block(0), client(1))
+            .unwrap();
+        assert!(filters.create_poll(PollFilter::Block(0), client(1)).is_ok());
+        assert!(filters
+            .create_poll(PollFilter::Block(0), client(1))
+            .is_err());
+
+        // other clients and clients with an unknown address are not affected
+        assert!(filters.create_poll(PollFilter::Block(0), client(2)).is_ok());
+        for _ in 0..3 {
+            assert!(filters.create_poll(PollFilter::Block(0), None).is_ok());
+        }
+
+        // uninstalling a filter frees a slot
+        assert!(filters.remove_poll(&first));
+        assert!(filters.create_poll(PollFilter::Block(0), client(1)).is_ok());
+    }
+
+    #[test]
+    fn should_limit_total_filters() {
+        let mut filters = Filters::new(FilterConfig {
+            max_per_client: 2,
+            max_total: 3,
+            ..Default::default()
+        });
+
+        assert!(filters.create_poll(PollFilter::Block(0), client(1)).is_ok());
+        let unknown = filters.create_poll(PollFilter::Block(0), None).unwrap();
+        assert!(filters.create_poll(PollFilter::Block(0), None).is_ok());
+
+        // clients with an unknown address count towards the total limit
+        assert!(filters.create_poll(PollFilter::Block(0), None).is_err());
+        assert!(filters
+            .create_poll(PollFilter::Block(0), client(2))
+            .is_err());
+
+        assert!(filters.remove_poll(&unknown));
+        assert!(filters.create_poll(PollFilter::Block(0), client(2)).is_ok());
+    }
+}
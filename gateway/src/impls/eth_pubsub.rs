@@ -18,34 +18,57 @@
 
 //! Eth PUB-SUB rpc implementation.
 
-use std::sync::{Arc, Weak};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    time::{Duration, Instant},
+};
 
 use anyhow::anyhow;
 use ethcore::{
     filter::{Filter as EthFilter, TxEntry as EthTxEntry, TxFilter as EthTxFilter},
     ids::BlockId,
 };
-use futures::{prelude::*, stream};
-use jsonrpc_core::Result;
+use futures::{
+    future::{self, Loop},
+    prelude::*,
+    stream,
+};
+use jsonrpc_core::{Error, ErrorCode, Result};
 use jsonrpc_macros::{
     pubsub::{Sink, Subscriber},
     Trailing,
 };
 use jsonrpc_pubsub::SubscriptionId;
 use lazy_static::lazy_static;
-use oasis_core_runtime::common::logger::get_logger;
 use parity_rpc::v1::{
     helpers::{errors, Subscribers},
-    metadata::Metadata,
-    traits::EthPubSub,
-    types::{pubsub, TransactionOutcome},
+    metadata::Origin,
+    types::{pubsub, BlockNumber, TransactionOutcome, H256},
+};
+use parking_lot::{Mutex, RwLock};
+use prometheus::{
+    labels, register_int_counter, register_int_counter_vec, IntCounter, IntCounterVec,
 };
-use parking_lot::RwLock;
-use prometheus::{labels, register_int_counter_vec, IntCounterVec};
 use slog::{error, info, warn, Logger};
-use tokio::spawn;
+use tokio::{spawn, timer::Delay};
 
-use crate::{pubsub::Listener, translator::Translator};
+use crate::{
+    logger::get_logger,
+    metadata::Metadata,
+    nodes::SyncStatus,
+    pubsub::Listener,
+    ratelimit::WsClients,
+    traits::{
+        admin::RpcSubscriptions,
+        eth::{RpcSubscriptionResult, RpcSyncStatus},
+        EthPubSub,
+    },
+    translator::Translator,
+    util::{jsonrpc_error, ERROR_LIMIT_EXCEEDED},
+};
 
 // Metrics.
 lazy_static! {
@@ -55,39 +78,240 @@ lazy_static! {
         &["call"]
     )
     .unwrap();
+    static ref ETH_PUBSUB_SLOW_SUBSCRIBERS: IntCounter = register_int_counter!(
+        "web3_gateway_eth_pubsub_slow_subscribers_dropped",
+        "Number of subscribers dropped for not keeping up with notifications"
+    )
+    .unwrap();
 }
 
-type PubSubClient = Sink<pubsub::Result>;
+/// Number of rounds whose logs are fetched at once when a logs subscription
+/// replays logs from a historical block.
+const LOGS_REPLAY_CHUNK: u64 = 100;
+
+/// Delay before retrying to fetch logs to replay, after a failure.
+const LOGS_REPLAY_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Number of consecutive failures to fetch logs to replay after which the
+/// subscription is terminated.
+const LOGS_REPLAY_MAX_FAILURES: u32 = 5;
+
+type PubSubClient = Sink<RpcSubscriptionResult>;
+
+type SubscriberSet<T> = Arc<RwLock<Subscribers<(PubSubClient, Subscription<T>)>>>;
+
+type ReplayStep = Box<dyn Future<Item = Loop<(), u32>, Error = ()> + Send>;
+
+/// Notifications being sent to a subscriber.
+#[derive(Clone)]
+struct Queue {
+    /// WebSocket session of the subscriber, if any.
+    session: Option<H256>,
+    queued: Arc<AtomicUsize>,
+    /// Whether the subscriber was dropped for being too slow.
+    dropped: Arc<AtomicBool>,
+}
+
+/// Subscription with the parameters of its kind.
+struct Subscription<T> {
+    params: T,
+    queue: Queue,
+}
+
+impl<T> Subscription<T> {
+    fn new(params: T, session: Option<H256>) -> Self {
+        Subscription {
+            params,
+            queue: Queue {
+                session,
+                queued: Default::default(),
+                dropped: Default::default(),
+            },
+        }
+    }
+}
+
+/// Progress of a logs subscription.
+struct LogsCursor {
+    /// Next round whose logs are not delivered yet.
+    next: u64,
+    /// Whether the subscription gets live notifications, or is still
+    /// replaying logs from a historical block.
+    live: bool,
+}
+
+impl LogsCursor {
+    /// Returns the next rounds to replay logs for, up to `last_block`, or
+    /// `None` once the replay caught up.
+    fn replay_range(&self, last_block: u64) -> Option<(u64, u64)> {
+        if self.next > last_block {
+            return None;
+        }
+        Some((self.next, last_block.min(self.next + LOGS_REPLAY_CHUNK - 1)))
+    }
+
+    /// Returns the rounds of notified blocks to send logs for, skipping the
+    /// rounds already delivered, and moves the cursor past them. Returns
+    /// `None` while the subscription is replaying logs.
+    fn notify_range(&mut self, from_block: u64, to_block: u64) -> Option<(u64, u64)> {
+        if !self.live || self.next > to_block {
+            return None;
+        }
+        let from_block = from_block.max(self.next);
+        self.next = to_block + 1;
+        Some((from_block, to_block))
+    }
+}
+
+/// Parameters of a logs subscription.
+struct LogsParams {
+    filter: EthFilter,
+    cursor: Arc<Mutex<LogsCursor>>,
+}
+
+/// Remove the subscribers without a session that were dropped for being too
+/// slow. Subscribers with a session are removed once it is closed.
+fn remove_dropped<S, T>(subscribers: &RwLock<Subscribers<(S, Subscription<T>)>>) {
+    let dropped: Vec<_> = subscribers
+        .read()
+        .iter()
+        .filter(|&(_, &(_, ref subscription))| {
+            subscription.queue.session.is_none()
+                && subscription.queue.dropped.load(Ordering::SeqCst)
+        })
+        .map(|(id, _)| SubscriptionId::String(id.as_string()))
+        .collect();
+    if dropped.is_empty() {
+        return;
+    }
+
+    let mut subscribers = subscribers.write();
+    for id in dropped {
+        subscribers.remove(&id);
+    }
+}
+
+fn error_subscription_limit(max_subscriptions: usize) -> Error {
+    Error {
+        code: ErrorCode::ServerError(ERROR_LIMIT_EXCEEDED),
+        message: format!("Too many subscriptions (max {})", max_subscriptions),
+        data: None,
+    }
+}
+
+fn error_replay_limit(max_replays: usize) -> Error {
+    Error {
+        code: ErrorCode::ServerError(ERROR_LIMIT_EXCEEDED),
+        message: format!(
+            "Too many logs subscriptions replaying logs (max {})",
+            max_replays
+        ),
+        data: None,
+    }
+}
+
+fn error_replay_depth(max_depth: u64) -> Error {
+    Error {
+        code: ErrorCode::ServerError(ERROR_LIMIT_EXCEEDED),
+        message: format!(
+            "Cannot replay logs from more than {} blocks behind the latest block",
+            max_depth
+        ),
+        data: None,
+    }
+}
+
+/// Sends notifications, dropping the subscribers which do not keep up.
+#[derive(Clone)]
+struct Notifier {
+    logger: Logger,
+    ws_clients: Arc<WsClients>,
+    max_queued: usize,
+}
+
+impl Notifier {
+    /// Count a notification queued for a subscriber. Returns false if the
+    /// subscriber has the max number of queued notifications, dropping it.
+    fn enqueue(&self, queue: &Queue) -> bool {
+        let queued = queue.queued.fetch_add(1, Ordering::SeqCst);
+        if self.max_queued == 0 || queued < self.max_queued {
+            return true;
+        }
+        queue.queued.fetch_sub(1, Ordering::SeqCst);
+
+        // Close the session of the subscriber, so that the client knows it
+        // missed notifications. Subscribers without a session are removed
+        // before the next notifications.
+        if !queue.dropped.swap(true, Ordering::SeqCst) {
+            ETH_PUBSUB_SLOW_SUBSCRIBERS.inc();
+            warn!(self.logger, "Dropping slow subscriber"; "session" => ?queue.session);
+            if let Some(ref session) = queue.session {
+                self.ws_clients.disconnect(session);
+            }
+        }
+        false
+    }
+
+    fn notify(&self, subscriber: &PubSubClient, queue: &Queue, result: RpcSubscriptionResult) {
+        if !self.enqueue(queue) {
+            return;
+        }
+
+        let logger = self.logger.clone();
+        let queued = queue.queued.clone();
+        spawn(subscriber.notify(Ok(result)).then(move |result| {
+            queued.fetch_sub(1, Ordering::SeqCst);
+            if let Err(err) = result {
+                warn!(logger, "Unable to send notification"; "err" => ?err);
+            }
+            Ok(())
+        }));
+    }
+}
 
 /// Eth PubSub implementation.
 pub struct EthPubSubClient {
     logger: Logger,
     handler: Arc<ChainNotificationHandler>,
-    heads_subscribers: Arc<RwLock<Subscribers<PubSubClient>>>,
-    logs_subscribers: Arc<RwLock<Subscribers<(PubSubClient, EthFilter)>>>,
-    tx_subscribers: Arc<RwLock<Subscribers<(PubSubClient, EthTxFilter)>>>,
+    ws_clients: Arc<WsClients>,
+    heads_subscribers: SubscriberSet<()>,
+    logs_subscribers: SubscriberSet<LogsParams>,
+    tx_subscribers: SubscriberSet<EthTxFilter>,
+    syncing_subscribers: SubscriberSet<()>,
 }
 
 impl EthPubSubClient {
-    /// Creates new `EthPubSubClient`.
-    pub fn new(translator: Arc<Translator>) -> Self {
+    /// Creates new `EthPubSubClient`, limiting the subscriptions of the
+    /// WebSocket sessions tracked by `ws_clients`.
+    pub fn new(translator: Arc<Translator>, ws_clients: Arc<WsClients>) -> Self {
         let heads_subscribers = Arc::new(RwLock::new(Subscribers::default()));
         let logs_subscribers = Arc::new(RwLock::new(Subscribers::default()));
         let tx_subscribers = Arc::new(RwLock::new(Subscribers::default()));
+        let syncing_subscribers = Arc::new(RwLock::new(Subscribers::default()));
         let logger = get_logger("gateway/impls/eth_pubsub");
 
         EthPubSubClient {
             logger: logger.clone(),
             handler: Arc::new(ChainNotificationHandler {
-                logger,
+                logger: logger.clone(),
                 translator,
+                notifier: Notifier {
+                    logger,
+                    ws_clients: ws_clients.clone(),
+                    max_queued: ws_clients.session_config().max_queued_notifications,
+                },
                 heads_subscribers: heads_subscribers.clone(),
                 logs_subscribers: logs_subscribers.clone(),
                 tx_subscribers: tx_subscribers.clone(),
+                syncing_subscribers: syncing_subscribers.clone(),
+                last_block: AtomicU64::new(0),
+                replays: AtomicUsize::new(0),
             }),
+            ws_clients,
             heads_subscribers,
             logs_subscribers,
             tx_subscribers,
+            syncing_subscribers,
         }
     }
 
@@ -101,20 +325,34 @@ impl EthPubSubClient {
 pub struct ChainNotificationHandler {
     logger: Logger,
     translator: Arc<Translator>,
-    heads_subscribers: Arc<RwLock<Subscribers<PubSubClient>>>,
-    logs_subscribers: Arc<RwLock<Subscribers<(PubSubClient, EthFilter)>>>,
-    tx_subscribers: Arc<RwLock<Subscribers<(PubSubClient, EthTxFilter)>>>,
+    notifier: Notifier,
+    heads_subscribers: SubscriberSet<()>,
+    logs_subscribers: SubscriberSet<LogsParams>,
+    tx_subscribers: SubscriberSet<EthTxFilter>,
+    syncing_subscribers: SubscriberSet<()>,
+    /// Last round notified to the handler, or fetched as the latest round
+    /// for a replay.
+    last_block: AtomicU64,
+    /// Number of logs subscriptions replaying logs.
+    replays: AtomicUsize,
 }
 
 impl ChainNotificationHandler {
-    fn notify(logger: &Logger, subscriber: &PubSubClient, result: pubsub::Result) {
-        let logger = logger.clone();
-        spawn(
-            subscriber
-                .notify(Ok(result))
-                .map(|_| ())
-                .map_err(move |err| warn!(logger, "Unable to send notification"; "err" => ?err)),
-        );
+    /// Returns the number of subscriptions of each kind.
+    pub fn subscriptions(&self) -> RpcSubscriptions {
+        RpcSubscriptions {
+            new_heads: self.heads_subscribers.read().len(),
+            logs: self.logs_subscribers.read().len(),
+            completed_transaction: self.tx_subscribers.read().len(),
+            syncing: self.syncing_subscribers.read().len(),
+        }
+    }
+
+    fn remove_dropped_subscribers(&self) {
+        remove_dropped(&self.heads_subscribers);
+        remove_dropped(&self.logs_subscribers);
+        remove_dropped(&self.tx_subscribers);
+        remove_dropped(&self.syncing_subscribers);
     }
 
     fn notify_heads(&self, from_block: u64, to_block: u64) {
@@ -127,7 +365,7 @@ impl ChainNotificationHandler {
         let heads_subscribers = self.heads_subscribers.clone();
         let translator = self.translator.clone();
         let logger = self.logger.clone();
-        let logger2 = self.logger.clone();
+        let notifier = self.notifier.clone();
         spawn(
             stream::iter_ok(from_block..=to_block)
                 .and_then(move |round| translator.get_block_by_round(round))
@@ -142,8 +380,12 @@ impl ChainNotificationHandler {
                     let subscribers = heads_subscribers.read();
 
                     for header in headers {
-                        for subscriber in subscribers.values() {
-                            Self::notify(&logger2, subscriber, pubsub::Result::Header(header.clone()));
+                        for &(ref subscriber, ref subscription) in subscribers.values() {
+                            notifier.notify(
+                                subscriber,
+                                &subscription.queue,
+                                RpcSubscriptionResult::Eth(pubsub::Result::Header(header.clone())),
+                            );
                         }
                     }
                 }),
@@ -151,23 +393,45 @@ impl ChainNotificationHandler {
     }
 
     fn notify_logs(&self, from_block: u64, to_block: u64) {
-        for &(ref subscriber, ref filter) in self.logs_subscribers.read().values() {
-            let mut filter = filter.clone();
+        // Replaying subscriptions switch to live notifications while holding
+        // the write lock, so they either see this round as notified, or are
+        // notified of it here.
+        let subscribers = self.logs_subscribers.read();
+        self.last_block.fetch_max(to_block, Ordering::SeqCst);
+
+        for &(ref subscriber, ref subscription) in subscribers.values() {
+            // Skip the rounds the subscription already got logs for.
+            let from_block = match subscription
+                .params
+                .cursor
+                .lock()
+                .notify_range(from_block, to_block)
+            {
+                Some((from_block, _)) => from_block,
+                None => continue,
+            };
+
+            let mut filter = subscription.params.filter.clone();
 
             // Limit query range.
             filter.from_block = BlockId::Number(from_block);
             filter.to_block = BlockId::Number(to_block);
 
             let subscriber = subscriber.clone();
+            let queue = subscription.queue.clone();
+            let notifier = self.notifier.clone();
             let logger = self.logger.clone();
-            let logger2 = self.logger.clone();
 
             spawn(
                 self.translator
                     .logs(filter)
                     .map(move |logs| {
                         for log in logs {
-                            Self::notify(&logger2, &subscriber, pubsub::Result::Log(log.into()));
+                            notifier.notify(
+                                &subscriber,
+                                &queue,
+                                RpcSubscriptionResult::Eth(pubsub::Result::Log(log.into())),
+                            );
                         }
                     })
                     .map_err(move |err| {
@@ -178,27 +442,276 @@ impl ChainNotificationHandler {
             );
         }
     }
+
+    /// Add a syncing subscription, and send it the current sync state, as
+    /// later notifications are only sent when the sync state changes.
+    fn subscribe_syncing(
+        &self,
+        subscriber: Subscriber<RpcSubscriptionResult>,
+        session: Option<H256>,
+    ) {
+        let subscription = Subscription::new((), session);
+        let queue = subscription.queue.clone();
+
+        // Hold the write lock until the current sync state is queued, so that
+        // it is sent before notifications of later changes.
+        let mut subscribers = self.syncing_subscribers.write();
+        subscribers.push(subscriber, subscription);
+        let sink = subscribers
+            .values()
+            .find(|&&(_, ref subscription)| Arc::ptr_eq(&subscription.queue.queued, &queue.queued))
+            .map(|&(ref subscriber, _)| subscriber.clone());
+        if let Some(sink) = sink {
+            let status = self.translator.nodes().sync_status();
+            self.notifier
+                .notify(&sink, &queue, RpcSubscriptionResult::Syncing(status.into()));
+        }
+    }
+
+    /// Count a logs replay, unless the max number of logs subscriptions
+    /// are replaying logs.
+    fn start_replay(&self) -> bool {
+        let max_replays = self.notifier.ws_clients.session_config().max_logs_replays;
+        if self.replays.fetch_add(1, Ordering::SeqCst) < max_replays || max_replays == 0 {
+            return true;
+        }
+        self.replays.fetch_sub(1, Ordering::SeqCst);
+        false
+    }
+
+    /// Add a logs subscription replaying logs from a historical block. The
+    /// replay must have been counted with `start_replay`.
+    ///
+    /// No blocks may have been notified yet, so the latest round is fetched
+    /// first and the replay covers at least the rounds up to it, after which
+    /// the notified blocks start.
+    fn subscribe_replaying_logs(
+        self: Arc<Self>,
+        subscriber: Subscriber<RpcSubscriptionResult>,
+        filter: EthFilter,
+        from_block: u64,
+        session: Option<H256>,
+    ) -> impl Future<Item = (), Error = ()> {
+        let handler = self.clone();
+        self.translator
+            .get_latest_block()
+            .then(move |result| {
+                let max_depth = self
+                    .notifier
+                    .ws_clients
+                    .session_config()
+                    .max_logs_replay_depth;
+                let error = match result {
+                    Ok(ref blk)
+                        if max_depth != 0
+                            && blk.number_u64().saturating_sub(from_block) > max_depth =>
+                    {
+                        error_replay_depth(max_depth)
+                    }
+                    Ok(blk) => {
+                        self.last_block
+                            .fetch_max(blk.number_u64(), Ordering::SeqCst);
+
+                        let cursor = Arc::new(Mutex::new(LogsCursor {
+                            next: from_block,
+                            live: false,
+                        }));
+                        self.logs_subscribers.write().push(
+                            subscriber,
+                            Subscription::new(
+                                LogsParams {
+                                    filter,
+                                    cursor: cursor.clone(),
+                                },
+                                session,
+                            ),
+                        );
+                        return future::Either::B(self.replay_logs(cursor));
+                    }
+                    Err(err) => {
+                        error!(self.logger, "Failed to fetch latest block to replay logs";
+                            "err" => ?err,
+                        );
+                        jsonrpc_error(err)
+                    }
+                };
+
+                if let Some(ref session) = session {
+                    self.notifier.ws_clients.remove_subscription(session);
+                }
+                let _ = subscriber.reject(error);
+                future::Either::A(future::ok(()))
+            })
+            .then(move |result| {
+                handler.replays.fetch_sub(1, Ordering::SeqCst);
+                result
+            })
+    }
+
+    /// Returns the sink and filter of a logs subscription, unless it was
+    /// removed.
+    fn logs_subscription(
+        &self,
+        cursor: &Arc<Mutex<LogsCursor>>,
+    ) -> Option<(PubSubClient, EthFilter)> {
+        self.logs_subscribers
+            .read()
+            .values()
+            .find(|&&(_, ref subscription)| Arc::ptr_eq(&subscription.params.cursor, cursor))
+            .map(|&(ref subscriber, ref subscription)| {
+                (subscriber.clone(), subscription.params.filter.clone())
+            })
+    }
+
+    /// Removes a logs subscription, unless it was already removed.
+    fn remove_logs_subscription(&self, cursor: &Arc<Mutex<LogsCursor>>) {
+        let mut subscribers = self.logs_subscribers.write();
+        let id = subscribers
+            .iter()
+            .find(|&(_, &(_, ref subscription))| Arc::ptr_eq(&subscription.params.cursor, cursor))
+            .map(|(id, _)| SubscriptionId::String(id.as_string()));
+        if let Some((_, subscription)) = id.and_then(|id| subscribers.remove(&id)) {
+            if let Some(ref session) = subscription.queue.session {
+                self.notifier.ws_clients.remove_subscription(session);
+            }
+        }
+    }
+
+    /// Replay the logs of a subscription from its cursor up to the last
+    /// round known to the handler, in chunks of `LOGS_REPLAY_CHUNK` rounds,
+    /// then switch it to live notifications.
+    ///
+    /// The subscription is terminated with an error after
+    /// `LOGS_REPLAY_MAX_FAILURES` consecutive failures to fetch logs.
+    fn replay_logs(
+        self: Arc<Self>,
+        cursor: Arc<Mutex<LogsCursor>>,
+    ) -> impl Future<Item = (), Error = ()> {
+        future::loop_fn(0, move |failures| -> ReplayStep {
+            let (subscriber, mut filter) = match self.logs_subscription(&cursor) {
+                Some(subscription) => subscription,
+                // The subscription was removed.
+                None => return Box::new(future::ok(Loop::Break(()))),
+            };
+
+            let range = cursor
+                .lock()
+                .replay_range(self.last_block.load(Ordering::SeqCst));
+            let (next, to_block) = match range {
+                Some(range) => range,
+                None => {
+                    // Blocks may have been notified since the last check.
+                    let _subscribers = self.logs_subscribers.write();
+                    let mut cursor = cursor.lock();
+                    if cursor
+                        .replay_range(self.last_block.load(Ordering::SeqCst))
+                        .is_none()
+                    {
+                        cursor.live = true;
+                        return Box::new(future::ok(Loop::Break(())));
+                    }
+                    return Box::new(future::ok(Loop::Continue(failures)));
+                }
+            };
+
+            filter.from_block = BlockId::Number(next);
+            filter.to_block = BlockId::Number(to_block);
+
+            let handler = self.clone();
+            let cursor = cursor.clone();
+            let logger = self.logger.clone();
+            Box::new(
+                self.translator
+                    .logs(filter)
+                    .then(move |result| -> ReplayStep {
+                        let logs = match result {
+                            Ok(logs) => logs,
+                            Err(err) => {
+                                error!(logger, "Failed to fetch logs to replay";
+                                    "err" => ?err,
+                                    "failures" => failures + 1,
+                                );
+                                if failures + 1 < LOGS_REPLAY_MAX_FAILURES {
+                                    return Box::new(
+                                        Delay::new(Instant::now() + LOGS_REPLAY_RETRY_DELAY).then(
+                                            move |_| Ok::<_, ()>(Loop::Continue(failures + 1)),
+                                        ),
+                                    );
+                                }
+
+                                // Give up and let the subscriber know.
+                                handler.remove_logs_subscription(&cursor);
+                                return Box::new(
+                                    subscriber
+                                        .notify(Err(jsonrpc_error(err)))
+                                        .then(|_| Ok::<_, ()>(Loop::Break(()))),
+                                );
+                            }
+                        };
+
+                        // Send the logs in order, and stop once the subscriber
+                        // is gone.
+                        Box::new(
+                            stream::iter_ok(logs)
+                                .for_each(move |log| {
+                                    subscriber
+                                        .notify(Ok(RpcSubscriptionResult::Eth(
+                                            pubsub::Result::Log(log.into()),
+                                        )))
+                                        .map(|_| ())
+                                })
+                                .then(move |result| match result {
+                                    Ok(()) => {
+                                        cursor.lock().next = to_block + 1;
+                                        Ok::<_, ()>(Loop::Continue(0))
+                                    }
+                                    Err(err) => {
+                                        warn!(logger, "Unable to send notification"; "err" => ?err);
+                                        Ok(Loop::Break(()))
+                                    }
+                                }),
+                        )
+                    }),
+            )
+        })
+    }
 }
 
 impl Listener for ChainNotificationHandler {
     fn notify_blocks(&self, from_block: u64, to_block: u64) {
+        self.remove_dropped_subscribers();
         self.notify_heads(from_block, to_block);
         self.notify_logs(from_block, to_block);
     }
 
     fn notify_completed_transaction(&self, entry: &EthTxEntry, output: Vec<u8>) {
-        for &(ref subscriber, ref filter) in self.tx_subscribers.read().values() {
-            if !filter.matches(entry) {
+        self.remove_dropped_subscribers();
+        for &(ref subscriber, ref subscription) in self.tx_subscribers.read().values() {
+            if !subscription.params.matches(entry) {
                 continue;
             }
 
-            Self::notify(
-                &self.logger,
-                &subscriber,
-                pubsub::Result::TransactionOutcome(TransactionOutcome {
-                    hash: entry.transaction_hash.into(),
-                    output: output.clone(),
-                }),
+            self.notifier.notify(
+                subscriber,
+                &subscription.queue,
+                RpcSubscriptionResult::Eth(pubsub::Result::TransactionOutcome(
+                    TransactionOutcome {
+                        hash: entry.transaction_hash.into(),
+                        output: output.clone(),
+                    },
+                )),
+            );
+        }
+    }
+
+    fn notify_sync_status(&self, status: SyncStatus) {
+        self.remove_dropped_subscribers();
+        let status = RpcSyncStatus::from(status);
+        for &(ref subscriber, ref subscription) in self.syncing_subscribers.read().values() {
+            self.notifier.notify(
+                subscriber,
+                &subscription.queue,
+                RpcSubscriptionResult::Syncing(status.clone()),
             );
         }
     }
@@ -209,8 +722,8 @@ impl EthPubSub for EthPubSubClient {
 
     fn subscribe(
         &self,
-        _meta: Metadata,
-        subscriber: Subscriber<pubsub::Result>,
+        meta: Metadata,
+        subscriber: Subscriber<RpcSubscriptionResult>,
         kind: pubsub::Kind,
         params: Trailing<pubsub::Params>,
     ) {
@@ -224,23 +737,79 @@ impl EthPubSub for EthPubSubClient {
                 "kind" => ?kind
         );
 
+        let session = match meta.origin {
+            Origin::Ws { ref session, .. } => Some(session.clone()),
+            _ => None,
+        };
+        if let Some(ref session) = session {
+            if !self.ws_clients.add_subscription(session) {
+                let max_subscriptions = self.ws_clients.session_config().max_subscriptions;
+                let _ = subscriber.reject(error_subscription_limit(max_subscriptions));
+                return;
+            }
+        }
+
         let error = match (kind, params.into()) {
             (pubsub::Kind::NewHeads, None) => {
-                self.heads_subscribers.write().push(subscriber);
+                self.heads_subscribers
+                    .write()
+                    .push(subscriber, Subscription::new((), session));
                 return;
             }
             (pubsub::Kind::NewHeads, _) => {
                 errors::invalid_params("newHeads", "Expected no parameters.")
             }
             (pubsub::Kind::Logs, Some(pubsub::Params::Logs(filter))) => {
-                self.logs_subscribers
-                    .write()
-                    .push(subscriber, filter.into());
-                return;
+                // Subscriptions from a historical block first replay its logs.
+                let replay_from = match filter.from_block {
+                    Some(BlockNumber::Num(number)) => Some(number),
+                    Some(BlockNumber::Earliest) => Some(0),
+                    _ => None,
+                };
+                match replay_from {
+                    Some(from_block) if self.handler.start_replay() => {
+                        spawn(self.handler.clone().subscribe_replaying_logs(
+                            subscriber,
+                            filter.into(),
+                            from_block,
+                            session,
+                        ));
+                        return;
+                    }
+                    Some(_) => {
+                        let max_replays = self.ws_clients.session_config().max_logs_replays;
+                        error_replay_limit(max_replays)
+                    }
+                    None => {
+                        self.logs_subscribers.write().push(
+                            subscriber,
+                            Subscription::new(
+                                LogsParams {
+                                    filter: filter.into(),
+                                    cursor: Arc::new(Mutex::new(LogsCursor {
+                                        next: 0,
+                                        live: true,
+                                    })),
+                                },
+                                session,
+                            ),
+                        );
+                        return;
+                    }
+                }
             }
             (pubsub::Kind::Logs, _) => errors::invalid_params("logs", "Expected a filter object."),
+            (pubsub::Kind::Syncing, None) => {
+                self.handler.subscribe_syncing(subscriber, session);
+                return;
+            }
+            (pubsub::Kind::Syncing, _) => {
+                errors::invalid_params("syncing", "Expected no parameters.")
+            }
             (pubsub::Kind::CompletedTransaction, Some(pubsub::Params::Transaction(filter))) => {
-                self.tx_subscribers.write().push(subscriber, filter.into());
+                self.tx_subscribers
+                    .write()
+                    .push(subscriber, Subscription::new(filter.into(), session));
                 return;
             }
             // we don't track pending transactions currently
@@ -248,6 +817,9 @@ impl EthPubSub for EthPubSubClient {
             _ => errors::unimplemented(None),
         };
 
+        if let Some(ref session) = session {
+            self.ws_clients.remove_subscription(session);
+        }
         let _ = subscriber.reject(error);
     }
 
@@ -257,10 +829,210 @@ impl EthPubSub for EthPubSubClient {
             .inc();
         info!(self.logger, "unsubscribe"; "id" => ?id);
 
-        let res = self.heads_subscribers.write().remove(&id).is_some();
-        let res2 = self.logs_subscribers.write().remove(&id).is_some();
-        let res3 = self.tx_subscribers.write().remove(&id).is_some();
+        let session = match (
+            self.heads_subscribers.write().remove(&id),
+            self.logs_subscribers.write().remove(&id),
+            self.tx_subscribers.write().remove(&id),
+            self.syncing_subscribers.write().remove(&id),
+        ) {
+            (Some((_, subscription)), ..) => subscription.queue.session,
+            (_, Some((_, subscription)), ..) => subscription.queue.session,
+            (_, _, Some((_, subscription)), _) => subscription.queue.session,
+            (.., Some((_, subscription))) => subscription.queue.session,
+            (None, None, None, None) => return Ok(false),
+        };
+
+        if let Some(ref session) = session {
+            self.ws_clients.remove_subscription(session);
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{sync::mpsc, thread};
+
+    use ws;
+
+    /// Server session tracked by the WebSocket clients.
+    struct ServerSession {
+        out: ws::Sender,
+        ws_clients: Arc<WsClients>,
+    }
+
+    impl ws::Handler for ServerSession {
+        fn on_open(&mut self, _shake: ws::Handshake) -> ws::Result<()> {
+            self.ws_clients
+                .open_session(H256::from(1), Default::default(), Some(self.out.clone()));
+            Ok(())
+        }
+    }
+
+    /// Client reporting the code its connection is closed with.
+    struct ClientSession(mpsc::Sender<ws::CloseCode>);
+
+    impl ws::Handler for ClientSession {
+        fn on_close(&mut self, code: ws::CloseCode, _reason: &str) {
+            let _ = self.0.send(code);
+        }
+    }
+
+    fn queue(session: Option<H256>) -> Queue {
+        Queue {
+            session,
+            queued: Default::default(),
+            dropped: Default::default(),
+        }
+    }
+
+    #[test]
+    fn should_drop_slow_subscribers() {
+        let ws_clients = Arc::new(WsClients::new(&Default::default(), Default::default()));
+        let notifier = Notifier {
+            logger: get_logger("gateway/impls/eth_pubsub/tests"),
+            ws_clients: ws_clients.clone(),
+            max_queued: 2,
+        };
+
+        let server_clients = ws_clients.clone();
+        let server = ws::Builder::new()
+            .build(move |out| ServerSession {
+                out,
+                ws_clients: server_clients.clone(),
+            })
+            .unwrap()
+            .bind("127.0.0.1:0")
+            .unwrap();
+        let url = format!("ws://{}", server.local_addr().unwrap());
+        let broadcaster = server.broadcaster();
+        let server = thread::spawn(move || server.run().map(|_| ()));
+
+        let (close_tx, close_rx) = mpsc::channel();
+        let client =
+            thread::spawn(move || ws::connect(url, |_out| ClientSession(close_tx.clone())));
+        while ws_clients.sessions().is_empty() {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // Subscribers with a session are disconnected once they have the max
+        // number of queued notifications.
+        let dropped = ETH_PUBSUB_SLOW_SUBSCRIBERS.get();
+        let ws_queue = queue(Some(H256::from(1)));
+        assert!(notifier.enqueue(&ws_queue));
+        assert!(notifier.enqueue(&ws_queue));
+        assert!(!notifier.enqueue(&ws_queue));
+        assert!(!notifier.enqueue(&ws_queue));
+        assert!(ws_queue.dropped.load(Ordering::SeqCst));
+        assert_eq!(ETH_PUBSUB_SLOW_SUBSCRIBERS.get(), dropped + 1);
+        assert_eq!(
+            close_rx.recv_timeout(Duration::from_secs(10)).unwrap(),
+            ws::CloseCode::Policy
+        );
+        client.join().unwrap().unwrap();
+        broadcaster.shutdown().unwrap();
+        server.join().unwrap().unwrap();
+
+        // Subscribers without a session are removed.
+        let ipc_queue = queue(None);
+        assert!(notifier.enqueue(&ipc_queue));
+        assert!(notifier.enqueue(&ipc_queue));
+        assert!(!notifier.enqueue(&ipc_queue));
+        assert_eq!(ETH_PUBSUB_SLOW_SUBSCRIBERS.get(), dropped + 2);
+
+        let subscribers = RwLock::new(Subscribers::default());
+        subscribers.write().insert((
+            (),
+            Subscription {
+                params: (),
+                queue: ipc_queue,
+            },
+        ));
+        subscribers.write().insert((
+            (),
+            Subscription {
+                params: (),
+                queue: ws_queue,
+            },
+        ));
+        subscribers
+            .write()
+            .insert(((), Subscription::new((), None)));
+
+        remove_dropped(&subscribers);
+        let subscribers = subscribers.read();
+        assert_eq!(subscribers.len(), 2);
+        assert!(subscribers
+            .values()
+            .all(
+                |&(_, ref subscription)| subscription.queue.session.is_some()
+                    || !subscription.queue.dropped.load(Ordering::SeqCst)
+            ));
+    }
+
+    #[test]
+    fn should_replay_logs_then_switch_to_live() {
+        let mut cursor = LogsCursor {
+            next: 5,
+            live: false,
+        };
+
+        // Logs are replayed in chunks up to the last round.
+        let mut replayed = vec![];
+        while let Some((from_block, to_block)) = cursor.replay_range(250) {
+            // Blocks notified while replaying are skipped.
+            assert_eq!(cursor.notify_range(240, 250), None);
+            replayed.push((from_block, to_block));
+            cursor.next = to_block + 1;
+        }
+        assert_eq!(replayed, vec![(5, 104), (105, 204), (205, 250)]);
 
-        Ok(res || res2 || res3)
+        // Blocks notified since are replayed too.
+        assert_eq!(cursor.notify_range(251, 260), None);
+        assert_eq!(cursor.replay_range(260), Some((251, 260)));
+        cursor.next = 261;
+        assert_eq!(cursor.replay_range(260), None);
+
+        // Live subscriptions get the logs of notified blocks.
+        cursor.live = true;
+        assert_eq!(cursor.notify_range(261, 270), Some((261, 270)));
+        assert_eq!(cursor.next, 271);
+    }
+
+    #[test]
+    fn should_not_deliver_logs_twice() {
+        let mut cursor = LogsCursor {
+            next: 251,
+            live: true,
+        };
+
+        // Rounds the replay already covered are skipped.
+        assert_eq!(cursor.notify_range(240, 260), Some((251, 260)));
+        assert_eq!(cursor.notify_range(255, 260), None);
+        assert_eq!(cursor.notify_range(255, 270), Some((261, 270)));
+        assert_eq!(cursor.next, 271);
+    }
+
+    #[test]
+    fn should_replay_logs_up_to_latest_round() {
+        let mut cursor = LogsCursor {
+            next: 5,
+            live: false,
+        };
+
+        // Without notified blocks, the replay would switch to live at once,
+        // and miss the rounds before the first notified blocks.
+        assert_eq!(cursor.replay_range(0), None);
+
+        // The replay covers the rounds up to the latest one, fetched when
+        // subscribing, and the first notified blocks start from round 1.
+        while let Some((_, to_block)) = cursor.replay_range(250) {
+            cursor.next = to_block + 1;
+        }
+        assert_eq!(cursor.next, 251);
+        cursor.live = true;
+        assert_eq!(cursor.notify_range(1, 300), Some((251, 300)));
     }
 }
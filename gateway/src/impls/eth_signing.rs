@@ -2,7 +2,6 @@ use jsonrpc_core::{futures::future, BoxFuture};
 use lazy_static::lazy_static;
 use parity_rpc::v1::{
     helpers::errors,
-    metadata::Metadata,
     traits::EthSigning,
     types::{
         Bytes, RichRawTransaction, TransactionRequest, H160 as RpcH160, H256 as RpcH256,
@@ -11,6 +10,8 @@ use parity_rpc::v1::{
 };
 use prometheus::{labels, register_int_counter_vec, IntCounterVec};
 
+use crate::metadata::Metadata;
+
 // Metrics.
 lazy_static! {
     static ref ETH_SIGNING_RPC_CALLS: IntCounterVec = register_int_counter_vec!(
@@ -19,6 +19,7 @@
 //! This doesn't re-implement all of the RPC APIs, just those which aren't
 //! significantly generic to be reused.
 
+pub mod admin;
 pub mod eth;
 pub mod eth_filter;
 #[cfg(feature = "pubsub")]
@@ -31,6 +32,6 @@ pub mod web3;
 #[cfg(feature = "pubsub")]
 pub use self::eth_pubsub::EthPubSubClient;
 pub use self::{
-    eth::EthClient, eth_filter::EthFilterClient, eth_signing::EthSigningClient, net::NetClient,
-    oasis::OasisClient, web3::Web3Client,
+    admin::AdminClient, eth::EthClient, eth_filter::EthFilterClient, eth_signing::EthSigningClient,
+    net::NetClient, oasis::OasisClient, web3::Web3Client,
 };
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use ethereum_types::Address;
+use ethereum_types::{Address, H256};
 use futures::prelude::*;
 use hash::keccak;
 use io_context::Context;
@@ -8,11 +8,10 @@ use jsonrpc_core::BoxFuture;
 use jsonrpc_macros::Trailing;
 use lazy_static::lazy_static;
 use oasis_core_keymanager_client::{KeyManagerClient, KeyPairId};
-use oasis_core_runtime::common::logger::get_logger;
+use oasis_core_runtime::common::cbor;
 use parity_rpc::v1::{
     helpers::errors,
-    metadata::Metadata,
-    types::{BlockNumber, Bytes, H160 as RpcH160},
+    types::{BlockNumber, Bytes, H160 as RpcH160, H256 as RpcH256},
 };
 use prometheus::{
     labels, register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec,
@@ -20,9 +19,15 @@ use prometheus::{
 use slog::{debug, info, Logger};
 
 use crate::{
-    traits::oasis::{Oasis, RpcExecutionPayload, RpcPublicKeyPayload},
-    translator::Translator,
-    util::{block_number_to_id, execution_error, jsonrpc_error},
+    context,
+    logger::get_logger,
+    metadata::Metadata,
+    traits::oasis::{
+        Oasis, RpcAccountProof, RpcExecutionPayload, RpcProofEntry, RpcPublicKeyPayload,
+        RpcStorageProof,
+    },
+    translator::{ProofEntry, Translator},
+    util::{block_number_to_id, jsonrpc_error, send_transaction_error},
 };
 
 // Metrics.
@@ -46,16 +51,14 @@ lazy_static! {
 pub struct OasisClient {
     logger: Logger,
     translator: Arc<Translator>,
-    km_client: Arc<dyn KeyManagerClient>,
 }
 
 impl OasisClient {
     /// Creates new OasisClient.
-    pub fn new(translator: Arc<Translator>, km_client: Arc<dyn KeyManagerClient>) -> Self {
+    pub fn new(translator: Arc<Translator>) -> Self {
         OasisClient {
             logger: get_logger("gateway/impls/oasis"),
             translator,
-            km_client,
         }
     }
 }
@@ -75,10 +78,13 @@ impl Oasis for OasisClient {
 
         let contract_id = KeyPairId::from(&keccak(contract.to_vec())[..]);
 
-        // TODO: Support proper I/O contexts (requires RPC interface changes).
+        let ctx = Context::create_child(&context::current());
         Box::new(
-            self.km_client
-                .get_public_key(Context::background(), contract_id)
+            self.translator
+                .nodes()
+                .select()
+                .km_client
+                .get_public_key(ctx, contract_id)
                 .map_err(move |err| errors::invalid_params(&contract.to_string(), err))
                 .map(move |maybe_payload| {
                     drop(timer);
@@ -106,10 +112,11 @@ impl Oasis for OasisClient {
                 "num" => ?num
         );
 
+        let ctx = context::current();
         Box::new(
             self.translator
                 .get_block_unwrap(block_number_to_id(num))
-                .and_then(move |blk| Ok(blk.state()?.storage_expiry(&address)?))
+                .and_then(move |blk| Ok(blk.state(ctx)?.storage_expiry(&address)?))
                 .map_err(jsonrpc_error),
         )
     }
@@ -128,8 +135,8 @@ impl Oasis for OasisClient {
 
         Box::new(
             self.translator
-                .send_raw_transaction(raw.into())
-                .map_err(execution_error)
+                .send_raw_transaction(context::current(), raw.into())
+                .map_err(send_transaction_error)
                 .then(move |maybe_result| {
                     drop(timer);
 
@@ -141,4 +148,69 @@ impl Oasis for OasisClient {
                 }),
         )
     }
+
+    fn get_proof(
+        &self,
+        address: RpcH160,
+        storage_keys: Vec<RpcH256>,
+        num: Trailing<BlockNumber>,
+    ) -> BoxFuture<RpcAccountProof> {
+        OASIS_RPC_CALLS.with(&labels! {"call" => "getProof",}).inc();
+        let timer = OASIS_RPC_CALL_TIME
+            .with(&labels! {"call" => "getProof",})
+            .start_timer();
+
+        let address: Address = RpcH160::into(address);
+        let storage_keys: Vec<H256> = storage_keys.into_iter().map(Into::into).collect();
+        let num = num.unwrap_or_default();
+
+        info!(
+            self.logger,
+            "oasis_getProof";
+                "address" => ?address,
+                "storage_keys" => ?storage_keys,
+                "num" => ?num
+        );
+
+        Box::new(
+            self.translator
+                .get_proof(
+                    context::current(),
+                    address,
+                    storage_keys,
+                    block_number_to_id(num),
+                )
+                .map_err(jsonrpc_error)
+                .then(move |maybe_proof| {
+                    drop(timer);
+
+                    maybe_proof.map(|proof| RpcAccountProof {
+                        address: proof.address.into(),
+                        balance: proof.balance.into(),
+                        nonce: proof.nonce.into(),
+                        code_hash: proof.code_hash.into(),
+                        state_root: proof.state_root.into(),
+                        account_proof: proof.account_proof.into_iter().map(proof_entry).collect(),
+                        storage_proof: proof
+                            .storage_proof
+                            .into_iter()
+                            .map(|(key, value, entries)| RpcStorageProof {
+                                key: key.into(),
+                                value: value.into(),
+                                proof: entries.into_iter().map(proof_entry).collect(),
+                            })
+                            .collect(),
+                    })
+                }),
+        )
+    }
+}
+
+/// Converts an MKVS proof entry into its RPC representation.
+fn proof_entry(entry: ProofEntry) -> RpcProofEntry {
+    RpcProofEntry {
+        key: entry.key.into(),
+        value: entry.value.map(Into::into),
+        proof: cbor::to_vec(&entry.proof).into(),
+    }
 }
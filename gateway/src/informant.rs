@@ -103,6 +103,7 @@ impl RpcStats {
 
     /// Count request. Returns number of requests in current second.
     pub fn count_request(&self, id: &H256) -> u16 {
+        self.requests.write().tick();
         self.sessions
             .read()
             .get(id)
@@ -115,7 +116,6 @@ impl RpcStats {
         self.sessions.read().len()
     }
 
-    #[cfg(test)]
     /// Returns requests rate
     pub fn requests_rate(&self, id: &H256) -> usize {
         self.sessions
@@ -124,6 +124,11 @@ impl RpcStats {
             .map(|calc| calc.read().rate())
             .unwrap_or(0)
     }
+
+    /// Returns requests rate of all sessions
+    pub fn rate(&self) -> usize {
+        self.requests.read().rate()
+    }
 }
 
 #[cfg(test)]
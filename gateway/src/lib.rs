@@ -16,11 +16,13 @@
 
 //! Web3 gateway.
 
-extern crate clap;
 extern crate futures;
 extern crate lazy_static;
+extern crate libc;
 #[macro_use]
 extern crate log;
+extern crate lru_cache;
+extern crate mio;
 extern crate parking_lot;
 #[macro_use]
 extern crate serde_derive;
@@ -31,20 +33,26 @@ extern crate anyhow;
 extern crate ethcore;
 extern crate ethereum_types;
 extern crate grpcio;
-#[cfg(test)]
 extern crate hex;
+extern crate hyper;
 extern crate io_context;
-extern crate jsonrpc_http_server;
+extern crate jsonrpc_ipc_server;
 extern crate jsonrpc_pubsub;
-extern crate jsonrpc_ws_server;
+extern crate jsonrpc_server_utils;
 extern crate keccak_hash as hash;
+extern crate openssl;
 extern crate parity_reactor;
 extern crate parity_rpc;
 extern crate prometheus;
+extern crate serde;
 extern crate serde_bytes;
+extern crate serde_json;
 extern crate slog;
+extern crate thiserror;
 extern crate tokio;
+extern crate tokio_openssl;
 extern crate tokio_threadpool;
+extern crate ws;
 
 extern crate oasis_core_client;
 extern crate oasis_core_keymanager_client;
@@ -53,73 +61,62 @@ extern crate oasis_core_runtime;
 extern crate oasis_ethwasi_runtime_api;
 extern crate oasis_ethwasi_runtime_common;
 
+mod auth;
+mod cache;
+mod context;
+mod health;
 mod impls;
 mod informant;
+pub mod logger;
+mod metadata;
 mod middleware;
+mod nodes;
 mod pubsub;
+mod ratelimit;
 mod rpc;
 mod rpc_apis;
 mod run;
 mod servers;
+mod tls;
 mod traits;
 mod translator;
 pub mod util;
+mod validation;
 
 use std::sync::Arc;
 
 use anyhow::Result;
-use clap::{value_t_or_exit, ArgMatches};
-use ethereum_types::U256;
 use grpcio::EnvBuilder;
-use oasis_core_client::{create_txn_api_client, Node, TxnClient};
-use oasis_core_runtime::common::namespace::Namespace;
+use oasis_core_client::{create_txn_api_client, TxnClient};
 use oasis_ethwasi_runtime_api::*;
 use serde_bytes::ByteBuf;
 
-pub use self::run::RunningGateway;
+use self::nodes::{NodeClient, Nodes};
+
+pub use self::{
+    cache::CacheConfig,
+    impls::eth_filter::FilterConfig,
+    ratelimit::{QuotaConfig, RateLimitConfig, WsSessionConfig},
+    rpc::AccessConfig,
+    rpc_apis::ApiSet,
+    run::{GatewayConfig, RunningGateway},
+    tls::TlsConfig,
+    translator::SimulatorConfig,
+};
 
 with_api! {
     create_txn_api_client!(EthereumRuntimeClient, api);
 }
 
-pub fn start(
-    args: ArgMatches,
-    pubsub_interval_secs: u64,
-    interface: &str,
-    http_port: u16,
-    num_threads: usize,
-    ws_port: u16,
-    ws_max_connections: usize,
-    ws_rate_limit: usize,
-    gas_price: U256,
-    jsonrpc_max_batch_size: usize,
-) -> Result<RunningGateway> {
-    let node_address = args.value_of("node-address").unwrap();
-    let runtime_id = value_t_or_exit!(args, "runtime-id", Namespace);
-
+pub fn start(config: GatewayConfig) -> Result<RunningGateway> {
     let env = Arc::new(EnvBuilder::new().build());
-    let node = Node::new(env.clone(), node_address);
-    let txn_client = TxnClient::new(node.channel(), runtime_id, None);
-    let client = EthereumRuntimeClient::new(txn_client);
-    // TODO: Key manager MRENCLAVE.
-    let km_client = Arc::new(oasis_core_keymanager_client::RemoteClient::new_grpc(
-        runtime_id,
-        None,
-        node.channel(),
-        1024, // TODO: How big should this cache be?
-    ));
+    let nodes = Nodes::new(
+        config
+            .node_addresses
+            .iter()
+            .map(|address| NodeClient::connect(env.clone(), address, config.runtime_id))
+            .collect(),
+    )?;
 
-    run::execute(
-        client,
-        km_client,
-        pubsub_interval_secs,
-        interface,
-        http_port,
-        num_threads,
-        ws_port,
-        ws_max_connections,
-        ws_rate_limit,
-        gas_price,
-        jsonrpc_max_batch_size,
-    )
+    run::execute(nodes, config)
 }
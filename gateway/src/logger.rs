@@ -0,0 +1,116 @@
+//! Gateway loggers, whose level can be changed at runtime.
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use log;
+use oasis_core_runtime::common::logger;
+use slog::{o, Drain, Level, Logger, OwnedKVList, Record};
+
+/// Max level of the gateway loggers, as the value of a `slog::Level`, or 0
+/// if logging is off. Records are not filtered until a level is set.
+static MAX_LEVEL: AtomicUsize = AtomicUsize::new(6);
+
+/// Drain discarding records above a level that can be changed at runtime.
+struct RuntimeLevelFilter<D> {
+    drain: D,
+    max_level: &'static AtomicUsize,
+}
+
+impl<D: Drain> Drain for RuntimeLevelFilter<D> {
+    type Ok = Option<D::Ok>;
+    type Err = D::Err;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        if record.level().as_usize() > self.max_level.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+
+        self.drain.log(record, values).map(Some)
+    }
+}
+
+/// Get a logger for the module, filtered by the runtime log level.
+pub fn get_logger(module: &'static str) -> Logger {
+    Logger::root(
+        RuntimeLevelFilter {
+            drain: logger::get_logger(module),
+            max_level: &MAX_LEVEL,
+        },
+        o!(),
+    )
+}
+
+/// Set the max level of the gateway loggers and of the `log` crate.
+pub fn set_max_level(level: log::LevelFilter) {
+    MAX_LEVEL.store(slog_level(level), Ordering::Relaxed);
+    log::set_max_level(level);
+}
+
+/// Returns the `slog::Level` value of a `log` level filter, or 0 if it is off.
+fn slog_level(level: log::LevelFilter) -> usize {
+    match level.to_level() {
+        Some(log::Level::Error) => Level::Error.as_usize(),
+        Some(log::Level::Warn) => Level::Warning.as_usize(),
+        Some(log::Level::Info) => Level::Info.as_usize(),
+        Some(log::Level::Debug) => Level::Debug.as_usize(),
+        Some(log::Level::Trace) => Level::Trace.as_usize(),
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use slog::{debug, error, info, Never};
+
+    /// Drain counting the records it receives.
+    struct CountingDrain(Arc<AtomicUsize>);
+
+    impl Drain for CountingDrain {
+        type Ok = ();
+        type Err = Never;
+
+        fn log(&self, _record: &Record, _values: &OwnedKVList) -> Result<(), Never> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_filter_by_runtime_level() {
+        static TEST_MAX_LEVEL: AtomicUsize = AtomicUsize::new(6);
+        let count = Arc::new(AtomicUsize::new(0));
+        let logger = Logger::root(
+            RuntimeLevelFilter {
+                drain: CountingDrain(count.clone()),
+                max_level: &TEST_MAX_LEVEL,
+            },
+            o!(),
+        );
+        let log_all = || {
+            error!(logger, "error");
+            info!(logger, "info");
+            debug!(logger, "debug");
+        };
+
+        log_all();
+        assert_eq!(count.swap(0, Ordering::SeqCst), 3);
+
+        TEST_MAX_LEVEL.store(slog_level(log::LevelFilter::Info), Ordering::SeqCst);
+        log_all();
+        assert_eq!(count.swap(0, Ordering::SeqCst), 2);
+
+        TEST_MAX_LEVEL.store(slog_level(log::LevelFilter::Error), Ordering::SeqCst);
+        log_all();
+        assert_eq!(count.swap(0, Ordering::SeqCst), 1);
+
+        TEST_MAX_LEVEL.store(slog_level(log::LevelFilter::Off), Ordering::SeqCst);
+        log_all();
+        assert_eq!(count.swap(0, Ordering::SeqCst), 0);
+    }
+}
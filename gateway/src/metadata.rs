@@ -0,0 +1,48 @@
+//! RPC metadata.
+use std::sync::Arc;
+
+use jsonrpc_core;
+use jsonrpc_pubsub::{PubSubMetadata, Session};
+use parity_rpc::{self, Origin};
+
+use crate::ratelimit::ClientInfo;
+
+/// Metadata of an RPC request.
+#[derive(Clone, Default)]
+pub struct Metadata {
+    /// Request origin.
+    pub origin: Origin,
+    /// Client of the request, if known.
+    pub client: Option<ClientInfo>,
+    /// Pub/sub session of the request.
+    pub session: Option<Arc<Session>>,
+}
+
+impl Metadata {
+    /// Returns true if the request originates from a dapp.
+    pub fn is_dapp(&self) -> bool {
+        parity_rpc::Metadata {
+            origin: self.origin.clone(),
+            session: None,
+        }
+        .is_dapp()
+    }
+}
+
+impl jsonrpc_core::Metadata for Metadata {}
+
+impl PubSubMetadata for Metadata {
+    fn session(&self) -> Option<Arc<Session>> {
+        self.session.clone()
+    }
+}
+
+impl From<parity_rpc::Metadata> for Metadata {
+    fn from(metadata: parity_rpc::Metadata) -> Self {
+        Metadata {
+            origin: metadata.origin,
+            client: None,
+            session: metadata.session,
+        }
+    }
+}
@@ -1,19 +1,45 @@
 //! RPC Middleware
 
+use auth::{self, ApiKeys};
+use context;
+use hyper::{self, header::CONTENT_TYPE, StatusCode};
 use informant::RpcStats;
 use jsonrpc_core as rpc;
-use jsonrpc_core::futures::{future::Either, Future};
-use jsonrpc_ws_server as ws;
+use jsonrpc_core::futures::{
+    future::{self, Either},
+    Future,
+};
+use jsonrpc_ipc_server as ipc;
 use lazy_static::lazy_static;
-use parity_rpc::{informant::ActivityNotifier, v1::types::H256, Metadata, Origin};
+use metadata::Metadata;
+use parity_rpc::{self, informant::ActivityNotifier, v1::types::H256, Origin, RpcExtractor};
 use prometheus::{
-    register_histogram, register_int_counter, register_int_gauge, Histogram, IntCounter, IntGauge,
+    register_histogram, register_int_counter, register_int_counter_vec, register_int_gauge,
+    Histogram, IntCounter, IntCounterVec, IntGauge,
 };
-use std::{sync::Arc, vec::Vec};
+use ratelimit::{Client, ClientInfo, Quotas, RateLimiter, WsClients};
+use rpc_apis::Api;
+use std::{net::IpAddr, slice, str, sync::Arc, time::Duration, vec::Vec};
+use tokio::timer::Timeout;
+use ws;
+
+/// Methods submitting transactions, which are exempt from the request
+/// timeout. A transaction may still be executed after its request timed out,
+/// so clients must get the result of the submission instead of a timeout
+/// error that would make them submit it again.
+const SUBMISSION_METHODS: &[&str] = &[
+    "eth_sendRawTransaction",
+    "eth_submitTransaction",
+    "oasis_invoke",
+];
 
 /// Custom JSON-RPC error codes
 const ERROR_BATCH_SIZE: i64 = -32099;
 const ERROR_RATE_LIMITED: i64 = -32098;
+const ERROR_QUOTA_EXCEEDED: i64 = -32097;
+const ERROR_UNAUTHORIZED: i64 = -32096;
+const ERROR_API_FORBIDDEN: i64 = -32095;
+const ERROR_TIMEOUT: i64 = -32094;
 
 // Metrics.
 lazy_static! {
@@ -22,6 +48,32 @@ lazy_static! {
         "Number of WebSocket rate limiter activations"
     )
     .unwrap();
+    static ref WS_CONNECTIONS_REJECTED: IntCounter = register_int_counter!(
+        "web3_gateway_ws_connections_rejected",
+        "Number of WebSocket connections rejected by the per-client connection limit"
+    )
+    .unwrap();
+    static ref HTTP_RATE_LIMITED: IntCounter = register_int_counter!(
+        "web3_gateway_http_rate_limited",
+        "Number of HTTP rate limiter activations"
+    )
+    .unwrap();
+    static ref JSONRPC_QUOTA_EXCEEDED: IntCounterVec = register_int_counter_vec!(
+        "web3_gateway_jsonrpc_quota_exceeded",
+        "Number of JSON-RPC calls rejected by per-client quotas",
+        &["method"]
+    )
+    .unwrap();
+    static ref UNAUTHORIZED: IntCounter = register_int_counter!(
+        "web3_gateway_unauthorized",
+        "Number of requests and connections rejected for an invalid or missing API key"
+    )
+    .unwrap();
+    static ref JSONRPC_TIMEOUTS: IntCounter = register_int_counter!(
+        "web3_gateway_jsonrpc_timeouts",
+        "Number of JSON-RPC requests that exceeded the request timeout"
+    )
+    .unwrap();
     static ref JSONRPC_BATCH_SIZE: Histogram =
         register_histogram!("web3_gateway_jsonrpc_batch_size", "JSON-RPC batch sizes").unwrap();
     static ref WS_SESSIONS: IntGauge =
@@ -37,7 +89,7 @@ fn error_batch_size() -> rpc::Error {
     }
 }
 
-/// A custom JSON-RPC error for WebSocket rate limit.
+/// A custom JSON-RPC error for rate limits.
 fn error_rate_limited() -> rpc::Error {
     rpc::Error {
         code: rpc::ErrorCode::ServerError(ERROR_RATE_LIMITED),
@@ -46,6 +98,42 @@ fn error_rate_limited() -> rpc::Error {
     }
 }
 
+/// A custom JSON-RPC error for requests exceeding the client's quota.
+fn error_quota_exceeded() -> rpc::Error {
+    rpc::Error {
+        code: rpc::ErrorCode::ServerError(ERROR_QUOTA_EXCEEDED),
+        message: "Request quota exceeded".into(),
+        data: None,
+    }
+}
+
+/// A custom JSON-RPC error for requests without a valid API key.
+fn error_unauthorized() -> rpc::Error {
+    rpc::Error {
+        code: rpc::ErrorCode::ServerError(ERROR_UNAUTHORIZED),
+        message: "Invalid or missing API key".into(),
+        data: None,
+    }
+}
+
+/// A custom JSON-RPC error for calls to APIs not allowed for the API key.
+fn error_api_forbidden() -> rpc::Error {
+    rpc::Error {
+        code: rpc::ErrorCode::ServerError(ERROR_API_FORBIDDEN),
+        message: "Method not allowed for API key".into(),
+        data: None,
+    }
+}
+
+/// A custom JSON-RPC error for requests exceeding the request timeout.
+fn error_timeout() -> rpc::Error {
+    rpc::Error {
+        code: rpc::ErrorCode::ServerError(ERROR_TIMEOUT),
+        message: "Request timed out".into(),
+        data: None,
+    }
+}
+
 trait ErrGen {
     fn generate(&self) -> rpc::Error;
 }
@@ -66,6 +154,38 @@ impl ErrGen for RateLimitedErrGen {
     }
 }
 
+struct QuotaExceededErrGen {}
+
+impl ErrGen for QuotaExceededErrGen {
+    fn generate(&self) -> rpc::Error {
+        error_quota_exceeded()
+    }
+}
+
+struct UnauthorizedErrGen {}
+
+impl ErrGen for UnauthorizedErrGen {
+    fn generate(&self) -> rpc::Error {
+        error_unauthorized()
+    }
+}
+
+struct ApiForbiddenErrGen {}
+
+impl ErrGen for ApiForbiddenErrGen {
+    fn generate(&self) -> rpc::Error {
+        error_api_forbidden()
+    }
+}
+
+struct TimeoutErrGen {}
+
+impl ErrGen for TimeoutErrGen {
+    fn generate(&self) -> rpc::Error {
+        error_timeout()
+    }
+}
+
 /// Given a single call it generates an error response
 /// from the error generator.
 fn generate_error_response_call(call: &rpc::Call, gen: &dyn ErrGen) -> rpc::Output {
@@ -101,32 +221,71 @@ fn generate_error_response(request: rpc::Request, gen: &dyn ErrGen) -> rpc::Futu
     })))
 }
 
-/// RPC middleware that enforces batch size limits.
+/// Returns the method of a call, if it is valid.
+fn call_method(call: &rpc::Call) -> Option<&str> {
+    match call {
+        rpc::Call::MethodCall(method) => Some(&method.method),
+        rpc::Call::Notification(notification) => Some(&notification.method),
+        rpc::Call::Invalid { .. } => None,
+    }
+}
+
+/// RPC middleware that enforces batch size limits, API key permissions and
+/// per-client quotas.
 pub struct Middleware<T: ActivityNotifier> {
     notifier: T,
     max_batch_size: usize,
+    request_timeout: Duration,
+    quotas: Option<Arc<Quotas>>,
+    api_keys: Option<Arc<ApiKeys>>,
+    ws_clients: Option<Arc<WsClients>>,
 }
 
 impl<T: ActivityNotifier> Middleware<T> {
-    pub fn new(notifier: T, max_batch_size: usize) -> Self {
+    /// Creates new middleware. Requests must be authenticated with one of
+    /// `api_keys`, if given. Sessions of `ws_clients` are identified by the
+    /// client of their handshake. Requests not completed within
+    /// `request_timeout` are answered with an error, unless they submit a
+    /// transaction.
+    pub fn new(
+        notifier: T,
+        max_batch_size: usize,
+        request_timeout: Duration,
+        quotas: Option<Arc<Quotas>>,
+        api_keys: Option<Arc<ApiKeys>>,
+        ws_clients: Option<Arc<WsClients>>,
+    ) -> Self {
         Middleware {
             notifier,
             max_batch_size,
+            request_timeout,
+            quotas,
+            api_keys,
+            ws_clients,
+        }
+    }
+
+    /// Returns the client to charge and limit for a request.
+    fn client(&self, meta: &Metadata) -> Option<Client> {
+        match (meta.client.as_ref().and_then(|info| info.ip), &meta.origin) {
+            (Some(ip), _) => Some(Client::Ip(ip)),
+            (None, Origin::Ws { ref session, .. }) => Some(Client::Session(session.clone())),
+            _ => None,
         }
     }
 }
 
-impl<M: rpc::Metadata, T: ActivityNotifier> rpc::Middleware<M> for Middleware<T> {
+impl<T: ActivityNotifier> rpc::Middleware<Metadata> for Middleware<T> {
     type Future = rpc::FutureResponse;
 
     fn on_request<F, X>(
         &self,
         request: rpc::Request,
-        meta: M,
+        meta: Metadata,
         process: F,
     ) -> Either<Self::Future, X>
     where
-        F: FnOnce(rpc::Request, M) -> X + Send,
+        F: FnOnce(rpc::Request, Metadata) -> X + Send,
         X: rpc::futures::Future<Item = Option<rpc::Response>, Error = ()> + Send + 'static,
     {
         self.notifier.active();
@@ -145,21 +304,158 @@ impl<M: rpc::Metadata, T: ActivityNotifier> rpc::Middleware<M> for Middleware<T>
             }
         }
 
-        Either::B(process(request, meta))
+        let calls = match request {
+            rpc::Request::Single(ref call) => slice::from_ref(call),
+            rpc::Request::Batch(ref calls) => calls.as_slice(),
+        };
+        let info = meta.client.as_ref();
+
+        // The admin API is only served on local interfaces, so reject admin
+        // calls of remote clients forwarded by a local reverse proxy. Clients
+        // with an unknown address are remote, unless they are connected over
+        // IPC.
+        let remote = match (info.and_then(|info| info.ip), &meta.origin) {
+            (Some(ip), _) => !ip.is_loopback(),
+            (None, Origin::Ipc(_)) => false,
+            (None, _) => true,
+        };
+        if remote
+            && calls
+                .iter()
+                .filter_map(call_method)
+                .any(|method| Api::from_method(method) == Some(Api::Admin))
+        {
+            error!("Rejecting admin JSON-RPC request from a remote client");
+            return Either::A(generate_error_response(request, &ApiForbiddenErrGen {}));
+        }
+
+        // Check that the request is authenticated with a key that is still
+        // valid, as keys may have been removed since the client connected, and
+        // that the key may make all calls in the request.
+        if let Some(ref api_keys) = self.api_keys {
+            let api_key = info
+                .and_then(|info| info.key.as_ref())
+                .and_then(|name| api_keys.get(name));
+            let api_key = match api_key {
+                Some(api_key) => api_key,
+                None => {
+                    UNAUTHORIZED.inc();
+                    return Either::A(generate_error_response(request, &UnauthorizedErrGen {}));
+                }
+            };
+
+            // Methods outside of the known APIs are never allowed.
+            let allowed = calls
+                .iter()
+                .filter_map(call_method)
+                .all(|method| Api::from_method(method).map_or(false, |api| api_key.allows(&api)));
+            if !allowed {
+                error!(
+                    "Rejecting JSON-RPC request not allowed for API key {}",
+                    api_key.name
+                );
+                return Either::A(generate_error_response(request, &ApiForbiddenErrGen {}));
+            }
+
+            if !api_key.check_rate(calls.len()) {
+                error!(
+                    "Rejecting JSON-RPC request over rate for API key {}",
+                    api_key.name
+                );
+                return Either::A(generate_error_response(request, &RateLimitedErrGen {}));
+            }
+        }
+
+        // Charge the client for all calls in the request, and reject the
+        // whole request if it exceeds the client's budget.
+        let client = self.client(&meta);
+        if let (Some(quotas), Some(client)) = (self.quotas.as_ref(), client.clone()) {
+            let cost = calls
+                .iter()
+                .filter_map(call_method)
+                .map(|method| quotas.cost(method))
+                .sum();
+
+            if !quotas.charge(client, cost) {
+                // Only label methods in the cost table, as clients may call
+                // arbitrary methods.
+                for method in calls.iter().filter_map(call_method) {
+                    let label = if quotas.is_priced(method) {
+                        method
+                    } else {
+                        "other"
+                    };
+                    JSONRPC_QUOTA_EXCEEDED.with_label_values(&[label]).inc();
+                }
+                error!("Rejecting JSON-RPC request over quota: cost {}", cost);
+                return Either::A(generate_error_response(request, &QuotaExceededErrGen {}));
+            }
+        }
+
+        // Requests submitting a transaction are not subject to the request
+        // timeout, so that their response is the result of the submission.
+        let timeout = if calls
+            .iter()
+            .filter_map(call_method)
+            .any(|method| SUBMISSION_METHODS.contains(&method))
+        {
+            None
+        } else {
+            Some(self.request_timeout)
+        };
+
+        // Dispatch the request with a context that expires with the request
+        // timeout, and is cancelled once the response is done or abandoned,
+        // or when the WebSocket session closes.
+        let (ctx, cancel) = context::new_request(timeout);
+        if let (Origin::Ws { ref session, .. }, Some(clients)) =
+            (&meta.origin, self.ws_clients.as_ref())
+        {
+            clients.track_request(session, &cancel);
+        }
+        let timed_out = request.clone();
+        let response = context::enter(ctx, client, || process(request, meta));
+
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => {
+                return Either::A(Box::new(response.then(move |result| {
+                    drop(cancel);
+                    result
+                })));
+            }
+        };
+
+        Either::A(Box::new(Timeout::new(response, timeout).then(
+            move |result| -> rpc::FutureResponse {
+                drop(cancel);
+                match result {
+                    Ok(response) => Box::new(future::ok(response)),
+                    Err(ref err) if err.is_elapsed() => {
+                        JSONRPC_TIMEOUTS.inc();
+                        error!("JSON-RPC request timed out");
+                        generate_error_response(timed_out, &TimeoutErrGen {})
+                    }
+                    Err(_) => Box::new(future::err(())),
+                }
+            },
+        )))
     }
 }
 
 /// WebSockets middleware that dispatches requests to handle.
 pub struct WsDispatcher {
     stats: Arc<RpcStats>,
+    clients: Arc<WsClients>,
     max_req_per_sec: usize,
 }
 
 impl WsDispatcher {
     /// Create new `WsDispatcher` with given full handler.
-    pub fn new(stats: Arc<RpcStats>, max_req_per_sec: usize) -> Self {
+    pub fn new(stats: Arc<RpcStats>, clients: Arc<WsClients>, max_req_per_sec: usize) -> Self {
         WsDispatcher {
             stats,
+            clients,
             max_req_per_sec,
         }
     }
@@ -178,9 +474,12 @@ impl rpc::Middleware<Metadata> for WsDispatcher {
         F: FnOnce(rpc::Request, Metadata) -> X + Send,
         X: Future<Item = Option<rpc::Response>, Error = ()> + Send + 'static,
     {
-        // Check request rate for session, and respond with an error if it exceeds max_req_per_sec.
+        // Check request rate for session and for the session's client, and
+        // respond with an error if either exceeds its limit.
         if let Origin::Ws { ref session, .. } = meta.origin {
-            if self.stats.count_request(session) as usize > self.max_req_per_sec {
+            if self.stats.count_request(session) as usize > self.max_req_per_sec
+                || !self.clients.check_request(session)
+            {
                 WS_RATE_LIMITED.inc();
                 error!("Rejecting WS request");
                 return Either::A(generate_error_response(request, &RateLimitedErrGen {}));
@@ -191,30 +490,161 @@ impl rpc::Middleware<Metadata> for WsDispatcher {
     }
 }
 
+/// IPC metadata extractor.
+pub struct IpcExtractor;
+
+impl ipc::MetaExtractor<Metadata> for IpcExtractor {
+    fn extract(&self, req: &ipc::RequestContext) -> Metadata {
+        ipc::MetaExtractor::<parity_rpc::Metadata>::extract(&RpcExtractor, req).into()
+    }
+}
+
+/// Returns a WebSocket handshake error response.
+fn ws_error_response(status: u16, reason: &str, message: &str) -> ws::Response {
+    let mut response = ws::Response::new(status, reason, format!("{}\n", message).into_bytes());
+    response
+        .headers_mut()
+        .push(("Connection".to_owned(), b"close".to_vec()));
+
+    response
+}
+
+/// Authenticate a WebSocket handshake, and return the name of its API key,
+/// or an error response if it does not have a valid one.
+pub fn ws_authenticate(api_keys: &ApiKeys, req: &ws::Request) -> Result<String, ws::Response> {
+    let api_key = auth::ws_request_key(
+        req.header(auth::API_KEY_HEADER)
+            .and_then(|value| str::from_utf8(value).ok()),
+        req.resource(),
+    )
+    .and_then(|key| api_keys.authenticate(key));
+
+    match api_key {
+        Some(api_key) => Ok(api_key.name.clone()),
+        None => {
+            UNAUTHORIZED.inc();
+            Err(ws_error_response(
+                401,
+                "Unauthorized",
+                "Invalid or missing API key",
+            ))
+        }
+    }
+}
+
+/// Returns true if the client may open another WebSocket connection.
+pub fn ws_admit(clients: &WsClients, ip: IpAddr) -> bool {
+    if clients.can_connect(ip) {
+        return true;
+    }
+
+    WS_CONNECTIONS_REJECTED.inc();
+    error!("Rejecting WS connection from {}", ip);
+    false
+}
+
+/// Returns the API key of an HTTP request.
+fn http_request_key(request: &hyper::Request<hyper::Body>) -> Option<&str> {
+    auth::http_request_key(
+        request
+            .headers()
+            .get(auth::API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok()),
+        request.uri().path(),
+    )
+}
+
+/// Returns an HTTP response with a JSON-RPC error.
+fn http_error_response(status: StatusCode, error: rpc::Error) -> hyper::Response<hyper::Body> {
+    // The request body has not been parsed yet, so respond with a single
+    // error without an id.
+    let output = rpc::Output::from(Err(error), rpc::Id::Null, Some(rpc::Version::V2));
+
+    hyper::Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(hyper::Body::from(
+            serde_json::to_string(&output).expect("output serialization cannot fail"),
+        ))
+        .expect("response must be valid")
+}
+
+/// Authenticate an HTTP request, and return the name of its API key, or an
+/// error response if it does not have a valid one.
+pub fn http_authenticate(
+    api_keys: &ApiKeys,
+    request: &hyper::Request<hyper::Body>,
+) -> Result<String, hyper::Response<hyper::Body>> {
+    match http_request_key(request).and_then(|key| api_keys.authenticate(key)) {
+        Some(api_key) => Ok(api_key.name.clone()),
+        None => {
+            UNAUTHORIZED.inc();
+            Err(http_error_response(
+                StatusCode::UNAUTHORIZED,
+                error_unauthorized(),
+            ))
+        }
+    }
+}
+
 /// WebSockets RPC usage statistics.
 pub struct WsStats {
     stats: Arc<RpcStats>,
+    clients: Arc<WsClients>,
 }
 
 impl WsStats {
     /// Creates new WS usage tracker.
-    pub fn new(stats: Arc<RpcStats>) -> Self {
-        WsStats { stats }
+    pub fn new(stats: Arc<RpcStats>, clients: Arc<WsClients>) -> Self {
+        WsStats { stats, clients }
     }
-}
 
-impl ws::SessionStats for WsStats {
-    fn open_session(&self, id: ws::SessionId) {
-        self.stats.open_session(H256::from(id));
+    /// Start tracking a session of the client. The session can be closed
+    /// through its `sender`.
+    pub fn open_session(&self, id: H256, client: ClientInfo, sender: ws::Sender) {
+        self.stats.open_session(id.clone());
+        self.clients.open_session(id, client, Some(sender));
         WS_SESSIONS.set(self.stats.sessions() as i64);
     }
 
-    fn close_session(&self, id: ws::SessionId) {
-        self.stats.close_session(&H256::from(id));
+    /// Stop tracking a session.
+    pub fn close_session(&self, id: &H256) {
+        self.stats.close_session(id);
+        self.clients.close_session(id);
         WS_SESSIONS.set(self.stats.sessions() as i64);
     }
 }
 
+/// HTTP request rate limiter keyed by client address.
+pub struct HttpRateLimiter {
+    limiter: RateLimiter,
+}
+
+impl HttpRateLimiter {
+    /// Creates new HTTP rate limiter.
+    pub fn new(max_req_per_sec: usize) -> Self {
+        HttpRateLimiter {
+            limiter: RateLimiter::new(max_req_per_sec),
+        }
+    }
+
+    /// Count a request of the client, and return an error response if it
+    /// exceeded its rate.
+    pub fn check(&self, ip: IpAddr) -> Option<hyper::Response<hyper::Body>> {
+        if self.limiter.check(ip) {
+            return None;
+        }
+
+        HTTP_RATE_LIMITED.inc();
+        error!("Rejecting HTTP request from {}", ip);
+
+        Some(http_error_response(
+            StatusCode::TOO_MANY_REQUESTS,
+            error_rate_limited(),
+        ))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -222,6 +652,9 @@ mod tests {
     use futures::Future;
     use informant::RpcStats;
     use jsonrpc_core::Middleware as mw;
+    use ratelimit::{self, QuotaConfig, RateLimitConfig};
+    use std::time::Instant;
+    use tokio::timer::Delay;
 
     pub struct TestNotifier {}
 
@@ -229,6 +662,8 @@ mod tests {
         fn active(&self) {}
     }
 
+    const TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
     fn validate_error_output(output: rpc::Output, id: jsonrpc_core::Id) {
         match output {
             rpc::Output::Failure(failure) => {
@@ -263,11 +698,13 @@ mod tests {
                 dapp: "".into(),
                 session: session_id.clone(),
             },
+            client: None,
             session: None,
         };
 
         // limit: 1 request/sec
-        let dispatcher = WsDispatcher::new(stats.clone(), 1);
+        let clients = Arc::new(WsClients::new(&Default::default(), Default::default()));
+        let dispatcher = WsDispatcher::new(stats.clone(), clients, 1);
 
         // a single request (should pass)
         let request_1 = make_request(1);
@@ -295,11 +732,13 @@ mod tests {
                 dapp: "".into(),
                 session: session_id.clone(),
             },
+            client: None,
             session: None,
         };
 
         // limit: 1 request/sec
-        let dispatcher = WsDispatcher::new(stats.clone(), 1);
+        let clients = Arc::new(WsClients::new(&Default::default(), Default::default()));
+        let dispatcher = WsDispatcher::new(stats.clone(), clients, 1);
 
         // two requests
         let request_1 = make_request(1);
@@ -332,13 +771,379 @@ mod tests {
         };
     }
 
+    #[test]
+    fn should_limit_request_rate_per_client() {
+        let stats = Arc::new(RpcStats::default());
+        let clients = Arc::new(WsClients::new(
+            &RateLimitConfig {
+                ws_max_req_per_sec: 1,
+                trusted_proxies: 1,
+                ..Default::default()
+            },
+            Default::default(),
+        ));
+        let ip = clients.client_ip("127.0.0.1".parse().unwrap(), Some("10.0.0.1"));
+
+        // limit: 10 requests/sec per session, 1 request/sec per client
+        let dispatcher = WsDispatcher::new(stats.clone(), clients.clone(), 10);
+
+        // two sessions of the same client, one request on each
+        let responses: Vec<_> = (1..3)
+            .map(|id| {
+                let session_id = H256::from(id);
+                stats.open_session(session_id.clone());
+                clients.open_session(
+                    session_id.clone(),
+                    ClientInfo {
+                        ip: Some(ip),
+                        key: None,
+                    },
+                    None,
+                );
+                let metadata = Metadata {
+                    origin: Origin::Ws {
+                        dapp: "".into(),
+                        session: session_id,
+                    },
+                    client: Some(ClientInfo {
+                        ip: Some(ip),
+                        key: None,
+                    }),
+                    session: None,
+                };
+
+                dispatcher
+                    .on_request(make_request(id), metadata, |_request, _meta| {
+                        Box::new(rpc::futures::finished(None))
+                    })
+                    .wait()
+                    .unwrap()
+            })
+            .collect();
+
+        // the second request should respond with a Failure
+        assert_eq!(responses[0], None);
+        match responses[1] {
+            Some(rpc::Response::Single(rpc::Output::Failure(ref failure))) => {
+                assert_eq!(
+                    failure.error.code,
+                    rpc::ErrorCode::ServerError(ERROR_RATE_LIMITED)
+                );
+            }
+            _ => assert!(false, "Did not enforce per-client rate limit"),
+        };
+    }
+
+    #[test]
+    fn should_limit_quota() {
+        let mut config = QuotaConfig {
+            budget: 10,
+            ..Default::default()
+        };
+        config.method_costs.insert("test".to_owned(), 4);
+        let quotas = Arc::new(Quotas::new(config));
+        let middleware =
+            Middleware::new(TestNotifier {}, 10, TEST_TIMEOUT, Some(quotas), None, None);
+        let metadata = Metadata {
+            client: Some(ClientInfo {
+                ip: Some("10.0.0.1".parse().unwrap()),
+                key: None,
+            }),
+            ..Default::default()
+        };
+
+        // batch of 2 calls costing 8 (should pass)
+        let batch = rpc::Request::Batch(vec![
+            rpc::Call::MethodCall(rpc::MethodCall {
+                jsonrpc: Some(rpc::Version::V2),
+                method: "test".to_owned(),
+                params: rpc::Params::Array(vec![]),
+                id: rpc::Id::Num(1),
+            }),
+            rpc::Call::MethodCall(rpc::MethodCall {
+                jsonrpc: Some(rpc::Version::V2),
+                method: "test".to_owned(),
+                params: rpc::Params::Array(vec![]),
+                id: rpc::Id::Num(2),
+            }),
+        ]);
+        let response_1 = middleware
+            .on_request(batch, metadata.clone(), |_request, _meta| {
+                Box::new(rpc::futures::finished(None))
+            })
+            .wait()
+            .unwrap();
+        assert_eq!(response_1, None);
+
+        // single call costing 4 (should fail)
+        let response_2 = middleware
+            .on_request(make_request(3), metadata.clone(), |_request, _meta| {
+                Box::new(rpc::futures::finished(None))
+            })
+            .wait()
+            .unwrap();
+        match response_2 {
+            Some(rpc::Response::Single(rpc::Output::Failure(failure))) => {
+                assert_eq!(
+                    failure.error.code,
+                    rpc::ErrorCode::ServerError(ERROR_QUOTA_EXCEEDED)
+                );
+                assert_eq!(failure.id, jsonrpc_core::Id::Num(3));
+            }
+            _ => assert!(false, "Did not enforce quota"),
+        };
+
+        // requests without a known client are not charged
+        let response_3 = middleware
+            .on_request(make_request(4), Metadata::default(), |_request, _meta| {
+                Box::new(rpc::futures::finished(None))
+            })
+            .wait()
+            .unwrap();
+        assert_eq!(response_3, None);
+    }
+
+    #[test]
+    fn should_charge_http_clients_by_peer() {
+        let mut config = QuotaConfig {
+            budget: 4,
+            ..Default::default()
+        };
+        config.method_costs.insert("test".to_owned(), 4);
+        let quotas = Arc::new(Quotas::new(config));
+        let middleware =
+            Middleware::new(TestNotifier {}, 10, TEST_TIMEOUT, Some(quotas), None, None);
+        // Without trusted proxies, the HTTP server identifies clients by their
+        // peer address, whatever X-Forwarded-For they send.
+        let metadata = |peer: &str, forwarded_for: &str| Metadata {
+            client: Some(ClientInfo {
+                ip: Some(ratelimit::client_ip(
+                    peer.parse().unwrap(),
+                    Some(forwarded_for),
+                    0,
+                )),
+                key: None,
+            }),
+            ..Default::default()
+        };
+        let is_over_quota = |metadata: Metadata| {
+            let response = middleware
+                .on_request(make_request(1), metadata, |_request, _meta| {
+                    Box::new(rpc::futures::finished(None))
+                })
+                .wait()
+                .unwrap();
+            match response {
+                Some(rpc::Response::Single(rpc::Output::Failure(failure))) => {
+                    failure.error.code == rpc::ErrorCode::ServerError(ERROR_QUOTA_EXCEEDED)
+                }
+                _ => false,
+            }
+        };
+
+        assert!(!is_over_quota(metadata("10.0.0.1", "192.168.1.1")));
+        assert!(is_over_quota(metadata("10.0.0.1", "192.168.1.2")));
+        assert!(!is_over_quota(metadata("10.0.0.2", "192.168.1.1")));
+    }
+
+    #[test]
+    fn should_enforce_api_keys() {
+        let api_keys = Arc::new(
+            ApiKeys::from_json(r#"[{"name": "net", "key": "secret", "apis": ["net"]}]"#).unwrap(),
+        );
+        let middleware = Middleware::new(
+            TestNotifier {},
+            10,
+            TEST_TIMEOUT,
+            None,
+            Some(api_keys),
+            None,
+        );
+        let on_request = |method: &str, key: Option<&str>| {
+            let request = rpc::Request::Single(rpc::Call::MethodCall(rpc::MethodCall {
+                jsonrpc: Some(rpc::Version::V2),
+                method: method.to_owned(),
+                params: rpc::Params::Array(vec![]),
+                id: rpc::Id::Num(1),
+            }));
+            let metadata = Metadata {
+                client: Some(ClientInfo {
+                    ip: None,
+                    key: key.map(Into::into),
+                }),
+                ..Default::default()
+            };
+
+            match middleware
+                .on_request(request, metadata, |_request, _meta| {
+                    Box::new(rpc::futures::finished(None))
+                })
+                .wait()
+                .unwrap()
+            {
+                Some(rpc::Response::Single(rpc::Output::Failure(failure))) => {
+                    Some(failure.error.code)
+                }
+                _ => None,
+            }
+        };
+
+        assert_eq!(on_request("net_version", Some("net")), None);
+        assert_eq!(
+            on_request("eth_call", Some("net")),
+            Some(rpc::ErrorCode::ServerError(ERROR_API_FORBIDDEN))
+        );
+        assert_eq!(
+            on_request("net_version", Some("removed")),
+            Some(rpc::ErrorCode::ServerError(ERROR_UNAUTHORIZED))
+        );
+        assert_eq!(
+            on_request("net_version", None),
+            Some(rpc::ErrorCode::ServerError(ERROR_UNAUTHORIZED))
+        );
+    }
+
+    #[test]
+    fn should_forbid_unknown_methods_for_api_keys() {
+        let api_keys = Arc::new(
+            ApiKeys::from_json(
+                r#"[
+                    {"name": "net", "key": "secret", "apis": ["net"]},
+                    {"name": "all", "key": "other secret"}
+                ]"#,
+            )
+            .unwrap(),
+        );
+        let middleware = Middleware::new(
+            TestNotifier {},
+            10,
+            TEST_TIMEOUT,
+            None,
+            Some(api_keys),
+            None,
+        );
+        let on_request = |request: rpc::Request, key: &str| {
+            let metadata = Metadata {
+                client: Some(ClientInfo {
+                    ip: None,
+                    key: Some(key.into()),
+                }),
+                ..Default::default()
+            };
+
+            middleware
+                .on_request(request, metadata, |_request, _meta| {
+                    Box::new(rpc::futures::finished(None))
+                })
+                .wait()
+                .unwrap()
+        };
+        let call = |method: &str, id: u64| {
+            rpc::Call::MethodCall(rpc::MethodCall {
+                jsonrpc: Some(rpc::Version::V2),
+                method: method.to_owned(),
+                params: rpc::Params::Array(vec![]),
+                id: rpc::Id::Num(id),
+            })
+        };
+
+        // methods outside of the known APIs are not allowed, whether the key
+        // is restricted or not
+        for key in &["net", "all"] {
+            match on_request(rpc::Request::Single(call("parity_chain", 1)), key) {
+                Some(rpc::Response::Single(rpc::Output::Failure(failure))) => {
+                    assert_eq!(
+                        failure.error.code,
+                        rpc::ErrorCode::ServerError(ERROR_API_FORBIDDEN)
+                    );
+                }
+                _ => assert!(false, "Did not forbid unknown method"),
+            }
+        }
+
+        // nor can they be hidden in a batch of allowed calls
+        match on_request(
+            rpc::Request::Batch(vec![call("net_version", 1), call("parity_chain", 2)]),
+            "net",
+        ) {
+            Some(rpc::Response::Batch(outputs)) => {
+                assert_eq!(outputs.len(), 2);
+                for output in outputs {
+                    match output {
+                        rpc::Output::Failure(failure) => assert_eq!(
+                            failure.error.code,
+                            rpc::ErrorCode::ServerError(ERROR_API_FORBIDDEN)
+                        ),
+                        _ => assert!(false, "Did not forbid batch with unknown method"),
+                    }
+                }
+            }
+            _ => assert!(false, "Did not forbid batch with unknown method"),
+        }
+    }
+
+    #[test]
+    fn should_restrict_admin_to_local_clients() {
+        let middleware = Middleware::new(TestNotifier {}, 10, TEST_TIMEOUT, None, None, None);
+        let on_request = |method: &str, origin: Origin, ip: Option<&str>| {
+            let request = rpc::Request::Single(rpc::Call::MethodCall(rpc::MethodCall {
+                jsonrpc: Some(rpc::Version::V2),
+                method: method.to_owned(),
+                params: rpc::Params::Array(vec![]),
+                id: rpc::Id::Num(1),
+            }));
+            let metadata = Metadata {
+                origin,
+                client: ip.map(|ip| ClientInfo {
+                    ip: Some(ip.parse().unwrap()),
+                    key: None,
+                }),
+                session: None,
+            };
+
+            match middleware
+                .on_request(request, metadata, |_request, _meta| {
+                    Box::new(rpc::futures::finished(None))
+                })
+                .wait()
+                .unwrap()
+            {
+                Some(rpc::Response::Single(rpc::Output::Failure(failure))) => {
+                    Some(failure.error.code)
+                }
+                _ => None,
+            }
+        };
+
+        let http = || Origin::Rpc("unknown origin / unknown agent".into());
+
+        assert_eq!(
+            on_request("admin_sessions", http(), Some("10.0.0.1")),
+            Some(rpc::ErrorCode::ServerError(ERROR_API_FORBIDDEN))
+        );
+        assert_eq!(on_request("eth_call", http(), Some("10.0.0.1")), None);
+        assert_eq!(
+            on_request("admin_sessions", http(), Some("127.0.0.1")),
+            None
+        );
+        assert_eq!(
+            on_request("admin_sessions", Origin::Ipc(H256::from(1)), None),
+            None
+        );
+        // clients with an unknown address are remote
+        assert_eq!(
+            on_request("admin_sessions", http(), None),
+            Some(rpc::ErrorCode::ServerError(ERROR_API_FORBIDDEN))
+        );
+    }
+
     #[test]
     fn should_limit_batch_size() {
         use futures::Future;
         use jsonrpc_core::Middleware as mw;
 
         // Middleware that accepts a max batch size of 1 request
-        let middleware = Middleware::new(TestNotifier {}, 1);
+        let middleware = Middleware::new(TestNotifier {}, 1, TEST_TIMEOUT, None, None, None);
 
         let batch_1 = rpc::Request::Batch(vec![rpc::Call::MethodCall(rpc::MethodCall {
             jsonrpc: Some(rpc::Version::V2),
@@ -363,7 +1168,7 @@ mod tests {
 
         // batch size: 1 (should pass)
         let response_1 = middleware
-            .on_request(batch_1, (), |_request, _meta| {
+            .on_request(batch_1, Metadata::default(), |_request, _meta| {
                 Box::new(rpc::futures::finished(None))
             })
             .wait()
@@ -374,7 +1179,7 @@ mod tests {
 
         // batch size: 2 (should fail)
         let response_2 = middleware
-            .on_request(batch_2, (), |_request, _meta| {
+            .on_request(batch_2, Metadata::default(), |_request, _meta| {
                 Box::new(rpc::futures::finished(None))
             })
             .wait()
@@ -390,4 +1195,62 @@ mod tests {
             _ => assert!(false, "Did not enforce batch size limit in batch"),
         };
     }
+
+    #[test]
+    fn should_time_out_requests() {
+        let middleware = Middleware::new(
+            TestNotifier {},
+            10,
+            Duration::from_millis(10),
+            None,
+            None,
+            None,
+        );
+
+        let response = tokio::runtime::current_thread::block_on_all(middleware.on_request(
+            make_request(1),
+            Metadata::default(),
+            |_request, _meta| rpc::futures::empty(),
+        ))
+        .unwrap();
+        match response {
+            Some(rpc::Response::Single(rpc::Output::Failure(failure))) => {
+                assert_eq!(
+                    failure.error.code,
+                    rpc::ErrorCode::ServerError(ERROR_TIMEOUT)
+                );
+                assert_eq!(failure.id, jsonrpc_core::Id::Num(1));
+            }
+            _ => assert!(false, "Did not time out request"),
+        };
+    }
+
+    #[test]
+    fn should_not_time_out_submissions() {
+        let middleware = Middleware::new(
+            TestNotifier {},
+            10,
+            Duration::from_millis(10),
+            None,
+            None,
+            None,
+        );
+        let request = rpc::Request::Single(rpc::Call::MethodCall(rpc::MethodCall {
+            jsonrpc: Some(rpc::Version::V2),
+            method: "eth_sendRawTransaction".to_owned(),
+            params: rpc::Params::Array(vec![]),
+            id: rpc::Id::Num(1),
+        }));
+
+        // The submission completes after the request timeout.
+        let response = tokio::runtime::current_thread::block_on_all(middleware.on_request(
+            request,
+            Metadata::default(),
+            |_request, _meta| {
+                Delay::new(Instant::now() + Duration::from_millis(50)).then(|_| Ok(None))
+            },
+        ))
+        .unwrap();
+        assert_eq!(response, None);
+    }
 }
@@ -0,0 +1,392 @@
+//! Connections to Oasis Core nodes.
+//!
+//! Reads are spread in round-robin order over the healthy nodes at the
+//! highest known round, so that reads do not go back to older blocks, and
+//! transaction submission fails over to the next node if a node is
+//! unreachable. Nodes are unhealthy until their first health check passes.
+//! Health checks also track whether the nodes are synced, and the latest
+//! round of each node. A node reporting to be synced is still syncing if it
+//! lags behind the highest round of any node, as nodes only report whether
+//! they completed their initial sync.
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use futures::{future, prelude::*};
+use grpcio::Environment;
+use lazy_static::lazy_static;
+use oasis_core_client::{transaction::api::storage::StorageClient, Node, TxnClient};
+use oasis_core_keymanager_client::{KeyManagerClient, RemoteClient};
+use oasis_core_runtime::common::namespace::Namespace;
+use parking_lot::Mutex;
+use prometheus::{labels, register_int_gauge_vec, IntGaugeVec};
+use slog::{info, warn, Logger};
+use tokio::timer::{Interval, Timeout};
+
+use crate::{logger::get_logger, EthereumRuntimeClient};
+
+// Metrics.
+lazy_static! {
+    static ref NODE_HEALTHY: IntGaugeVec = register_int_gauge_vec!(
+        "web3_gateway_node_healthy",
+        "Whether an Oasis Core node passes health checks",
+        &["node"]
+    )
+    .unwrap();
+}
+
+/// Time after which a node health check is considered failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Max number of rounds a synced node can be behind the highest round of any
+/// node, allowing for blocks produced between the health checks of nodes.
+const MAX_SYNC_LAG: u64 = 5;
+
+/// Size of the key manager client cache of each node.
+const KM_CACHE_SIZE: usize = 1024;
+
+/// Clients of a single Oasis Core node.
+pub struct NodeClient {
+    /// Node address.
+    pub address: String,
+    pub client: Arc<EthereumRuntimeClient>,
+    pub storage_client: StorageClient,
+    pub km_client: Arc<dyn KeyManagerClient>,
+    healthy: AtomicBool,
+    synced: AtomicBool,
+    latest_round: AtomicU64,
+}
+
+impl NodeClient {
+    /// Connect to the node at `address`.
+    pub fn connect(env: Arc<Environment>, address: &str, runtime_id: Namespace) -> Self {
+        let node = Node::new(env, address);
+        let txn_client = TxnClient::new(node.channel(), runtime_id, None);
+        // TODO: Key manager MRENCLAVE.
+        let km_client = Arc::new(RemoteClient::new_grpc(
+            runtime_id,
+            None,
+            node.channel(),
+            KM_CACHE_SIZE,
+        ));
+
+        Self::new(
+            address.to_owned(),
+            EthereumRuntimeClient::new(txn_client),
+            StorageClient::new(node.channel()),
+            km_client,
+        )
+    }
+
+    /// Create the clients of a node from clients built over its channel.
+    pub fn new(
+        address: String,
+        client: EthereumRuntimeClient,
+        storage_client: StorageClient,
+        km_client: Arc<dyn KeyManagerClient>,
+    ) -> Self {
+        Self {
+            address,
+            client: Arc::new(client),
+            storage_client,
+            km_client,
+            healthy: AtomicBool::new(false),
+            synced: AtomicBool::new(true),
+            latest_round: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether the node passed its last health check.
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::SeqCst)
+    }
+}
+
+/// Sync state of the nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncStatus {
+    /// A healthy node is synced, and at most `MAX_SYNC_LAG` rounds behind the
+    /// highest round of any node.
+    Synced,
+    /// No healthy node is synced and up to date.
+    Syncing {
+        /// Latest round of the healthy nodes when they started syncing.
+        starting: u64,
+        /// Latest round of the healthy nodes.
+        current: u64,
+        /// Highest latest round of any node.
+        highest: u64,
+    },
+}
+
+/// Oasis Core nodes the gateway is connected to.
+pub struct Nodes {
+    logger: Logger,
+    nodes: Vec<Arc<NodeClient>>,
+    next: AtomicUsize,
+    sync_status: Mutex<SyncStatus>,
+}
+
+impl Nodes {
+    /// Create the node set, which must not be empty.
+    pub fn new(nodes: Vec<NodeClient>) -> Result<Self> {
+        if nodes.is_empty() {
+            return Err(anyhow!("no Oasis Core node address given"));
+        }
+
+        for node in &nodes {
+            NODE_HEALTHY
+                .with(&labels! {"node" => node.address.as_str(),})
+                .set(0);
+        }
+
+        Ok(Self {
+            logger: get_logger("gateway/nodes"),
+            nodes: nodes.into_iter().map(Arc::new).collect(),
+            next: AtomicUsize::new(0),
+            sync_status: Mutex::new(SyncStatus::Synced),
+        })
+    }
+
+    /// All nodes.
+    pub fn all(&self) -> &[Arc<NodeClient>] {
+        &self.nodes
+    }
+
+    /// Returns the next healthy node at the highest known round in
+    /// round-robin order, or the next node if none of them is healthy.
+    pub fn select(&self) -> Arc<NodeClient> {
+        self.failover_order().swap_remove(0)
+    }
+
+    /// Returns the healthy nodes at the highest known round, starting with the
+    /// next one in round-robin order, followed by the other healthy nodes and
+    /// by the unhealthy nodes.
+    pub fn failover_order(&self) -> Vec<Arc<NodeClient>> {
+        let start = self.next.fetch_add(1, Ordering::SeqCst);
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|node| (node.is_healthy(), node.latest_round.load(Ordering::SeqCst)))
+            .collect();
+
+        failover_order(&nodes, start)
+            .into_iter()
+            .map(|i| self.nodes[i].clone())
+            .collect()
+    }
+
+    /// Returns the sync state of the nodes as of the last health checks.
+    pub fn sync_status(&self) -> SyncStatus {
+        *self.sync_status.lock()
+    }
+
+    /// Mark a node as unhealthy until its next successful health check.
+    pub fn mark_unhealthy(&self, node: &NodeClient) {
+        self.set_healthy(node, false);
+    }
+
+    /// Set whether a node is healthy, until its next health check.
+    pub fn set_healthy(&self, node: &NodeClient, healthy: bool) {
+        if node.healthy.swap(healthy, Ordering::SeqCst) != healthy {
+            if healthy {
+                info!(self.logger, "Oasis Core node is healthy"; "node" => &node.address);
+            } else {
+                warn!(self.logger, "Oasis Core node is unhealthy"; "node" => &node.address);
+            }
+        }
+        NODE_HEALTHY
+            .with(&labels! {"node" => node.address.as_str(),})
+            .set(healthy as i64);
+    }
+
+    fn update_sync_status(&self) {
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                (
+                    node.is_healthy(),
+                    node.synced.load(Ordering::SeqCst),
+                    node.latest_round.load(Ordering::SeqCst),
+                )
+            })
+            .collect();
+
+        let mut sync_status = self.sync_status.lock();
+        let status = sync_status_of(&nodes, *sync_status);
+        if status != *sync_status {
+            match status {
+                SyncStatus::Synced => info!(self.logger, "Oasis Core nodes are synced"),
+                SyncStatus::Syncing {
+                    current, highest, ..
+                } => warn!(self.logger, "Oasis Core nodes are syncing";
+                    "current_round" => current,
+                    "highest_round" => highest,
+                ),
+            }
+        }
+        *sync_status = status;
+    }
+
+    /// Check the health of all nodes now and every `interval`, by fetching
+    /// their latest block and sync state.
+    pub fn start_health_checks(
+        self: Arc<Self>,
+        interval: Duration,
+    ) -> impl Future<Item = (), Error = ()> {
+        Interval::new(Instant::now(), interval)
+            .map_err(|_| ())
+            .for_each(move |_| {
+                let nodes = self.clone();
+                let sync_nodes = self.clone();
+                future::join_all(self.nodes.iter().cloned().map(move |node| {
+                    let nodes = nodes.clone();
+                    let txn_client = node.client.txn_client();
+                    let check = txn_client.get_latest_block().join(txn_client.is_synced());
+                    Timeout::new(check, CHECK_TIMEOUT).then(move |result| {
+                        if let Ok((ref snapshot, synced)) = result {
+                            node.latest_round
+                                .store(snapshot.block.header.round, Ordering::SeqCst);
+                            node.synced.store(synced, Ordering::SeqCst);
+                        }
+                        nodes.set_healthy(&node, result.is_ok());
+                        Ok(())
+                    })
+                }))
+                .map(move |_| sync_nodes.update_sync_status())
+            })
+    }
+}
+
+/// Returns the sync state of nodes given as `(healthy, synced, latest round)`,
+/// keeping the starting round of the `previous` state if still syncing.
+fn sync_status_of(nodes: &[(bool, bool, u64)], previous: SyncStatus) -> SyncStatus {
+    let highest = nodes.iter().map(|&(_, _, round)| round).max().unwrap_or(0);
+    if nodes
+        .iter()
+        .any(|&(healthy, synced, round)| healthy && synced && round + MAX_SYNC_LAG >= highest)
+    {
+        return SyncStatus::Synced;
+    }
+
+    let current = nodes
+        .iter()
+        .filter(|&&(healthy, _, _)| healthy)
+        .map(|&(_, _, round)| round)
+        .max()
+        .unwrap_or(highest);
+    let starting = match previous {
+        SyncStatus::Syncing { starting, .. } => starting,
+        SyncStatus::Synced => current,
+    };
+
+    SyncStatus::Syncing {
+        starting,
+        current,
+        highest,
+    }
+}
+
+/// Returns the indices of nodes given as `(healthy, latest round)`, starting
+/// from `start`: first the healthy nodes at the highest round of any healthy
+/// node, then the other healthy nodes, then the unhealthy nodes.
+fn failover_order(nodes: &[(bool, u64)], start: usize) -> Vec<usize> {
+    let highest = nodes
+        .iter()
+        .filter(|&&(healthy, _)| healthy)
+        .map(|&(_, round)| round)
+        .max();
+    let (mut order, rest): (Vec<_>, Vec<_>) = (0..nodes.len())
+        .map(|i| (start + i) % nodes.len())
+        .partition(|&i| nodes[i].0 && Some(nodes[i].1) == highest);
+    let (lagging, unhealthy): (Vec<_>, Vec<_>) = rest.into_iter().partition(|&i| nodes[i].0);
+    order.extend(lagging);
+    order.extend(unhealthy);
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_prefer_healthy_nodes() {
+        let nodes = [(true, 5), (true, 5), (true, 5)];
+        assert_eq!(failover_order(&nodes, 1), vec![1, 2, 0]);
+        let nodes = [(true, 5), (false, 5), (true, 5)];
+        assert_eq!(failover_order(&nodes, 1), vec![2, 0, 1]);
+        assert_eq!(failover_order(&[(false, 5), (true, 5)], 0), vec![1, 0]);
+        assert_eq!(failover_order(&[(false, 0), (false, 0)], 1), vec![1, 0]);
+    }
+
+    #[test]
+    fn should_prefer_nodes_at_highest_round() {
+        let nodes = [(true, 4), (true, 5), (true, 3), (true, 5)];
+        assert_eq!(failover_order(&nodes, 0), vec![1, 3, 0, 2]);
+        assert_eq!(failover_order(&nodes, 2), vec![3, 1, 2, 0]);
+        // unhealthy nodes at a higher round are not preferred
+        let nodes = [(true, 4), (false, 6), (true, 3)];
+        assert_eq!(failover_order(&nodes, 1), vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn should_report_sync_status() {
+        let synced = SyncStatus::Synced;
+        assert_eq!(
+            sync_status_of(&[(true, false, 10), (true, true, 12)], synced),
+            SyncStatus::Synced
+        );
+        // unhealthy nodes do not count as synced
+        let syncing = sync_status_of(&[(true, false, 10), (false, true, 12)], synced);
+        assert_eq!(
+            syncing,
+            SyncStatus::Syncing {
+                starting: 10,
+                current: 10,
+                highest: 12,
+            }
+        );
+        // the starting round is kept while syncing
+        assert_eq!(
+            sync_status_of(&[(true, false, 11), (false, true, 12)], syncing),
+            SyncStatus::Syncing {
+                starting: 10,
+                current: 11,
+                highest: 12,
+            }
+        );
+        assert_eq!(
+            sync_status_of(&[(true, true, 12), (false, true, 12)], syncing),
+            SyncStatus::Synced
+        );
+    }
+
+    #[test]
+    fn should_report_lagging_nodes_as_syncing() {
+        let synced = SyncStatus::Synced;
+        assert_eq!(
+            sync_status_of(&[(true, true, 15), (true, true, 20)], synced),
+            SyncStatus::Synced
+        );
+        // nodes which completed their initial sync may fall behind
+        let syncing = sync_status_of(&[(true, true, 14), (false, true, 20)], synced);
+        assert_eq!(
+            syncing,
+            SyncStatus::Syncing {
+                starting: 14,
+                current: 14,
+                highest: 20,
+            }
+        );
+        assert_eq!(
+            sync_status_of(&[(true, true, 18), (false, true, 20)], syncing),
+            SyncStatus::Synced
+        );
+    }
+}
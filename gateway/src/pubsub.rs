@@ -1,20 +1,20 @@
 //! Pub/sub support.
 use std::{
+    mem,
     process::abort,
     sync::{
         atomic::{AtomicU64, Ordering},
-        Arc, RwLock, Weak,
+        Arc, Mutex, RwLock, Weak,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use ethcore::filter::TxEntry;
 use futures::prelude::*;
-use oasis_core_runtime::common::logger::get_logger;
 use slog::{error, Logger};
 use tokio::timer::Interval;
 
-use crate::translator::Translator;
+use crate::{logger::get_logger, nodes::SyncStatus, translator::Translator};
 
 /// An actor listening to chain events.
 ///
@@ -23,12 +23,16 @@ pub trait Listener: Send + Sync {
     fn notify_blocks(&self, from_block: u64, to_block: u64);
 
     fn notify_completed_transaction(&self, entry: &TxEntry, output: Vec<u8>);
+
+    fn notify_sync_status(&self, status: SyncStatus);
 }
 
 struct Inner {
     logger: Logger,
     translator: Arc<Translator>,
     last_notified_block: AtomicU64,
+    last_poll: Mutex<Instant>,
+    sync_status: Mutex<SyncStatus>,
     listeners: RwLock<Vec<Weak<dyn Listener>>>,
 }
 
@@ -43,6 +47,8 @@ impl Broker {
                 logger: get_logger("gateway/pubsub/broker"),
                 translator,
                 last_notified_block: AtomicU64::new(0),
+                last_poll: Mutex::new(Instant::now()),
+                sync_status: Mutex::new(SyncStatus::Synced),
                 listeners: RwLock::new(vec![]),
             }),
         }
@@ -53,6 +59,12 @@ impl Broker {
         listeners.push(listener);
     }
 
+    /// Time elapsed since the broker last polled for new blocks, or since it
+    /// was created if it has not polled yet.
+    pub fn last_poll_elapsed(&self) -> Duration {
+        self.inner.last_poll.lock().unwrap().elapsed()
+    }
+
     pub fn start(&self, interval: Duration) -> impl Future<Item = (), Error = ()> {
         let inner = self.inner.clone();
         let logger = inner.logger.clone();
@@ -60,9 +72,23 @@ impl Broker {
         Interval::new_interval(interval)
             .map_err(Into::into)
             .for_each(move |_| {
+                // Notify all listeners if the node sync state changed.
+                let sync_status = inner.translator.nodes().sync_status();
+                let previous = mem::replace(&mut *inner.sync_status.lock().unwrap(), sync_status);
+                if previous != sync_status {
+                    for listener in inner.listeners.read().unwrap().iter() {
+                        if let Some(listener) = listener.upgrade() {
+                            listener.notify_sync_status(sync_status);
+                        }
+                    }
+                }
+
                 // Get latest block and notify all listeners of the difference.
+                // This also refreshes the latest block cached by the translator.
                 let inner = inner.clone();
-                inner.translator.get_latest_block().map(move |blk| {
+                inner.translator.refresh_latest_block().map(move |blk| {
+                    *inner.last_poll.lock().unwrap() = Instant::now();
+
                     let last_notified_block = inner.last_notified_block.load(Ordering::SeqCst);
                     let listeners = inner.listeners.read().unwrap();
 
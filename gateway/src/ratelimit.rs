@@ -0,0 +1,742 @@
+//! Per-client rate limiting.
+//!
+//! Clients are identified by the peer address of their connection, or by the
+//! `X-Forwarded-For` header set by trusted reverse proxies in front of the
+//! gateway, if any.
+//!
+//! WebSocket sessions are also limited in subscriptions, and closed once
+//! idle or unresponsive.
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+use futures::prelude::*;
+use lazy_static::lazy_static;
+use parity_rpc::v1::types::H256;
+use parking_lot::Mutex;
+use prometheus::{register_int_counter, IntCounter};
+use tokio::timer::Interval;
+use ws;
+
+use crate::context::CancelGuard;
+
+// Metrics.
+lazy_static! {
+    static ref WS_IDLE_DISCONNECTS: IntCounter = register_int_counter!(
+        "web3_gateway_ws_idle_disconnects",
+        "Number of WebSocket sessions closed for being idle"
+    )
+    .unwrap();
+}
+
+/// Interval at which idle token buckets are removed.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Per-client rate limiting configuration.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateLimitConfig {
+    /// Max HTTP requests/second allowed per client (0 for no limit).
+    pub http_max_req_per_sec: usize,
+    /// Max WebSocket requests/second allowed per client, across all of its
+    /// connections (0 for no limit).
+    pub ws_max_req_per_sec: usize,
+    /// Max concurrent WebSocket connections allowed per client (0 for no limit).
+    pub ws_max_connections: usize,
+    /// Number of trusted reverse proxies in front of the gateway that append
+    /// to the `X-Forwarded-For` header (0 to ignore the header).
+    pub trusted_proxies: usize,
+}
+
+/// Determine the client address of a connection from `peer`.
+///
+/// Without trusted proxies, the client is the peer. Otherwise each proxy
+/// appends the address it received the request from to the
+/// `X-Forwarded-For` header, so the client address is the one appended by the
+/// outermost trusted proxy. Any addresses before it may have been set by the
+/// client and are ignored. If the header is missing or malformed, the client
+/// is the peer.
+pub fn client_ip(peer: IpAddr, forwarded_for: Option<&str>, trusted_proxies: usize) -> IpAddr {
+    let forwarded_for = match forwarded_for {
+        Some(forwarded_for) if trusted_proxies > 0 => forwarded_for,
+        _ => return peer,
+    };
+
+    let addrs: Vec<&str> = forwarded_for.split(',').map(str::trim).collect();
+    let addr = addrs[addrs.len().saturating_sub(trusted_proxies)];
+
+    addr.parse::<IpAddr>()
+        .or_else(|_| addr.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .unwrap_or(peer)
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct Buckets<K> {
+    buckets: HashMap<K, Bucket>,
+    last_cleanup: Instant,
+}
+
+/// Token bucket rate limiter keyed by client.
+///
+/// Each client may burst up to one second worth of requests.
+pub struct RateLimiter<K = IpAddr> {
+    rate: f64,
+    buckets: Mutex<Buckets<K>>,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    /// Create a new rate limiter allowing `max_req_per_sec` requests/second
+    /// per client.
+    pub fn new(max_req_per_sec: usize) -> Self {
+        RateLimiter {
+            rate: max_req_per_sec as f64,
+            buckets: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                last_cleanup: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take a token for a request from the client. Returns false if the client
+    /// exceeded its rate.
+    pub fn check(&self, client: K) -> bool {
+        self.take_at(client, 1.0, Instant::now())
+    }
+
+    /// Take `cost` tokens for a request from the client. Returns false if the
+    /// client exceeded its rate.
+    pub fn take(&self, client: K, cost: f64) -> bool {
+        self.take_at(client, cost, Instant::now())
+    }
+
+    #[cfg(test)]
+    fn check_at(&self, client: K, now: Instant) -> bool {
+        self.take_at(client, 1.0, now)
+    }
+
+    fn take_at(&self, client: K, cost: f64, now: Instant) -> bool {
+        let rate = self.rate;
+        let mut buckets = self.buckets.lock();
+
+        // Drop buckets which have refilled, they are equivalent to new ones.
+        if now.duration_since(buckets.last_cleanup) >= CLEANUP_INTERVAL {
+            buckets.buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < rate
+            });
+            buckets.last_cleanup = now;
+        }
+
+        let bucket = buckets.buckets.entry(client).or_insert(Bucket {
+            tokens: rate,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(rate);
+        bucket.updated = now;
+
+        if bucket.tokens < cost {
+            return false;
+        }
+        bucket.tokens -= cost;
+        true
+    }
+
+    #[cfg(test)]
+    fn clients(&self) -> usize {
+        self.buckets.lock().buckets.len()
+    }
+}
+
+/// WebSocket session limits.
+///
+/// Sessions are pinged every `ping_interval`, and closed once their client
+/// does not answer for two intervals, so that sessions with subscriptions
+/// whose client is gone do not stay open.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WsSessionConfig {
+    /// Time without requests after which a session without subscriptions is
+    /// closed (0 to disable).
+    pub idle_timeout: Duration,
+    /// Interval between pings of a session (0 to disable).
+    pub ping_interval: Duration,
+    /// Max subscriptions per session (0 for no limit).
+    pub max_subscriptions: usize,
+    /// Max notifications being sent to a subscriber, beyond which its session
+    /// is closed (0 for no limit).
+    pub max_queued_notifications: usize,
+    /// Max number of rounds a logs subscription can replay logs from (0 for
+    /// no limit).
+    pub max_logs_replay_depth: u64,
+    /// Max number of logs subscriptions replaying logs at once (0 for no
+    /// limit).
+    pub max_logs_replays: usize,
+}
+
+impl WsSessionConfig {
+    /// Returns true if a session whose client was last heard from at
+    /// `last_seen` did not answer its pings.
+    pub fn is_unresponsive(&self, last_seen: Instant, now: Instant) -> bool {
+        self.ping_interval != Duration::from_secs(0)
+            && now.duration_since(last_seen) >= self.ping_interval * 2
+    }
+}
+
+impl Default for WsSessionConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(300),
+            ping_interval: Duration::from_secs(30),
+            max_subscriptions: 100,
+            max_queued_notifications: 1024,
+            max_logs_replay_depth: 10000,
+            max_logs_replays: 16,
+        }
+    }
+}
+
+/// Identity of the client of an HTTP request or a WebSocket session.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientInfo {
+    /// Client address, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip: Option<IpAddr>,
+    /// Name of the API key the client authenticated with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+struct Session {
+    client: ClientInfo,
+    sender: Option<ws::Sender>,
+    /// Contexts of the session's requests, cancelled when it closes.
+    requests: Vec<Weak<CancelGuard>>,
+    last_request: Instant,
+    subscriptions: usize,
+}
+
+struct Sessions {
+    sessions: HashMap<H256, Session>,
+    connections: HashMap<IpAddr, usize>,
+}
+
+/// Tracks the clients of WebSocket sessions, enforcing per-client connection
+/// and request rate limits, and per-session limits.
+pub struct WsClients {
+    max_connections: usize,
+    trusted_proxies: usize,
+    limiter: Option<RateLimiter>,
+    session_config: WsSessionConfig,
+    sessions: Mutex<Sessions>,
+}
+
+impl WsClients {
+    /// Create a new WebSocket client tracker from the rate limiting and
+    /// session configs.
+    pub fn new(config: &RateLimitConfig, session_config: WsSessionConfig) -> Self {
+        WsClients {
+            max_connections: config.ws_max_connections,
+            trusted_proxies: config.trusted_proxies,
+            limiter: match config.ws_max_req_per_sec {
+                0 => None,
+                max_req_per_sec => Some(RateLimiter::new(max_req_per_sec)),
+            },
+            session_config,
+            sessions: Mutex::new(Sessions {
+                sessions: HashMap::new(),
+                connections: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Determine the client address of a connection from `peer`.
+    pub fn client_ip(&self, peer: IpAddr, forwarded_for: Option<&str>) -> IpAddr {
+        client_ip(peer, forwarded_for, self.trusted_proxies)
+    }
+
+    /// Returns true if the client may open another connection.
+    pub fn can_connect(&self, ip: IpAddr) -> bool {
+        self.max_connections == 0
+            || self
+                .sessions
+                .lock()
+                .connections
+                .get(&ip)
+                .map_or(true, |connections| *connections < self.max_connections)
+    }
+
+    /// Start tracking a session of the client. The session can be closed
+    /// through its `sender`, if given.
+    pub fn open_session(&self, id: H256, client: ClientInfo, sender: Option<ws::Sender>) {
+        let mut sessions = self.sessions.lock();
+        if let Some(ip) = client.ip {
+            *sessions.connections.entry(ip).or_insert(0) += 1;
+        }
+        sessions.sessions.insert(
+            id,
+            Session {
+                client,
+                sender,
+                requests: vec![],
+                last_request: Instant::now(),
+                subscriptions: 0,
+            },
+        );
+    }
+
+    /// Cancel the context of a request of the session when it closes.
+    pub fn track_request(&self, id: &H256, cancel: &Arc<CancelGuard>) {
+        if let Some(session) = self.sessions.lock().sessions.get_mut(id) {
+            session
+                .requests
+                .retain(|request| request.upgrade().is_some());
+            session.requests.push(Arc::downgrade(cancel));
+        }
+    }
+
+    /// Stop tracking a session, cancelling its pending requests.
+    pub fn close_session(&self, id: &H256) {
+        let mut sessions = self.sessions.lock();
+        let session = match sessions.sessions.remove(id) {
+            Some(session) => session,
+            None => return,
+        };
+        for request in session.requests.iter().filter_map(Weak::upgrade) {
+            request.cancel();
+        }
+
+        if let Some(ip) = session.client.ip {
+            let remove = match sessions.connections.get_mut(&ip) {
+                Some(connections) => {
+                    *connections -= 1;
+                    *connections == 0
+                }
+                None => false,
+            };
+            if remove {
+                sessions.connections.remove(&ip);
+            }
+        }
+    }
+
+    /// Returns the client address of the session, if known.
+    pub fn session_ip(&self, id: &H256) -> Option<IpAddr> {
+        self.sessions
+            .lock()
+            .sessions
+            .get(id)
+            .and_then(|session| session.client.ip)
+    }
+
+    /// Returns the tracked sessions and their clients.
+    pub fn sessions(&self) -> Vec<(H256, ClientInfo)> {
+        self.sessions
+            .lock()
+            .sessions
+            .iter()
+            .map(|(id, session)| (id.clone(), session.client.clone()))
+            .collect()
+    }
+
+    /// Close a session. Returns false if the session is not tracked or cannot
+    /// be closed.
+    pub fn disconnect(&self, id: &H256) -> bool {
+        let sessions = self.sessions.lock();
+        match sessions
+            .sessions
+            .get(id)
+            .and_then(|session| session.sender.as_ref())
+        {
+            Some(sender) => sender.close(ws::CloseCode::Policy).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Count a subscription of the session. Returns false if the session has
+    /// the max number of subscriptions.
+    pub fn add_subscription(&self, id: &H256) -> bool {
+        let max_subscriptions = self.session_config.max_subscriptions;
+        match self.sessions.lock().sessions.get_mut(id) {
+            Some(ref session)
+                if max_subscriptions != 0 && session.subscriptions >= max_subscriptions =>
+            {
+                false
+            }
+            Some(session) => {
+                session.subscriptions += 1;
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Stop counting a subscription of the session.
+    pub fn remove_subscription(&self, id: &H256) {
+        if let Some(session) = self.sessions.lock().sessions.get_mut(id) {
+            session.subscriptions = session.subscriptions.saturating_sub(1);
+        }
+    }
+
+    /// Returns the session limits.
+    pub fn session_config(&self) -> &WsSessionConfig {
+        &self.session_config
+    }
+
+    /// Returns the sessions without subscriptions and without requests for
+    /// the idle timeout.
+    fn idle_sessions(&self, now: Instant) -> Vec<H256> {
+        let idle_timeout = self.session_config.idle_timeout;
+        if idle_timeout == Duration::from_secs(0) {
+            return vec![];
+        }
+
+        self.sessions
+            .lock()
+            .sessions
+            .iter()
+            .filter(|&(_, session)| {
+                session.subscriptions == 0
+                    && now.duration_since(session.last_request) >= idle_timeout
+            })
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Close the idle sessions every `interval`.
+    pub fn start_idle_checks(
+        self: Arc<Self>,
+        interval: Duration,
+    ) -> impl Future<Item = (), Error = ()> {
+        Interval::new_interval(interval)
+            .map_err(|_| ())
+            .for_each(move |_| {
+                for id in self.idle_sessions(Instant::now()) {
+                    if self.disconnect(&id) {
+                        WS_IDLE_DISCONNECTS.inc();
+                    }
+                }
+                Ok(())
+            })
+    }
+
+    /// Count a request on the session. Returns false if the session's client
+    /// exceeded its rate.
+    pub fn check_request(&self, id: &H256) -> bool {
+        if let Some(session) = self.sessions.lock().sessions.get_mut(id) {
+            session.last_request = Instant::now();
+        }
+
+        let limiter = match self.limiter {
+            Some(ref limiter) => limiter,
+            None => return true,
+        };
+
+        self.session_ip(id).map_or(true, |ip| limiter.check(ip))
+    }
+}
+
+/// Client charged for requests against a per-client budget.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Client {
+    /// Client with a known address.
+    Ip(IpAddr),
+    /// WebSocket session of a client with an unknown address.
+    Session(H256),
+}
+
+/// Per-method request cost configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaConfig {
+    /// Cost units/second allowed per client (0 for no limit). A request
+    /// costing more than the budget is always rejected.
+    pub budget: usize,
+    /// Cost of methods without an entry in `method_costs`.
+    pub default_cost: usize,
+    /// Cost of each method.
+    pub method_costs: HashMap<String, usize>,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        let method_costs = [
+            ("eth_call", 10),
+            ("eth_callMany", 20),
+            ("eth_estimateGas", 20),
+            ("eth_getLogs", 20),
+            ("eth_getProof", 10),
+            ("oasis_getProof", 10),
+        ]
+        .iter()
+        .map(|&(method, cost)| (method.to_owned(), cost))
+        .collect();
+
+        QuotaConfig {
+            budget: 0,
+            default_cost: 1,
+            method_costs,
+        }
+    }
+}
+
+/// Cost-weighted per-client request quotas.
+pub struct Quotas {
+    config: QuotaConfig,
+    limiter: RateLimiter<Client>,
+}
+
+impl Quotas {
+    /// Create new quotas from the config.
+    pub fn new(config: QuotaConfig) -> Self {
+        Quotas {
+            limiter: RateLimiter::new(config.budget),
+            config,
+        }
+    }
+
+    /// Returns the cost of a call to the method.
+    pub fn cost(&self, method: &str) -> usize {
+        self.config
+            .method_costs
+            .get(method)
+            .cloned()
+            .unwrap_or(self.config.default_cost)
+    }
+
+    /// Returns true if the method has an entry in the cost table.
+    pub fn is_priced(&self, method: &str) -> bool {
+        self.config.method_costs.contains_key(method)
+    }
+
+    /// Charge the client for a request. Returns false if the client exceeded
+    /// its budget.
+    pub fn charge(&self, client: Client, cost: usize) -> bool {
+        self.limiter.take(client, cost as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_resolve_client_ip() {
+        let peer: IpAddr = "127.0.0.1".parse().unwrap();
+        let xff = Some("10.0.0.1, 192.168.1.1, 172.16.0.1");
+
+        // without trusted proxies, the header is ignored
+        assert_eq!(client_ip(peer, xff, 0), peer);
+        assert_eq!(client_ip(peer, None, 1), peer);
+        assert_eq!(
+            client_ip(peer, xff, 1),
+            "172.16.0.1".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            client_ip(peer, xff, 2),
+            "192.168.1.1".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            client_ip(peer, xff, 5),
+            "10.0.0.1".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            client_ip(peer, Some("10.0.0.1:1234"), 1),
+            "10.0.0.1".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            client_ip(peer, Some("::1"), 1),
+            "::1".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(client_ip(peer, Some("unknown"), 1), peer);
+    }
+
+    #[test]
+    fn should_limit_request_rate_per_client() {
+        let limiter: RateLimiter = RateLimiter::new(2);
+        let client_1 = "10.0.0.1".parse().unwrap();
+        let client_2 = "10.0.0.2".parse().unwrap();
+        let now = Instant::now();
+
+        // burst of 2 requests
+        assert!(limiter.check_at(client_1, now));
+        assert!(limiter.check_at(client_1, now));
+        assert!(!limiter.check_at(client_1, now));
+
+        // other clients are not affected
+        assert!(limiter.check_at(client_2, now));
+
+        // refills at 2 requests/sec
+        let now = now + Duration::from_millis(500);
+        assert!(limiter.check_at(client_1, now));
+        assert!(!limiter.check_at(client_1, now));
+
+        // refilled buckets are removed
+        assert_eq!(limiter.clients(), 2);
+        assert!(limiter.check_at(client_1, now + CLEANUP_INTERVAL));
+        assert_eq!(limiter.clients(), 1);
+    }
+
+    #[test]
+    fn should_charge_method_costs() {
+        let mut config = QuotaConfig {
+            budget: 10,
+            ..Default::default()
+        };
+        config.method_costs.insert("eth_getLogs".to_owned(), 8);
+        let quotas = Quotas::new(config);
+        let client = Client::Session(H256::from(1));
+
+        assert_eq!(quotas.cost("eth_getLogs"), 8);
+        assert_eq!(quotas.cost("eth_blockNumber"), 1);
+        assert!(quotas.is_priced("eth_call"));
+        assert!(!quotas.is_priced("eth_blockNumber"));
+
+        assert!(quotas.charge(client.clone(), quotas.cost("eth_getLogs")));
+        assert!(!quotas.charge(client.clone(), quotas.cost("eth_getLogs")));
+        assert!(quotas.charge(client, quotas.cost("eth_blockNumber")));
+
+        // other clients have their own budget
+        assert!(quotas.charge(Client::Session(H256::from(2)), 10));
+        assert!(!quotas.charge(Client::Session(H256::from(3)), 11));
+    }
+
+    #[test]
+    fn should_limit_connections_per_client() {
+        let clients = WsClients::new(
+            &RateLimitConfig {
+                ws_max_connections: 2,
+                ws_max_req_per_sec: 1,
+                trusted_proxies: 1,
+                ..Default::default()
+            },
+            Default::default(),
+        );
+        let ip = clients.client_ip("127.0.0.1".parse().unwrap(), Some("10.0.0.1"));
+        let client = ClientInfo {
+            ip: Some(ip),
+            key: None,
+        };
+
+        clients.open_session(H256::from(1), client.clone(), None);
+        assert!(clients.can_connect(ip));
+        clients.open_session(H256::from(2), client, None);
+        assert!(!clients.can_connect(ip));
+
+        // the rate limit is shared by the client's sessions
+        assert!(clients.check_request(&H256::from(1)));
+        assert!(!clients.check_request(&H256::from(2)));
+
+        clients.close_session(&H256::from(1));
+        assert!(clients.can_connect(ip));
+
+        // sessions without a known client address are not limited
+        assert!(clients.check_request(&H256::from(3)));
+        assert!(clients.check_request(&H256::from(3)));
+    }
+
+    #[test]
+    fn should_limit_clients_by_peer_without_proxies() {
+        let clients = WsClients::new(
+            &RateLimitConfig {
+                ws_max_connections: 1,
+                ..Default::default()
+            },
+            Default::default(),
+        );
+        let peer_1: IpAddr = "10.0.0.1".parse().unwrap();
+        let peer_2: IpAddr = "10.0.0.2".parse().unwrap();
+
+        // clients cannot evade the limit by setting the header themselves
+        let ip = clients.client_ip(peer_1, Some("192.168.1.1"));
+        assert_eq!(ip, peer_1);
+        clients.open_session(
+            H256::from(1),
+            ClientInfo {
+                ip: Some(ip),
+                key: None,
+            },
+            None,
+        );
+        assert!(!clients.can_connect(clients.client_ip(peer_1, Some("192.168.1.2"))));
+        assert!(clients.can_connect(clients.client_ip(peer_2, None)));
+    }
+
+    #[test]
+    fn should_limit_subscriptions_per_session() {
+        let clients = WsClients::new(
+            &Default::default(),
+            WsSessionConfig {
+                max_subscriptions: 2,
+                ..Default::default()
+            },
+        );
+        clients.open_session(H256::from(1), Default::default(), None);
+        clients.open_session(H256::from(2), Default::default(), None);
+
+        assert!(clients.add_subscription(&H256::from(1)));
+        assert!(clients.add_subscription(&H256::from(1)));
+        assert!(!clients.add_subscription(&H256::from(1)));
+        assert!(clients.add_subscription(&H256::from(2)));
+
+        clients.remove_subscription(&H256::from(1));
+        assert!(clients.add_subscription(&H256::from(1)));
+    }
+
+    #[test]
+    fn should_find_idle_sessions() {
+        let clients = WsClients::new(
+            &Default::default(),
+            WsSessionConfig {
+                idle_timeout: Duration::from_secs(60),
+                ..Default::default()
+            },
+        );
+        clients.open_session(H256::from(1), Default::default(), None);
+        clients.open_session(H256::from(2), Default::default(), None);
+        clients.open_session(H256::from(3), Default::default(), None);
+        let later = Instant::now() + Duration::from_secs(60);
+        assert!(clients.idle_sessions(Instant::now()).is_empty());
+
+        // sessions with subscriptions are not idle
+        clients.add_subscription(&H256::from(2));
+        let idle = clients.idle_sessions(later);
+        assert_eq!(idle.len(), 2);
+        assert!(idle.contains(&H256::from(1)));
+        assert!(idle.contains(&H256::from(3)));
+
+        let clients = WsClients::new(
+            &Default::default(),
+            WsSessionConfig {
+                idle_timeout: Duration::from_secs(0),
+                ..Default::default()
+            },
+        );
+        clients.open_session(H256::from(1), Default::default(), None);
+        assert!(clients.idle_sessions(later).is_empty());
+    }
+
+    #[test]
+    fn should_find_unresponsive_sessions() {
+        let config = WsSessionConfig {
+            ping_interval: Duration::from_secs(30),
+            ..Default::default()
+        };
+        let last_seen = Instant::now();
+        assert!(!config.is_unresponsive(last_seen, last_seen + Duration::from_secs(30)));
+        assert!(!config.is_unresponsive(last_seen, last_seen + Duration::from_secs(59)));
+        assert!(config.is_unresponsive(last_seen, last_seen + Duration::from_secs(60)));
+
+        // sessions are never unresponsive without pings
+        let config = WsSessionConfig {
+            ping_interval: Duration::from_secs(0),
+            ..Default::default()
+        };
+        assert!(!config.is_unresponsive(last_seen, last_seen + Duration::from_secs(3600)));
+    }
+}
@@ -14,18 +14,30 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{collections::HashSet, io, sync::Arc};
-
+use std::{
+    collections::HashSet,
+    fs, io,
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use auth::ApiKeys;
+use health::HealthChecker;
 use informant::RpcStats;
 use jsonrpc_core::MetaIoHandler;
-use middleware::{Middleware, WsDispatcher, WsStats};
-use parity_rpc::{self as rpc, DomainsValidation, Metadata};
+use libc;
+use metadata::Metadata;
+use middleware::{HttpRateLimiter, IpcExtractor, Middleware, WsDispatcher, WsStats};
+use parity_rpc as rpc;
+use ratelimit::{Quotas, RateLimitConfig, WsClients};
 use rpc_apis::{self, ApiSet};
+use tls::TlsAcceptor;
 use tokio::runtime::TaskExecutor;
+use ws;
 
-use servers;
-
-pub use parity_rpc::{ws::Server as WsServer, HttpServer, RequestMiddleware};
+pub use servers::{self, HttpServer, IpcServer, WsServer};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct HttpConfiguration {
@@ -37,6 +49,14 @@ pub struct HttpConfiguration {
     pub hosts: Option<Vec<String>>,
     pub server_threads: usize,
     pub max_batch_size: usize,
+    pub rate_limit: RateLimitConfig,
+    /// Max number of concurrent connections.
+    pub max_connections: usize,
+    /// Time allowed for a client to send a request on an idle connection
+    /// (0 to disable).
+    pub idle_timeout: Duration,
+    /// Serve over TLS.
+    pub tls: bool,
 }
 
 impl Default for HttpConfiguration {
@@ -50,6 +70,10 @@ impl Default for HttpConfiguration {
             hosts: Some(vec![]),
             server_threads: 1,
             max_batch_size: 10,
+            rate_limit: RateLimitConfig::default(),
+            max_connections: 10000,
+            idle_timeout: Duration::from_secs(60),
+            tls: false,
         }
     }
 }
@@ -67,6 +91,8 @@ pub struct WsConfiguration {
     pub dapps_address: Option<rpc::Host>,
     pub max_batch_size: usize,
     pub max_req_per_sec: usize,
+    /// Serve over TLS.
+    pub tls: bool,
 }
 
 impl Default for WsConfiguration {
@@ -87,6 +113,29 @@ impl Default for WsConfiguration {
             dapps_address: Some("127.0.0.1:8545".into()),
             max_batch_size: 10,
             max_req_per_sec: 50,
+            tls: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IpcConfiguration {
+    pub enabled: bool,
+    pub socket_addr: String,
+    pub apis: ApiSet,
+    /// Permissions of the socket file.
+    pub permissions: u32,
+    pub max_batch_size: usize,
+}
+
+impl Default for IpcConfiguration {
+    fn default() -> Self {
+        IpcConfiguration {
+            enabled: false,
+            socket_addr: "jsonrpc.ipc".into(),
+            apis: ApiSet::UnsafeContext,
+            permissions: 0o600,
+            max_batch_size: 10,
         }
     }
 }
@@ -113,10 +162,48 @@ fn address(
     }
 }
 
+/// Allowed origins and `Host` headers of the RPC servers. `None` allows all,
+/// and the server's own address is always allowed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessConfig {
+    /// CORS allow-list of the HTTP server.
+    pub http_cors: Option<Vec<String>>,
+    /// Allowed `Host` headers of the HTTP server.
+    pub http_hosts: Option<Vec<String>>,
+    /// Allowed origins of WebSocket connections.
+    pub ws_origins: Option<Vec<String>>,
+    /// Allowed `Host` headers of the WebSocket server.
+    pub ws_hosts: Option<Vec<String>>,
+}
+
+impl AccessConfig {
+    /// Parse a comma-separated list of origins or hosts, where `all` allows
+    /// all and `none` allows none.
+    pub fn parse_list(list: &str) -> Option<Vec<String>> {
+        match list {
+            "all" | "*" => None,
+            "none" => Some(vec![]),
+            list => Some(
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(Into::into)
+                    .collect(),
+            ),
+        }
+    }
+}
+
 pub struct Dependencies<D: rpc_apis::Dependencies> {
     pub apis: Arc<D>,
     pub executor: TaskExecutor,
     pub stats: Arc<RpcStats>,
+    pub health: Arc<HealthChecker>,
+    pub quotas: Option<Arc<Quotas>>,
+    pub api_keys: Option<Arc<ApiKeys>>,
+    pub ws_clients: Arc<WsClients>,
+    pub tls: Option<Arc<TlsAcceptor>>,
+    pub request_timeout: Duration,
 }
 
 pub fn new_ws<D: rpc_apis::Dependencies>(
@@ -132,10 +219,18 @@ pub fn new_ws<D: rpc_apis::Dependencies>(
         .parse()
         .map_err(|_| format!("Invalid WebSockets listen host/port given: {}", url))?;
 
+    let clients = deps.ws_clients.clone();
     let handler = {
         let mut handler = MetaIoHandler::with_middleware((
-            WsDispatcher::new(deps.stats.clone(), conf.max_req_per_sec),
-            Middleware::new(deps.apis.activity_notifier(), conf.max_batch_size),
+            WsDispatcher::new(deps.stats.clone(), clients.clone(), conf.max_req_per_sec),
+            Middleware::new(
+                deps.apis.activity_notifier(),
+                conf.max_batch_size,
+                deps.request_timeout,
+                deps.quotas.clone(),
+                deps.api_keys.clone(),
+                Some(clients.clone()),
+            ),
         ));
         let apis = conf.apis.list_apis();
         deps.apis.extend_with_set(&mut handler, &apis);
@@ -143,26 +238,26 @@ pub fn new_ws<D: rpc_apis::Dependencies>(
         handler
     };
 
-    let remote = deps.executor.clone();
-    let allowed_origins = into_domains(collect_hosts(conf.origins, &conf.dapps_address));
-    let allowed_hosts = into_domains(collect_hosts(conf.hosts, &Some(url.clone().into())));
+    let tls = deps.tls.clone().filter(|_| conf.tls);
+    let allowed_origins = into_list(collect_hosts(conf.origins, &conf.dapps_address));
+    let allowed_hosts = into_list(collect_hosts(conf.hosts, &Some(url.clone().into())));
 
     let start_result = servers::start_ws(
         &addr,
         handler,
-        remote.clone(),
+        deps.executor.clone(),
         allowed_origins,
         allowed_hosts,
         conf.max_connections,
-        rpc::WsExtractor::new(None),
-        rpc::WsExtractor::new(None),
-        WsStats::new(deps.stats.clone()),
+        deps.api_keys.clone(),
+        WsStats::new(deps.stats.clone(), clients.clone()),
+        clients,
+        tls,
     );
 
     match start_result {
         Ok(server) => Ok(Some(server)),
-        Err(rpc::ws::Error(rpc::ws::ErrorKind::Io(ref err), _))
-            if err.kind() == io::ErrorKind::AddrInUse => Err(
+        Err(ref err) if is_addr_in_use(err) => Err(
             format!("WebSockets address {} is already in use, make sure that another instance of an Ethereum client is not running or change the address using the --ws-port and --ws-interface options.", url)
         ),
         Err(e) => Err(format!("WebSockets error: {:?}", e)),
@@ -184,19 +279,28 @@ pub fn new_http<D: rpc_apis::Dependencies>(
         .parse()
         .map_err(|_| format!("Invalid {} listen host/port given: {}", id, url))?;
     let handler = setup_apis(conf.apis, deps, conf.max_batch_size);
-    let executor = deps.executor.clone();
+    let tls = deps.tls.clone().filter(|_| conf.tls);
+    let rate_limiter = match conf.rate_limit.http_max_req_per_sec {
+        0 => None,
+        max_req_per_sec => Some(HttpRateLimiter::new(max_req_per_sec)),
+    };
 
-    let cors_domains = into_domains(conf.cors);
-    let allowed_hosts = into_domains(collect_hosts(conf.hosts, &Some(url.clone().into())));
+    let cors_domains = into_list(conf.cors);
+    let allowed_hosts = into_list(collect_hosts(conf.hosts, &Some(url.clone().into())));
 
     let start_result = servers::start_http(
         &addr,
         cors_domains,
         allowed_hosts,
         handler,
-        executor,
-        rpc::RpcExtractor,
         conf.server_threads,
+        deps.health.clone(),
+        rate_limiter,
+        deps.api_keys.clone(),
+        conf.rate_limit.trusted_proxies,
+        tls,
+        conf.max_connections,
+        conf.idle_timeout,
     );
 
     match start_result {
@@ -211,10 +315,93 @@ pub fn new_http<D: rpc_apis::Dependencies>(
     }
 }
 
-fn into_domains<T: From<String>>(items: Option<Vec<String>>) -> DomainsValidation<T> {
-    items
-        .map(|vals| vals.into_iter().map(T::from).collect())
-        .into()
+pub fn new_ipc<D: rpc_apis::Dependencies>(
+    conf: IpcConfiguration,
+    deps: &Dependencies<D>,
+) -> Result<Option<IpcServer>, String> {
+    if !conf.enabled {
+        return Ok(None);
+    }
+
+    // IPC clients run on the same host as the gateway, so they are neither
+    // authenticated nor rate limited.
+    let handler = {
+        let mut handler = MetaIoHandler::with_middleware(Middleware::new(
+            deps.apis.activity_notifier(),
+            conf.max_batch_size,
+            deps.request_timeout,
+            None,
+            None,
+            None,
+        ));
+        let apis = conf.apis.list_apis();
+        deps.apis.extend_with_set(&mut handler, &apis);
+
+        handler
+    };
+
+    let path = Path::new(&conf.socket_addr);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| {
+            format!(
+                "Unable to create IPC directory at {}: {}",
+                dir.display(),
+                err
+            )
+        })?;
+    }
+    // Remove the socket of a previous instance, which would make binding fail.
+    match fs::symlink_metadata(path) {
+        Ok(ref metadata) if metadata.file_type().is_socket() => {
+            fs::remove_file(path).map_err(|err| {
+                format!("Unable to remove IPC socket at {}: {}", path.display(), err)
+            })?;
+        }
+        _ => (),
+    }
+
+    // Create the socket with the configured permissions, so that it is never
+    // accessible to other users, even before the server starts.
+    let server = {
+        let _umask = Umask::set(!conf.permissions & 0o777);
+        servers::start_ipc(&conf.socket_addr, handler, IpcExtractor)
+            .map_err(|err| format!("IPC error: {}", err))?
+    };
+    fs::set_permissions(path, fs::Permissions::from_mode(conf.permissions)).map_err(|err| {
+        format!(
+            "Unable to set permissions of IPC socket at {}: {}",
+            path.display(),
+            err
+        )
+    })?;
+
+    Ok(Some(server))
+}
+
+/// Process file mode creation mask, which is restored when dropped.
+struct Umask(libc::mode_t);
+
+impl Umask {
+    fn set(mask: u32) -> Self {
+        Umask(unsafe { libc::umask(mask as libc::mode_t) })
+    }
+}
+
+impl Drop for Umask {
+    fn drop(&mut self) {
+        unsafe { libc::umask(self.0) };
+    }
+}
+
+fn into_list<T: From<String>>(items: Option<Vec<String>>) -> Option<Vec<T>> {
+    items.map(|vals| vals.into_iter().map(T::from).collect())
+}
+
+fn is_addr_in_use(err: &ws::Error) -> bool {
+    match err.kind {
+        ws::ErrorKind::Io(ref err) => err.kind() == io::ErrorKind::AddrInUse,
+        _ => false,
+    }
 }
 
 fn collect_hosts(
@@ -248,6 +435,10 @@ where
     let mut handler = MetaIoHandler::with_middleware(Middleware::new(
         deps.apis.activity_notifier(),
         max_batch_size,
+        deps.request_timeout,
+        deps.quotas.clone(),
+        deps.api_keys.clone(),
+        None,
     ));
     let apis = apis.list_apis();
     deps.apis.extend_with_set(&mut handler, &apis);
@@ -257,7 +448,18 @@ where
 
 #[cfg(test)]
 mod tests {
-    use super::address;
+    use super::{address, AccessConfig};
+
+    #[test]
+    fn should_parse_access_list() {
+        assert_eq!(AccessConfig::parse_list("all"), None);
+        assert_eq!(AccessConfig::parse_list("*"), None);
+        assert_eq!(AccessConfig::parse_list("none"), Some(vec![]));
+        assert_eq!(
+            AccessConfig::parse_list("https://a.example, b.example:8545"),
+            Some(vec!["https://a.example".into(), "b.example:8545".into()])
+        );
+    }
 
     #[test]
     fn should_return_proper_address() {
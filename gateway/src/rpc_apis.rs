@@ -14,18 +14,27 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{cmp::PartialEq, collections::HashSet, str::FromStr, sync::Arc};
+use std::{
+    cmp::PartialEq,
+    collections::HashSet,
+    str::FromStr,
+    sync::{Arc, Weak},
+};
 
 use jsonrpc_core::{self as core, MetaIoHandler};
-use oasis_core_keymanager_client::KeyManagerClient;
-use parity_rpc::{informant::ActivityNotifier, Host, Metadata};
+use parity_rpc::{informant::ActivityNotifier, Host};
+use parking_lot::{Mutex, RwLock};
 
 use crate::{
+    auth::ApiKeys,
     impls::{
-        EthClient, EthFilterClient, EthPubSubClient, EthSigningClient, NetClient, OasisClient,
-        Web3Client,
+        eth_filter::Filters, eth_pubsub::ChainNotificationHandler, AdminClient, EthClient,
+        EthFilterClient, EthPubSubClient, EthSigningClient, NetClient, OasisClient, Web3Client,
     },
+    informant::RpcStats,
+    metadata::Metadata,
     pubsub::Broker,
+    ratelimit::WsClients,
     translator::Translator,
 };
 
@@ -41,6 +50,8 @@ pub enum Api {
     EthPubSub,
     /// Oasis (Safe)
     Oasis,
+    /// Admin (UNSAFE: Gateway operations)
+    Admin,
 }
 
 impl FromStr for Api {
@@ -55,11 +66,29 @@ impl FromStr for Api {
             "eth" => Ok(Eth),
             "pubsub" => Ok(EthPubSub),
             "oasis" => Ok(Oasis),
+            "admin" => Ok(Admin),
             api => Err(format!("Unknown api: {}", api)),
         }
     }
 }
 
+impl Api {
+    /// Returns the API a JSON-RPC method belongs to.
+    pub fn from_method(method: &str) -> Option<Api> {
+        use self::Api::*;
+
+        match method {
+            "eth_subscribe" | "eth_unsubscribe" => Some(EthPubSub),
+            method if method.starts_with("eth_") => Some(Eth),
+            method if method.starts_with("net_") => Some(Net),
+            method if method.starts_with("web3_") => Some(Web3),
+            method if method.starts_with("oasis_") => Some(Oasis),
+            method if method.starts_with("admin_") => Some(Admin),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ApiSet {
     // Used in tests.
@@ -147,8 +176,14 @@ pub trait Dependencies {
 pub struct FullDependencies {
     pub translator: Arc<Translator>,
     pub broker: Arc<Broker>,
-    pub km_client: Arc<dyn KeyManagerClient>,
     pub ws_address: Option<Host>,
+    pub stats: Arc<RpcStats>,
+    pub ws_clients: Arc<WsClients>,
+    pub api_keys: Option<Arc<ApiKeys>>,
+    /// Filters installed over any transport.
+    pub filters: Arc<Mutex<Filters>>,
+    /// Notification handlers of the pub/sub clients of all transports.
+    pub pubsub_handlers: Arc<RwLock<Vec<Weak<ChainNotificationHandler>>>>,
 }
 
 impl FullDependencies {
@@ -160,8 +195,8 @@ impl FullDependencies {
     ) where
         S: core::Middleware<Metadata>,
     {
-        use parity_rpc::v1::{Eth, EthFilter, EthPubSub, EthSigning, Net, Web3};
-        use traits::Oasis;
+        use parity_rpc::v1::{EthFilter, EthSigning, Net, Web3};
+        use traits::{Admin, EthCallMany, EthProof, EthPubSub, Oasis};
 
         for api in apis {
             match *api {
@@ -173,27 +208,45 @@ impl FullDependencies {
                 }
                 Api::Eth => {
                     let client = EthClient::new(self.translator.clone());
-                    handler.extend_with(client.to_delegate());
+                    handler.extend_with(client.eth_delegate());
+
+                    let call_many_client = EthClient::new(self.translator.clone());
+                    handler.extend_with(EthCallMany::to_delegate(call_many_client));
+
+                    let proof_client = EthClient::new(self.translator.clone());
+                    handler.extend_with(EthProof::to_delegate(proof_client));
 
                     let signing_client = EthSigningClient::new();
                     handler.extend_with(signing_client.to_delegate());
 
                     if !for_generic_pubsub {
-                        let filter_client = EthFilterClient::new(self.translator.clone());
+                        let filter_client =
+                            EthFilterClient::new(self.translator.clone(), self.filters.clone());
                         handler.extend_with(filter_client.to_delegate());
                     }
                 }
                 Api::EthPubSub => {
                     if !for_generic_pubsub {
-                        let pubsub_client = EthPubSubClient::new(self.translator.clone());
+                        let pubsub_client =
+                            EthPubSubClient::new(self.translator.clone(), self.ws_clients.clone());
                         self.broker.add_listener(pubsub_client.handler());
+                        self.pubsub_handlers.write().push(pubsub_client.handler());
                         handler.extend_with(pubsub_client.to_delegate());
                     }
                 }
                 Api::Oasis => {
+                    handler.extend_with(OasisClient::new(self.translator.clone()).to_delegate());
+                }
+                Api::Admin => {
                     handler.extend_with(
-                        OasisClient::new(self.translator.clone(), self.km_client.clone())
-                            .to_delegate(),
+                        AdminClient::new(
+                            self.stats.clone(),
+                            self.ws_clients.clone(),
+                            self.filters.clone(),
+                            self.pubsub_handlers.clone(),
+                            self.api_keys.clone(),
+                        )
+                        .to_delegate(),
                     );
                 }
             }
@@ -218,19 +271,26 @@ impl Dependencies for FullDependencies {
     }
 }
 
+/// APIs that are safe to expose on public endpoints.
+const PUBLIC_APIS: &[Api] = &[Api::Web3, Api::Net, Api::Eth, Api::EthPubSub, Api::Oasis];
+
+/// APIs exposing gateway internals, only enabled when explicitly listed or
+/// with `all`.
+const UNSAFE_APIS: &[Api] = &[Api::Admin];
+
 impl ApiSet {
     pub fn list_apis(&self) -> HashSet<Api> {
-        let public_list: HashSet<Api> = [Api::Web3, Api::Net, Api::Eth, Api::EthPubSub, Api::Oasis]
-            .iter()
-            .cloned()
-            .collect();
+        let mut public_list: HashSet<Api> = PUBLIC_APIS.iter().cloned().collect();
 
         match *self {
             ApiSet::List(ref apis) => apis.clone(),
             ApiSet::UnsafeContext => public_list,
             #[cfg(test)]
             ApiSet::SafeContext => public_list,
-            ApiSet::All => public_list,
+            ApiSet::All => {
+                public_list.extend(UNSAFE_APIS.iter().cloned());
+                public_list
+            }
         }
     }
 }
@@ -246,6 +306,7 @@ mod test {
         assert_eq!(Api::Eth, "eth".parse().unwrap());
         assert_eq!(Api::EthPubSub, "pubsub".parse().unwrap());
         assert_eq!(Api::Oasis, "oasis".parse().unwrap());
+        assert_eq!(Api::Admin, "admin".parse().unwrap());
         assert!("rp".parse::<Api>().is_err());
     }
 
@@ -297,9 +358,16 @@ mod test {
         assert_eq!(
             "all".parse::<ApiSet>().unwrap(),
             ApiSet::List(
-                vec![Api::Web3, Api::Net, Api::Eth, Api::EthPubSub, Api::Oasis,]
-                    .into_iter()
-                    .collect()
+                vec![
+                    Api::Web3,
+                    Api::Net,
+                    Api::Eth,
+                    Api::EthPubSub,
+                    Api::Oasis,
+                    Api::Admin,
+                ]
+                .into_iter()
+                .collect()
             )
         );
     }
@@ -315,4 +383,24 @@ mod test {
             )
         );
     }
+
+    #[test]
+    fn test_api_set_removal() {
+        assert_eq!(
+            "safe,-pubsub,-oasis".parse::<ApiSet>().unwrap(),
+            ApiSet::List(vec![Api::Web3, Api::Net, Api::Eth].into_iter().collect())
+        );
+        assert!("safe,-foo".parse::<ApiSet>().is_err());
+    }
+
+    #[test]
+    fn test_api_from_method() {
+        assert_eq!(Api::from_method("eth_call"), Some(Api::Eth));
+        assert_eq!(Api::from_method("eth_subscribe"), Some(Api::EthPubSub));
+        assert_eq!(Api::from_method("net_version"), Some(Api::Net));
+        assert_eq!(Api::from_method("web3_clientVersion"), Some(Api::Web3));
+        assert_eq!(Api::from_method("oasis_getProof"), Some(Api::Oasis));
+        assert_eq!(Api::from_method("admin_sessions"), Some(Api::Admin));
+        assert_eq!(Api::from_method("parity_chain"), None);
+    }
 }
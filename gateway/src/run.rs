@@ -15,87 +15,266 @@
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::{
+    net::IpAddr,
+    path::PathBuf,
     sync::{Arc, Weak},
     thread,
     time::{Duration, Instant},
 };
 
-use anyhow::{Error, Result};
+use anyhow::{ensure, Error, Result};
 use ethereum_types::U256;
+use futures::{future, Future};
 use informant;
-use oasis_core_keymanager_client::KeyManagerClient;
-use oasis_core_runtime::common::logger::get_logger;
-use rpc::{self, HttpConfiguration, WsConfiguration};
-use rpc_apis;
-use slog::{info, warn, Logger};
-
-use crate::{pubsub::Broker, translator::Translator, EthereumRuntimeClient};
-
-pub fn execute(
-    client: EthereumRuntimeClient,
-    km_client: Arc<dyn KeyManagerClient>,
-    pubsub_interval_secs: u64,
-    interface: &str,
-    http_port: u16,
-    num_threads: usize,
-    ws_port: u16,
-    ws_max_connections: usize,
-    ws_rate_limit: usize,
-    gas_price: U256,
-    jsonrpc_max_batch_size: usize,
-) -> Result<RunningGateway> {
+use oasis_core_runtime::common::namespace::Namespace;
+use parking_lot::Mutex;
+use rpc::{self, AccessConfig, HttpConfiguration, IpcConfiguration, WsConfiguration};
+use rpc_apis::{self, Api, ApiSet};
+use slog::{error, info, warn, Logger};
+
+use crate::{
+    auth::ApiKeys,
+    cache::CacheConfig,
+    health::HealthChecker,
+    impls::eth_filter::{FilterConfig, Filters},
+    logger::get_logger,
+    nodes::Nodes,
+    pubsub::Broker,
+    ratelimit::{QuotaConfig, Quotas, RateLimitConfig, WsClients, WsSessionConfig},
+    tls::{TlsAcceptor, TlsConfig},
+    translator::{SimulatorConfig, Translator},
+};
+
+/// Number of consecutive missed polls after which the pub/sub broker is
+/// considered unhealthy.
+const BROKER_MAX_MISSED_POLLS: u64 = 3;
+
+/// Interval between Oasis Core node health checks.
+const NODE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Interval between checks for idle WebSocket sessions.
+const WS_IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Gateway configuration.
+pub struct GatewayConfig {
+    /// Addresses of the Oasis Core nodes to fail over between.
+    pub node_addresses: Vec<String>,
+    /// Oasis Core runtime identifier of the runtime.
+    pub runtime_id: Namespace,
+    /// Interval between pub/sub notifications (in sec).
+    pub pubsub_interval_secs: u64,
+    /// Interface address of the HTTP and WebSocket servers.
+    pub interface: String,
+    pub http_port: u16,
+    /// Number of threads of the HTTP server.
+    pub num_threads: usize,
+    pub http_apis: ApiSet,
+    /// Max number of concurrent HTTP connections.
+    pub http_max_connections: usize,
+    /// Time allowed for a client to send a request on an idle HTTP
+    /// connection (0 to disable).
+    pub http_idle_timeout: Duration,
+    pub ws_port: u16,
+    pub ws_apis: ApiSet,
+    /// Max number of concurrent WebSocket connections, across all clients.
+    pub ws_max_connections: usize,
+    /// Max requests/second allowed on a single WebSocket connection.
+    pub ws_connection_rate_limit: usize,
+    pub ws_session_config: WsSessionConfig,
+    /// Path of the IPC socket, if the IPC server is enabled.
+    pub ipc_path: Option<PathBuf>,
+    pub ipc_apis: ApiSet,
+    pub ipc_permissions: u32,
+    /// Per-client rate limits.
+    pub rate_limit: RateLimitConfig,
+    pub quota_config: QuotaConfig,
+    /// Path of the API keys file, if API keys are required.
+    pub api_keys_path: Option<PathBuf>,
+    pub access: AccessConfig,
+    pub tls_config: Option<TlsConfig>,
+    pub gas_price: U256,
+    pub jsonrpc_max_batch_size: usize,
+    pub rpc_timeout: Duration,
+    pub simulator_config: SimulatorConfig,
+    pub cache_config: CacheConfig,
+    pub filter_config: FilterConfig,
+    /// Max age of the latest block for the gateway to be reported as ready.
+    pub max_block_age: Duration,
+}
+
+pub fn execute(nodes: Nodes, config: GatewayConfig) -> Result<RunningGateway> {
+    let GatewayConfig {
+        node_addresses: _,
+        runtime_id: _,
+        pubsub_interval_secs,
+        interface,
+        http_port,
+        num_threads,
+        http_apis,
+        http_max_connections,
+        http_idle_timeout,
+        ws_port,
+        ws_apis,
+        ws_max_connections,
+        ws_connection_rate_limit,
+        ws_session_config,
+        ipc_path,
+        ipc_apis,
+        ipc_permissions,
+        rate_limit,
+        quota_config,
+        api_keys_path,
+        access,
+        tls_config,
+        gas_price,
+        jsonrpc_max_batch_size,
+        rpc_timeout,
+        simulator_config,
+        cache_config,
+        filter_config,
+        max_block_age,
+    } = config;
     let logger = get_logger("gateway/execute");
 
     let mut runtime = tokio::runtime::Runtime::new()?;
 
-    // Wait for the Oasis Core node to be fully synced.
-    info!(logger, "Waiting for the Oasis Core node to be fully synced");
-    runtime.block_on(client.txn_client().wait_sync())?;
+    // Wait for an Oasis Core node to be fully synced.
+    info!(logger, "Waiting for an Oasis Core node to be fully synced");
+    let (synced, _) = runtime.block_on(future::select_ok(nodes.all().iter().map(|node| {
+        let node = node.clone();
+        node.client
+            .txn_client()
+            .wait_sync()
+            .map(move |()| node.address.clone())
+    })))?;
     info!(
         logger,
-        "Oasis Core node is fully synced, proceeding with initialization"
+        "Oasis Core node is fully synced, proceeding with initialization";
+        "node" => synced
     );
 
-    let translator = Arc::new(Translator::new(client, gas_price));
+    let nodes = Arc::new(nodes);
+    runtime.spawn(nodes.clone().start_health_checks(NODE_CHECK_INTERVAL));
+
+    let translator = Arc::new(Translator::new(
+        nodes.clone(),
+        gas_price,
+        simulator_config,
+        cache_config,
+    ));
     let broker = Arc::new(Broker::new(translator.clone()));
     runtime.spawn(broker.start(Duration::new(pubsub_interval_secs, 0)));
 
+    let health = Arc::new(HealthChecker::new(
+        translator.clone(),
+        broker.clone(),
+        max_block_age,
+        Duration::new(BROKER_MAX_MISSED_POLLS * pubsub_interval_secs, 0),
+    ));
+
     let rpc_stats = Arc::new(informant::RpcStats::default());
 
-    // expose the http and ws servers to the world
-    // conf corresponds to parity command-line options "--unsafe-expose" + "--jsonrpc-cors=all"
+    let api_keys = match api_keys_path {
+        Some(path) => {
+            let api_keys = ApiKeys::load(&path)?;
+            info!(logger, "Loaded API keys"; "count" => api_keys.count());
+            Some(Arc::new(api_keys))
+        }
+        None => None,
+    };
+
+    let tls = match tls_config {
+        Some(tls_config) => {
+            if rate_limit.trusted_proxies > 0 {
+                warn!(
+                    logger,
+                    "Clients can set X-Forwarded-For themselves when TLS is terminated by the gateway"
+                );
+            }
+            Some(Arc::new(TlsAcceptor::new(tls_config)?))
+        }
+        None => None,
+    };
+
+    // The admin API is only served over IPC and on local interfaces.
+    let local = interface == "localhost"
+        || interface
+            .parse::<IpAddr>()
+            .map_or(false, |ip| ip.is_loopback());
+    ensure!(
+        local
+            || !(http_apis.list_apis().contains(&Api::Admin)
+                || ws_apis.list_apis().contains(&Api::Admin)),
+        "The admin API is only available over IPC or on a loopback interface"
+    );
+
+    let ws_idle_timeout = ws_session_config.idle_timeout;
+    let ws_clients = Arc::new(WsClients::new(&rate_limit, ws_session_config));
+    if ws_idle_timeout != Duration::from_secs(0) {
+        runtime.spawn(ws_clients.clone().start_idle_checks(WS_IDLE_CHECK_INTERVAL));
+    }
+
     let mut ws_conf = WsConfiguration::default();
-    ws_conf.origins = None;
-    ws_conf.hosts = None;
-    ws_conf.interface = interface.into();
+    ws_conf.origins = access.ws_origins;
+    ws_conf.hosts = access.ws_hosts;
+    ws_conf.interface = interface.clone();
     ws_conf.port = ws_port;
+    ws_conf.apis = ws_apis;
     ws_conf.max_batch_size = jsonrpc_max_batch_size;
-    ws_conf.max_req_per_sec = ws_rate_limit;
+    ws_conf.max_req_per_sec = ws_connection_rate_limit;
+    ws_conf.tls = tls.is_some();
 
     // max # of concurrent connections. the default is 100, which is "low" and "should be increased":
     // https://github.com/tomusdrw/ws-rs/blob/f12d19c4c19422fc79af28a3181f598bc07ecd1e/src/lib.rs#L128
     ws_conf.max_connections = ws_max_connections;
 
     let mut http_conf = HttpConfiguration::default();
-    http_conf.cors = None;
-    http_conf.hosts = None;
-    http_conf.interface = interface.into();
+    http_conf.cors = access.http_cors;
+    http_conf.hosts = access.http_hosts;
+    http_conf.interface = interface;
     http_conf.port = http_port;
+    http_conf.apis = http_apis;
     http_conf.server_threads = num_threads;
     http_conf.max_batch_size = jsonrpc_max_batch_size;
+    http_conf.rate_limit = rate_limit;
+    http_conf.max_connections = http_max_connections;
+    http_conf.idle_timeout = http_idle_timeout;
+    http_conf.tls = tls.is_some();
+
+    let mut ipc_conf = IpcConfiguration::default();
+    if let Some(ipc_path) = ipc_path {
+        ipc_conf.enabled = true;
+        ipc_conf.socket_addr = ipc_path.to_string_lossy().into_owned();
+    }
+    ipc_conf.apis = ipc_apis;
+    ipc_conf.permissions = ipc_permissions;
+    ipc_conf.max_batch_size = jsonrpc_max_batch_size;
 
     // Define RPC handlers.
     let deps_for_rpc_apis = Arc::new(rpc_apis::FullDependencies {
         translator: translator.clone(),
         broker: broker.clone(),
-        km_client: km_client.clone(),
         ws_address: ws_conf.address(),
+        stats: rpc_stats.clone(),
+        ws_clients: ws_clients.clone(),
+        api_keys: api_keys.clone(),
+        filters: Arc::new(Mutex::new(Filters::new(filter_config))),
+        pubsub_handlers: Default::default(),
     });
 
     let dependencies = rpc::Dependencies {
         apis: deps_for_rpc_apis.clone(),
         executor: runtime.executor(),
         stats: rpc_stats.clone(),
+        health,
+        quotas: match quota_config.budget {
+            0 => None,
+            _ => Some(Arc::new(Quotas::new(quota_config))),
+        },
+        api_keys: api_keys.clone(),
+        ws_clients,
+        tls: tls.clone(),
+        request_timeout: rpc_timeout,
     };
 
     // Start RPC servers.
@@ -106,13 +285,23 @@ pub fn execute(
     let http_server =
         rpc::new_http("HTTP JSON-RPC", "jsonrpc", http_conf, &dependencies).map_err(Error::msg)?;
 
+    let ipc_server = if ipc_conf.enabled {
+        info!(logger, "Starting IPC server"; "conf" => ?ipc_conf);
+        rpc::new_ipc(ipc_conf, &dependencies).map_err(Error::msg)?
+    } else {
+        None
+    };
+
     let running_client = RunningGateway {
         logger,
         runtime,
         translator,
-        km_client,
+        nodes,
+        api_keys,
+        tls,
         http_server,
         ws_server,
+        ipc_server,
     };
     Ok(running_client)
 }
@@ -125,21 +314,44 @@ pub struct RunningGateway {
     logger: Logger,
     runtime: tokio::runtime::Runtime,
     translator: Arc<Translator>,
-    km_client: Arc<dyn KeyManagerClient>,
-    http_server: Option<jsonrpc_http_server::Server>,
-    ws_server: Option<jsonrpc_ws_server::Server>,
+    nodes: Arc<Nodes>,
+    api_keys: Option<Arc<ApiKeys>>,
+    tls: Option<Arc<TlsAcceptor>>,
+    http_server: Option<rpc::HttpServer>,
+    ws_server: Option<rpc::WsServer>,
+    ipc_server: Option<jsonrpc_ipc_server::Server>,
 }
 
 impl RunningGateway {
+    /// Reloads the gateway configuration that may change at runtime.
+    pub fn reload(&self) {
+        if let Some(ref api_keys) = self.api_keys {
+            match api_keys.reload() {
+                Ok(()) => info!(self.logger, "Reloaded API keys"; "count" => api_keys.count()),
+                Err(err) => error!(self.logger, "Failed to reload API keys"; "err" => ?err),
+            }
+        }
+
+        if let Some(ref tls) = self.tls {
+            match tls.reload() {
+                Ok(()) => info!(self.logger, "Reloaded TLS certificates"),
+                Err(err) => error!(self.logger, "Failed to reload TLS certificates"; "err" => ?err),
+            }
+        }
+    }
+
     /// Shuts down the gateway.
     pub fn shutdown(self) {
         let RunningGateway {
             logger,
             runtime,
             translator,
-            km_client,
+            nodes,
+            api_keys: _,
+            tls: _,
             http_server,
             ws_server,
+            ipc_server,
         } = self;
 
         info!(logger, "Terminating event loop");
@@ -151,8 +363,9 @@ impl RunningGateway {
         drop(runtime.shutdown_now());
         drop(http_server);
         drop(ws_server);
+        drop(ipc_server);
         drop(translator);
-        drop(km_client);
+        drop(nodes);
 
         wait_for_drop(logger, weak_translator);
     }
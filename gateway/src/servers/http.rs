@@ -0,0 +1,949 @@
+//! HTTP JSON-RPC server.
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::{future, Async, Future, Poll, Stream};
+use hyper::{
+    self,
+    header::{self, AsHeaderName, HeaderValue},
+    server::conn::Http,
+    service::service_fn,
+    Body, Method, Request, Response, StatusCode,
+};
+use jsonrpc_core::{MetaIoHandler, Middleware};
+use jsonrpc_server_utils::{
+    cors::{self, AccessControlAllowOrigin, AllowCors},
+    hosts::{self, Host},
+};
+use parity_rpc::Origin;
+use serde_json;
+use slog::{debug, error, Logger};
+use tokio::{
+    self,
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream},
+    runtime::{Builder as RuntimeBuilder, Runtime},
+    timer::Delay,
+};
+
+use crate::{
+    auth::{ApiKeys, API_KEY_HEADER},
+    health::{HealthChecker, Readiness},
+    logger::get_logger,
+    metadata::Metadata,
+    middleware::{self, HttpRateLimiter},
+    ratelimit::{self, ClientInfo},
+    tls::TlsAcceptor,
+};
+
+use super::HANDSHAKE_TIMEOUT;
+
+/// Max size of a request body.
+const MAX_REQUEST_BODY_SIZE: usize = 5 * 1024 * 1024;
+
+type ResponseFuture = Box<dyn Future<Item = Response<Body>, Error = hyper::Error> + Send>;
+
+/// HTTP server, which stops when dropped.
+pub struct HttpServer {
+    local_addr: SocketAddr,
+    _runtime: Runtime,
+}
+
+impl HttpServer {
+    /// Returns the address the server listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+struct HttpHandler<S: Middleware<Metadata>> {
+    logger: Logger,
+    handler: MetaIoHandler<Metadata, S>,
+    cors_domains: Option<Vec<AccessControlAllowOrigin>>,
+    allowed_hosts: Option<Vec<Host>>,
+    health: Arc<HealthChecker>,
+    rate_limiter: Option<HttpRateLimiter>,
+    api_keys: Option<Arc<ApiKeys>>,
+    trusted_proxies: usize,
+    tls: Option<Arc<TlsAcceptor>>,
+    connections: Arc<AtomicUsize>,
+    max_connections: usize,
+    idle_timeout: Duration,
+}
+
+/// Slot of an open connection, released when dropped.
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    /// Take a slot, unless `max_connections` connections are open.
+    fn acquire(connections: &Arc<AtomicUsize>, max_connections: usize) -> Option<Self> {
+        let mut open = connections.load(Ordering::SeqCst);
+        loop {
+            if open >= max_connections {
+                return None;
+            }
+            match connections.compare_exchange(open, open + 1, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => return Some(ConnectionSlot(connections.clone())),
+                Err(current) => open = current,
+            }
+        }
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Requests of a connection.
+#[derive(Default)]
+struct ConnectionRequests {
+    /// Number of requests whose headers were received.
+    started: AtomicUsize,
+    /// Number of requests being processed.
+    pending: AtomicUsize,
+}
+
+/// A request being processed, until dropped.
+struct PendingRequest(Arc<ConnectionRequests>);
+
+impl PendingRequest {
+    fn start(requests: &Arc<ConnectionRequests>) -> Self {
+        requests.started.fetch_add(1, Ordering::SeqCst);
+        requests.pending.fetch_add(1, Ordering::SeqCst);
+        PendingRequest(requests.clone())
+    }
+}
+
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        self.0.pending.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// I/O of a connection, which fails once the client did not send the
+/// headers of a request within `timeout` of the connection being opened or
+/// of the last response, so that idle and slow clients do not hold
+/// connections.
+struct IdleTimeout<I> {
+    io: I,
+    requests: Arc<ConnectionRequests>,
+    timeout: Duration,
+    started: usize,
+    deadline: Option<Delay>,
+}
+
+impl<I> IdleTimeout<I> {
+    fn new(io: I, requests: Arc<ConnectionRequests>, timeout: Duration) -> Self {
+        Self {
+            io,
+            requests,
+            timeout,
+            started: 0,
+            deadline: None,
+        }
+    }
+
+    /// Fail if the connection is idle past its deadline.
+    fn check_idle(&mut self) -> io::Result<()> {
+        if self.timeout == Duration::from_secs(0) {
+            return Ok(());
+        }
+
+        // The deadline restarts once the requests started since it was set
+        // are processed.
+        let started = self.requests.started.load(Ordering::SeqCst);
+        let pending = self.requests.pending.load(Ordering::SeqCst);
+        if pending > 0 || started != self.started {
+            self.started = started;
+            self.deadline = None;
+        }
+        if pending > 0 {
+            return Ok(());
+        }
+
+        let timeout = self.timeout;
+        let deadline = self
+            .deadline
+            .get_or_insert_with(|| Delay::new(Instant::now() + timeout));
+        match deadline.poll() {
+            Ok(Async::NotReady) => Ok(()),
+            Ok(Async::Ready(())) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "connection is idle",
+            )),
+            Err(err) => Err(io::Error::new(io::ErrorKind::Other, err)),
+        }
+    }
+}
+
+impl<I: io::Read> io::Read for IdleTimeout<I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.check_idle()?;
+        self.io.read(buf)
+    }
+}
+
+impl<I: io::Write> io::Write for IdleTimeout<I> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.io.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+impl<I: AsyncRead> AsyncRead for IdleTimeout<I> {}
+
+impl<I: AsyncWrite> AsyncWrite for IdleTimeout<I> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.io.shutdown()
+    }
+}
+
+/// Start an HTTP server on `addr`, serving requests on `threads` threads,
+/// over TLS if `tls` is set.
+///
+/// `None` allows all CORS domains or hosts. Clients are identified by the
+/// peer address of their connection, or by the `X-Forwarded-For` header of
+/// `trusted_proxies` reverse proxies. Up to `max_connections` connections are
+/// served at once, and connections on which no request is sent within
+/// `idle_timeout` are closed (0 to disable).
+pub fn start_http<S: Middleware<Metadata>>(
+    addr: &SocketAddr,
+    cors_domains: Option<Vec<AccessControlAllowOrigin>>,
+    allowed_hosts: Option<Vec<Host>>,
+    handler: MetaIoHandler<Metadata, S>,
+    threads: usize,
+    health: Arc<HealthChecker>,
+    rate_limiter: Option<HttpRateLimiter>,
+    api_keys: Option<Arc<ApiKeys>>,
+    trusted_proxies: usize,
+    tls: Option<Arc<TlsAcceptor>>,
+    max_connections: usize,
+    idle_timeout: Duration,
+) -> io::Result<HttpServer> {
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    let mut runtime = RuntimeBuilder::new()
+        .core_threads(threads.max(1))
+        .name_prefix("http-server-")
+        .build()?;
+
+    let server = Arc::new(HttpHandler {
+        logger: get_logger("gateway/servers/http"),
+        handler,
+        cors_domains,
+        allowed_hosts,
+        health,
+        rate_limiter,
+        api_keys,
+        trusted_proxies,
+        tls,
+        connections: Default::default(),
+        max_connections,
+        idle_timeout,
+    });
+    runtime.spawn(
+        listener
+            .incoming()
+            .then(Ok::<_, ()>)
+            .for_each(move |stream| {
+                match stream {
+                    Ok(stream) => accept(&server, stream),
+                    Err(err) => {
+                        error!(server.logger, "Failed to accept HTTP connection"; "err" => ?err)
+                    }
+                }
+                Ok(())
+            }),
+    );
+
+    Ok(HttpServer {
+        local_addr,
+        _runtime: runtime,
+    })
+}
+
+fn accept<S: Middleware<Metadata>>(server: &Arc<HttpHandler<S>>, stream: TcpStream) {
+    // Dropping the stream closes the connection.
+    let slot = match ConnectionSlot::acquire(&server.connections, server.max_connections) {
+        Some(slot) => slot,
+        None => {
+            debug!(
+                server.logger,
+                "Rejecting HTTP connection, too many connections are open"
+            );
+            return;
+        }
+    };
+    let peer = match stream.peer_addr() {
+        Ok(peer) => peer,
+        Err(err) => {
+            debug!(server.logger, "HTTP connection closed before it was served"; "err" => ?err);
+            return;
+        }
+    };
+    let _ = stream.set_nodelay(true);
+
+    match server.tls {
+        Some(ref tls) => {
+            let server = server.clone();
+            tokio::spawn(tls.accept(stream, HANDSHAKE_TIMEOUT).then(move |stream| {
+                match stream {
+                    Ok(stream) => serve(&server, stream, peer, slot),
+                    Err(err) => debug!(server.logger, "TLS connection failed"; "err" => ?err),
+                }
+                Ok(())
+            }));
+        }
+        None => serve(server, stream, peer, slot),
+    }
+}
+
+/// Serve the HTTP connection of `peer`, releasing its slot once it is closed.
+fn serve<S, I>(server: &Arc<HttpHandler<S>>, io: I, peer: SocketAddr, slot: ConnectionSlot)
+where
+    S: Middleware<Metadata>,
+    I: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let logger = server.logger.clone();
+    let requests = Arc::new(ConnectionRequests::default());
+    let io = IdleTimeout::new(io, requests.clone(), server.idle_timeout);
+    let server = server.clone();
+
+    tokio::spawn(
+        Http::new()
+            .serve_connection(
+                io,
+                service_fn(move |request| {
+                    let pending = PendingRequest::start(&requests);
+                    handle(server.clone(), request, peer).then(move |response| {
+                        drop(pending);
+                        response
+                    })
+                }),
+            )
+            .then(move |result| {
+                drop(slot);
+                if let Err(err) = result {
+                    debug!(logger, "HTTP connection failed"; "err" => ?err);
+                }
+                Ok(())
+            }),
+    );
+}
+
+/// Returns the value of a request header, if it is valid.
+fn header_value<K: AsHeaderName>(request: &Request<Body>, name: K) -> Option<&str> {
+    request
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+}
+
+fn handle<S: Middleware<Metadata>>(
+    server: Arc<HttpHandler<S>>,
+    request: Request<Body>,
+    peer: SocketAddr,
+) -> ResponseFuture {
+    let host = header_value(&request, header::HOST);
+    if !hosts::is_host_valid(host, &server.allowed_hosts) {
+        return respond(text_response(
+            StatusCode::FORBIDDEN,
+            "Provided Host header is not whitelisted.",
+        ));
+    }
+
+    // If the requested url is /status or /health/live, terminate with 200 OK
+    // response. If it is /health/ready, respond with the result of the
+    // readiness checks.
+    let path = request.uri().path();
+    if path == "/status" || path == "/health/live" {
+        return respond(Response::new(Body::empty()));
+    } else if path == "/health/ready" {
+        return readiness_response(&server.health);
+    }
+
+    let allow_origin = match cors::get_cors_allow_origin(
+        header_value(&request, header::ORIGIN),
+        host,
+        &server.cors_domains,
+    ) {
+        AllowCors::Invalid => {
+            return respond(text_response(
+                StatusCode::FORBIDDEN,
+                "Origin of the request is not whitelisted. CORS headers would not be sent and \
+                 any side-effects were cancelled as well.",
+            ));
+        }
+        AllowCors::NotRequired => None,
+        AllowCors::Ok(origin) => HeaderValue::from_str(&origin.to_string()).ok(),
+    };
+
+    Box::new(process(server, request, peer).map(move |mut response| {
+        if let Some(origin) = allow_origin {
+            let headers = response.headers_mut();
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+            headers.insert(header::VARY, HeaderValue::from_static("origin"));
+        }
+        response
+    }))
+}
+
+/// Process a JSON-RPC request, unless it is not authenticated or the client
+/// exceeded its rate limit.
+fn process<S: Middleware<Metadata>>(
+    server: Arc<HttpHandler<S>>,
+    request: Request<Body>,
+    peer: SocketAddr,
+) -> ResponseFuture {
+    match *request.method() {
+        Method::POST => (),
+        Method::OPTIONS => return respond(preflight_response()),
+        _ => {
+            return respond(text_response(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Used HTTP Method is not allowed. POST or OPTIONS is required.",
+            ));
+        }
+    }
+
+    let is_json = header_value(&request, header::CONTENT_TYPE).map_or(false, |content_type| {
+        content_type.split(';').next().map_or(false, |mime| {
+            mime.trim().eq_ignore_ascii_case("application/json")
+        })
+    });
+    if !is_json {
+        return respond(text_response(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Supplied content type is not allowed. Content-Type: application/json is required.",
+        ));
+    }
+
+    let key = match server.api_keys {
+        Some(ref api_keys) => match middleware::http_authenticate(api_keys, &request) {
+            Ok(key) => Some(key),
+            Err(response) => return respond(response),
+        },
+        None => None,
+    };
+
+    let ip = ratelimit::client_ip(
+        peer.ip(),
+        header_value(&request, "x-forwarded-for"),
+        server.trusted_proxies,
+    );
+    if let Some(response) = server
+        .rate_limiter
+        .as_ref()
+        .and_then(|rate_limiter| rate_limiter.check(ip))
+    {
+        return respond(response);
+    }
+
+    let metadata = Metadata {
+        origin: Origin::Rpc(format!(
+            "{} / {}",
+            header_value(&request, header::ORIGIN).unwrap_or("unknown origin"),
+            header_value(&request, header::USER_AGENT).unwrap_or("unknown agent")
+        )),
+        client: Some(ClientInfo { ip: Some(ip), key }),
+        session: None,
+    };
+
+    Box::new(
+        read_body(request.into_body()).and_then(move |body| -> ResponseFuture {
+            let body = match body {
+                Some(body) => body,
+                None => {
+                    return respond(text_response(
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        "Request body is too large.",
+                    ));
+                }
+            };
+
+            Box::new(
+                server
+                    .handler
+                    .handle_request(&String::from_utf8_lossy(&body), metadata)
+                    .then(|response| {
+                        Ok(match response {
+                            Ok(response) => json_response(response.unwrap_or_default()),
+                            Err(()) => text_response(
+                                StatusCode::INTERNAL_SERVER_ERROR,
+                                "Failed to process the request.",
+                            ),
+                        })
+                    }),
+            )
+        }),
+    )
+}
+
+/// Read a request body, unless it is larger than the max size.
+fn read_body(body: Body) -> impl Future<Item = Option<Vec<u8>>, Error = hyper::Error> {
+    body.fold(Some(Vec::new()), |body, chunk| -> Result<_, hyper::Error> {
+        Ok(body.and_then(|mut body| {
+            if body.len() + chunk.len() > MAX_REQUEST_BODY_SIZE {
+                return None;
+            }
+            body.extend_from_slice(&chunk);
+            Some(body)
+        }))
+    })
+}
+
+fn respond(response: Response<Body>) -> ResponseFuture {
+    Box::new(future::ok(response))
+}
+
+fn json_response(body: String) -> Response<Body> {
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .expect("response must be valid")
+}
+
+fn text_response(status: StatusCode, message: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(Body::from(format!("{}\n", message)))
+        .expect("response must be valid")
+}
+
+/// Respond to a CORS preflight request.
+fn preflight_response() -> Response<Body> {
+    Response::builder()
+        .header(header::ACCESS_CONTROL_ALLOW_METHODS, "OPTIONS, POST")
+        .header(
+            header::ACCESS_CONTROL_ALLOW_HEADERS,
+            format!(
+                "Origin, Content-Type, Accept, User-Agent, {}",
+                API_KEY_HEADER
+            ),
+        )
+        .body(Body::empty())
+        .expect("response must be valid")
+}
+
+/// Respond with the readiness report, with status 503 if the gateway is not ready.
+fn readiness_response(health: &HealthChecker) -> ResponseFuture {
+    Box::new(
+        health
+            .readiness()
+            .then(|readiness| Ok(readiness_report_response(readiness))),
+    )
+}
+
+fn readiness_report_response(readiness: Result<Readiness, ()>) -> Response<Body> {
+    let response = match readiness {
+        Ok(readiness) => Response::builder()
+            .status(if readiness.ready {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            })
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                serde_json::to_string(&readiness).expect("readiness serialization cannot fail"),
+            )),
+        Err(()) => Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::empty()),
+    };
+
+    response.expect("response must be valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        io::{Read, Write},
+        net, thread,
+    };
+
+    use grpcio::Environment;
+    use jsonrpc_core::NoopMiddleware;
+    use oasis_core_runtime::common::namespace::Namespace;
+    use parity_rpc::v1::types::U256;
+    use serde_json::Value;
+
+    use crate::{
+        health::{BlockCheck, Check},
+        nodes::{NodeClient, Nodes},
+        pubsub::Broker,
+        translator::Translator,
+    };
+
+    /// Options of a test server.
+    struct TestOptions {
+        cors_domains: Option<Vec<AccessControlAllowOrigin>>,
+        allowed_hosts: Option<Vec<Host>>,
+        rate_limiter: Option<HttpRateLimiter>,
+        api_keys: Option<Arc<ApiKeys>>,
+        trusted_proxies: usize,
+        max_connections: usize,
+        idle_timeout: Duration,
+    }
+
+    impl Default for TestOptions {
+        fn default() -> Self {
+            Self {
+                cors_domains: None,
+                allowed_hosts: None,
+                rate_limiter: None,
+                api_keys: None,
+                trusted_proxies: 0,
+                max_connections: 100,
+                idle_timeout: Duration::from_secs(60),
+            }
+        }
+    }
+
+    fn io_handler() -> MetaIoHandler<Metadata, NoopMiddleware> {
+        let mut handler = MetaIoHandler::default();
+        handler.add_method("web3_clientVersion", |_| {
+            Ok(Value::String("test".to_owned()))
+        });
+        handler
+    }
+
+    /// Health checker of a node which is never reached.
+    fn health() -> Arc<HealthChecker> {
+        let env = Arc::new(Environment::new(1));
+        let node = NodeClient::connect(env, "127.0.0.1:1", Namespace::default());
+        let nodes = Arc::new(Nodes::new(vec![node]).unwrap());
+        let translator = Arc::new(Translator::new(
+            nodes,
+            U256::zero(),
+            Default::default(),
+            Default::default(),
+        ));
+        let broker = Arc::new(Broker::new(translator.clone()));
+        Arc::new(HealthChecker::new(
+            translator,
+            broker,
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        ))
+    }
+
+    fn start(options: TestOptions) -> HttpServer {
+        start_http(
+            &"127.0.0.1:0".parse().unwrap(),
+            options.cors_domains,
+            options.allowed_hosts,
+            io_handler(),
+            1,
+            health(),
+            options.rate_limiter,
+            options.api_keys,
+            options.trusted_proxies,
+            None,
+            options.max_connections,
+            options.idle_timeout,
+        )
+        .unwrap()
+    }
+
+    fn connect(server: &HttpServer) -> net::TcpStream {
+        let stream = net::TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+    }
+
+    /// Send a request with `headers` on `stream`, and return the status and
+    /// the headers of the response.
+    fn send(
+        mut stream: net::TcpStream,
+        method: &str,
+        headers: &[&str],
+        body: &str,
+    ) -> (u16, String) {
+        let mut request = format!(
+            "{} / HTTP/1.1\r\nConnection: close\r\nContent-Length: {}\r\n",
+            method,
+            body.len()
+        );
+        for header in headers {
+            request.push_str(header);
+            request.push_str("\r\n");
+        }
+        request.push_str("\r\n");
+        request.push_str(body);
+        stream.write_all(request.as_bytes()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let headers = response.split("\r\n\r\n").next().unwrap().to_lowercase();
+        (status, headers)
+    }
+
+    const RPC_REQUEST: &str = r#"{"jsonrpc":"2.0","id":1,"method":"web3_clientVersion"}"#;
+
+    /// Send a JSON-RPC request with `headers`.
+    fn rpc(server: &HttpServer, headers: &[&str]) -> (u16, String) {
+        let mut headers = headers.to_vec();
+        headers.push("Host: localhost");
+        headers.push("Content-Type: application/json");
+        send(connect(server), "POST", &headers, RPC_REQUEST)
+    }
+
+    /// Returns true if the server closed the connection without a response.
+    fn is_closed(mut stream: net::TcpStream) -> bool {
+        let mut buf = [0; 1];
+        match stream.read(&mut buf) {
+            Ok(n) => n == 0,
+            Err(err) => err.kind() == io::ErrorKind::ConnectionReset,
+        }
+    }
+
+    fn readiness(block_age: u64) -> Readiness {
+        let latest_block = BlockCheck {
+            ok: block_age <= 60,
+            number: Some(10),
+            age: Some(block_age),
+            max_age: 60,
+        };
+        Readiness {
+            ready: latest_block.ok,
+            node: Check {
+                ok: true,
+                error: None,
+            },
+            latest_block,
+            broker: Check {
+                ok: true,
+                error: None,
+            },
+            key_manager: Check {
+                ok: true,
+                error: None,
+            },
+        }
+    }
+
+    fn body_json(response: Response<Body>) -> Value {
+        let body = response.into_body().concat2().wait().unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[test]
+    fn should_respond_ok_when_ready() {
+        let response = readiness_report_response(Ok(readiness(5)));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+
+        let body = body_json(response);
+        assert_eq!(body["ready"], true);
+        assert_eq!(body["latest_block"]["number"], 10);
+        assert_eq!(body["latest_block"]["age"], 5);
+    }
+
+    #[test]
+    fn should_respond_unavailable_when_not_ready() {
+        let response = readiness_report_response(Ok(readiness(120)));
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body = body_json(response);
+        assert_eq!(body["ready"], false);
+        assert_eq!(body["latest_block"]["ok"], false);
+        assert_eq!(body["latest_block"]["max_age"], 60);
+        assert!(body["node"].get("error").is_none());
+    }
+
+    #[test]
+    fn should_respond_error_when_checks_fail() {
+        let response = readiness_report_response(Err(()));
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn should_serve_requests() {
+        let server = start(TestOptions::default());
+        let (status, headers) = rpc(&server, &[]);
+        assert_eq!(status, 200);
+        assert!(headers.contains("content-type: application/json"));
+    }
+
+    #[test]
+    fn should_reject_hosts_not_allowed() {
+        let server = start(TestOptions {
+            allowed_hosts: Some(vec!["localhost".to_owned().into()]),
+            ..Default::default()
+        });
+        assert_eq!(rpc(&server, &[]).0, 200);
+
+        let headers = ["Host: example.com", "Content-Type: application/json"];
+        let (status, _) = send(connect(&server), "POST", &headers, RPC_REQUEST);
+        assert_eq!(status, 403);
+    }
+
+    #[test]
+    fn should_reject_origins_not_allowed() {
+        let server = start(TestOptions {
+            cors_domains: Some(vec!["https://app.example.com".to_owned().into()]),
+            ..Default::default()
+        });
+
+        let (status, _) = rpc(&server, &["Origin: https://evil.example.com"]);
+        assert_eq!(status, 403);
+
+        let (status, headers) = rpc(&server, &["Origin: https://app.example.com"]);
+        assert_eq!(status, 200);
+        assert!(headers.contains("access-control-allow-origin: https://app.example.com"));
+        assert!(headers.contains("vary: origin"));
+    }
+
+    #[test]
+    fn should_reject_invalid_requests() {
+        let server = start(TestOptions::default());
+
+        let (status, _) = send(connect(&server), "GET", &["Host: localhost"], "");
+        assert_eq!(status, 405);
+
+        let headers = ["Host: localhost", "Content-Type: text/plain"];
+        let (status, _) = send(connect(&server), "POST", &headers, RPC_REQUEST);
+        assert_eq!(status, 415);
+    }
+
+    #[test]
+    fn should_reject_requests_without_valid_api_key() {
+        let server = start(TestOptions {
+            api_keys: Some(Arc::new(
+                ApiKeys::from_json(r#"[{"name": "test", "key": "secret"}]"#).unwrap(),
+            )),
+            ..Default::default()
+        });
+
+        assert_eq!(rpc(&server, &[]).0, 401);
+        assert_eq!(rpc(&server, &["X-Api-Key: invalid"]).0, 401);
+        assert_eq!(rpc(&server, &["X-Api-Key: secret"]).0, 200);
+    }
+
+    #[test]
+    fn should_rate_limit_clients() {
+        let server = start(TestOptions {
+            rate_limiter: Some(HttpRateLimiter::new(1)),
+            ..Default::default()
+        });
+
+        assert_eq!(rpc(&server, &[]).0, 200);
+        assert_eq!(rpc(&server, &[]).0, 429);
+    }
+
+    #[test]
+    fn should_rate_limit_forwarded_clients_separately() {
+        let server = start(TestOptions {
+            rate_limiter: Some(HttpRateLimiter::new(1)),
+            trusted_proxies: 1,
+            ..Default::default()
+        });
+
+        assert_eq!(rpc(&server, &["X-Forwarded-For: 10.0.0.1"]).0, 200);
+        assert_eq!(rpc(&server, &["X-Forwarded-For: 10.0.0.1"]).0, 429);
+        // Addresses set by the client before the trusted proxy are ignored.
+        assert_eq!(
+            rpc(&server, &["X-Forwarded-For: 10.0.0.3, 10.0.0.1"]).0,
+            429
+        );
+        assert_eq!(rpc(&server, &["X-Forwarded-For: 10.0.0.2"]).0, 200);
+    }
+
+    #[test]
+    fn should_reject_large_bodies() {
+        let server = Arc::new(HttpHandler {
+            logger: get_logger("gateway/servers/http"),
+            handler: io_handler(),
+            cors_domains: None,
+            allowed_hosts: None,
+            health: health(),
+            rate_limiter: None,
+            api_keys: None,
+            trusted_proxies: 0,
+            tls: None,
+            connections: Default::default(),
+            max_connections: 1,
+            idle_timeout: Duration::from_secs(60),
+        });
+        let request = |size| {
+            Request::post("/")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(vec![b' '; size]))
+                .unwrap()
+        };
+        let peer = "127.0.0.1:1000".parse().unwrap();
+
+        let response = process(server.clone(), request(MAX_REQUEST_BODY_SIZE + 1), peer)
+            .wait()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // The body is not a valid request, but is not too large.
+        let response = process(server, request(MAX_REQUEST_BODY_SIZE), peer)
+            .wait()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn should_close_idle_connections() {
+        let server = start(TestOptions {
+            idle_timeout: Duration::from_millis(200),
+            ..Default::default()
+        });
+
+        // A connection without a request is closed.
+        assert!(is_closed(connect(&server)));
+
+        // A connection is closed once idle after its last response.
+        let mut stream = connect(&server);
+        let request = format!(
+            "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\n\r\n{}",
+            RPC_REQUEST.len(),
+            RPC_REQUEST
+        );
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = [0; 12];
+        stream.read_exact(&mut response).unwrap();
+        assert_eq!(&response, b"HTTP/1.1 200");
+        // Reading the rest of the response fails if the connection is kept
+        // open past the read timeout.
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).unwrap();
+    }
+
+    #[test]
+    fn should_limit_connections() {
+        let server = start(TestOptions {
+            max_connections: 1,
+            ..Default::default()
+        });
+
+        let stream = connect(&server);
+        // Let the server accept the first connection.
+        thread::sleep(Duration::from_millis(100));
+        assert!(is_closed(connect(&server)));
+
+        let headers = ["Host: localhost", "Content-Type: application/json"];
+        assert_eq!(send(stream, "POST", &headers, RPC_REQUEST).0, 200);
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright 2015-2018 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Parity RPC.
+//!
+//! The HTTP and WebSocket servers are implemented by the gateway, so that
+//! clients can be identified by the peer address of their connection, and
+//! TLS is terminated by the servers themselves.
+
+#![warn(missing_docs)]
+
+mod http;
+mod websocket;
+
+use std::time::Duration;
+
+use jsonrpc_core;
+use jsonrpc_ipc_server as ipc;
+
+pub use self::{
+    http::{start_http, HttpServer},
+    websocket::{start_ws, WsServer},
+};
+
+/// Time allowed for clients to complete the TLS and WebSocket handshakes.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// RPC IPC Server instance
+pub type IpcServer = ipc::Server;
+
+/// Start IPC server listening on given path.
+pub fn start_ipc<M, S, H, T>(addr: &str, handler: H, extractor: T) -> ::std::io::Result<IpcServer>
+where
+    M: jsonrpc_core::Metadata,
+    S: jsonrpc_core::Middleware<M>,
+    H: Into<jsonrpc_core::MetaIoHandler<M, S>>,
+    T: ipc::MetaExtractor<M>,
+{
+    ipc::ServerBuilder::with_meta_extractor(handler, extractor).start(addr)
+}
@@ -0,0 +1,369 @@
+//! WebSocket JSON-RPC server.
+use std::{
+    io,
+    net::SocketAddr,
+    str,
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use futures::{sync::mpsc, Future, Stream};
+use jsonrpc_core::{MetaIoHandler, Middleware};
+use jsonrpc_pubsub::Session;
+use jsonrpc_server_utils::{
+    cors::{self, AccessControlAllowOrigin, AllowCors},
+    hosts::{self, Host},
+};
+use lazy_static::lazy_static;
+use mio::tcp::TcpStream;
+use openssl::ssl::SslStream;
+use parity_rpc::{v1::types::H256, Origin};
+use prometheus::{register_int_counter, IntCounter};
+use slog::{debug, error, Logger};
+use tokio::runtime::TaskExecutor;
+use ws;
+
+use crate::{
+    auth::ApiKeys,
+    logger::get_logger,
+    metadata::Metadata,
+    middleware::{self, WsStats},
+    ratelimit::{ClientInfo, WsClients},
+    tls::TlsAcceptor,
+};
+
+use super::HANDSHAKE_TIMEOUT;
+
+/// Timeout of the TLS and WebSocket handshakes.
+const HANDSHAKE: ws::util::Token = ws::util::Token(1);
+/// Timeout of the next ping of a session.
+const PING: ws::util::Token = ws::util::Token(2);
+
+// Metrics.
+lazy_static! {
+    static ref WS_UNRESPONSIVE_DISCONNECTS: IntCounter = register_int_counter!(
+        "web3_gateway_ws_unresponsive_disconnects",
+        "Number of WebSocket sessions closed for not answering pings"
+    )
+    .unwrap();
+}
+
+/// WebSocket server, which stops when dropped.
+pub struct WsServer {
+    local_addr: SocketAddr,
+    broadcaster: ws::Sender,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl WsServer {
+    /// Returns the address the server listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for WsServer {
+    fn drop(&mut self) {
+        let _ = self.broadcaster.shutdown();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// State shared by the sessions of a server.
+struct Shared<S: Middleware<Metadata>> {
+    logger: Logger,
+    handler: MetaIoHandler<Metadata, S>,
+    executor: TaskExecutor,
+    allowed_origins: Option<Vec<AccessControlAllowOrigin>>,
+    allowed_hosts: Option<Vec<Host>>,
+    api_keys: Option<Arc<ApiKeys>>,
+    stats: WsStats,
+    clients: Arc<WsClients>,
+    tls: Option<Arc<TlsAcceptor>>,
+}
+
+struct Factory<S: Middleware<Metadata>> {
+    shared: Arc<Shared<S>>,
+    next_id: u64,
+}
+
+impl<S: Middleware<Metadata>> ws::Factory for Factory<S> {
+    type Handler = WsSession<S>;
+
+    fn connection_made(&mut self, out: ws::Sender) -> Self::Handler {
+        self.next_id += 1;
+        let _ = out.timeout(HANDSHAKE_TIMEOUT.as_millis() as u64, HANDSHAKE);
+
+        WsSession {
+            shared: self.shared.clone(),
+            id: H256::from(self.next_id),
+            out,
+            key: None,
+            metadata: None,
+            last_seen: Instant::now(),
+        }
+    }
+}
+
+/// A WebSocket connection.
+struct WsSession<S: Middleware<Metadata>> {
+    shared: Arc<Shared<S>>,
+    id: H256,
+    out: ws::Sender,
+    /// Name of the API key of the handshake.
+    key: Option<String>,
+    /// Metadata of the session's requests, set once it is open.
+    metadata: Option<Metadata>,
+    /// Time the last frame was received from the client.
+    last_seen: Instant,
+}
+
+/// Returns the value of a handshake header, if it is valid.
+fn header_value<'a>(req: &'a ws::Request, name: &str) -> Option<&'a str> {
+    req.header(name)
+        .and_then(|value| str::from_utf8(value).ok())
+}
+
+impl<S: Middleware<Metadata>> WsSession<S> {
+    /// Schedule the next ping of the session, if pings are enabled.
+    fn schedule_ping(&self) -> ws::Result<()> {
+        let ping_interval = self.shared.clients.session_config().ping_interval;
+        if ping_interval == Duration::from_secs(0) {
+            return Ok(());
+        }
+
+        self.out.timeout(ping_interval.as_millis() as u64, PING)
+    }
+
+    fn forbidden(&self, message: &str) -> ws::Response {
+        let mut response =
+            ws::Response::new(403, "Forbidden", format!("{}\n", message).into_bytes());
+        response
+            .headers_mut()
+            .push(("Connection".to_owned(), b"close".to_vec()));
+
+        response
+    }
+}
+
+impl<S: Middleware<Metadata>> ws::Handler for WsSession<S> {
+    fn on_request(&mut self, req: &ws::Request) -> ws::Result<ws::Response> {
+        let host = header_value(req, "host");
+        if !hosts::is_host_valid(host, &self.shared.allowed_hosts) {
+            return Ok(self.forbidden("Provided Host header is not whitelisted."));
+        }
+
+        // Connections without an Origin header are always allowed.
+        if let AllowCors::Invalid = cors::get_cors_allow_origin(
+            header_value(req, "origin"),
+            host,
+            &self.shared.allowed_origins,
+        ) {
+            return Ok(self.forbidden("Origin of the request is not whitelisted."));
+        }
+
+        if let Some(ref api_keys) = self.shared.api_keys {
+            match middleware::ws_authenticate(api_keys, req) {
+                Ok(key) => self.key = Some(key),
+                Err(response) => return Ok(response),
+            }
+        }
+
+        ws::Response::from_request(req)
+    }
+
+    fn on_open(&mut self, shake: ws::Handshake) -> ws::Result<()> {
+        let peer = match shake.peer_addr {
+            Some(peer) => peer,
+            None => {
+                debug!(
+                    self.shared.logger,
+                    "WebSocket connection closed before it was opened"
+                );
+                return self.out.close(ws::CloseCode::Away);
+            }
+        };
+        let ip = self
+            .shared
+            .clients
+            .client_ip(peer.ip(), header_value(&shake.request, "x-forwarded-for"));
+        if !middleware::ws_admit(&self.shared.clients, ip) {
+            return self
+                .out
+                .close_with_reason(ws::CloseCode::Policy, "Too many connections");
+        }
+
+        let client = ClientInfo {
+            ip: Some(ip),
+            key: self.key.take(),
+        };
+        self.shared
+            .stats
+            .open_session(self.id.clone(), client.clone(), self.out.clone());
+
+        // Forward the session's notifications to the connection.
+        let (sender, receiver) = mpsc::channel(1);
+        let out = self.out.clone();
+        self.shared
+            .executor
+            .spawn(receiver.for_each(move |notification| out.send(notification).map_err(|_| ())));
+
+        self.metadata = Some(Metadata {
+            origin: Origin::Ws {
+                dapp: header_value(&shake.request, "origin")
+                    .unwrap_or_default()
+                    .to_owned()
+                    .into(),
+                session: self.id.clone(),
+            },
+            client: Some(client),
+            session: Some(Arc::new(Session::new(sender))),
+        });
+
+        self.schedule_ping()
+    }
+
+    fn on_message(&mut self, msg: ws::Message) -> ws::Result<()> {
+        let metadata = match self.metadata {
+            Some(ref metadata) => metadata.clone(),
+            None => return Ok(()),
+        };
+        let request = msg.into_text()?;
+        let out = self.out.clone();
+        let logger = self.shared.logger.clone();
+
+        self.shared
+            .executor
+            .spawn(
+                self.shared
+                    .handler
+                    .handle_request(&request, metadata)
+                    .map(move |response| {
+                        if let Some(response) = response {
+                            if let Err(err) = out.send(response) {
+                                debug!(logger, "Failed to send WebSocket response"; "err" => ?err);
+                            }
+                        }
+                    }),
+            );
+
+        Ok(())
+    }
+
+    fn on_frame(&mut self, frame: ws::Frame) -> ws::Result<Option<ws::Frame>> {
+        // Any frame, including pongs, shows that the client is alive.
+        self.last_seen = Instant::now();
+
+        Ok(Some(frame))
+    }
+
+    fn on_timeout(&mut self, event: ws::util::Token) -> ws::Result<()> {
+        match event {
+            // Drop connections that did not complete the handshakes in time.
+            HANDSHAKE if self.metadata.is_none() => {
+                debug!(self.shared.logger, "WebSocket handshake timed out"; "session" => ?self.id);
+                Err(io::Error::new(io::ErrorKind::TimedOut, "handshake timed out").into())
+            }
+            // Drop connections whose client does not answer pings, without
+            // waiting for a closing handshake it would not answer either.
+            PING if self
+                .shared
+                .clients
+                .session_config()
+                .is_unresponsive(self.last_seen, Instant::now()) =>
+            {
+                WS_UNRESPONSIVE_DISCONNECTS.inc();
+                debug!(self.shared.logger, "WebSocket session is unresponsive"; "session" => ?self.id);
+                Err(io::Error::new(io::ErrorKind::TimedOut, "session is unresponsive").into())
+            }
+            PING => {
+                self.out.ping(vec![])?;
+                self.schedule_ping()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn upgrade_ssl_server(&mut self, sock: TcpStream) -> ws::Result<SslStream<TcpStream>> {
+        match self.shared.tls {
+            Some(ref tls) => tls.current().accept(sock).map_err(From::from),
+            None => Err(ws::Error::new(
+                ws::ErrorKind::Internal,
+                "TLS is not enabled",
+            )),
+        }
+    }
+}
+
+impl<S: Middleware<Metadata>> Drop for WsSession<S> {
+    fn drop(&mut self) {
+        if self.metadata.take().is_some() {
+            self.shared.stats.close_session(&self.id);
+        }
+    }
+}
+
+/// Start a WebSocket server on `addr`, accepting up to `max_connections`
+/// connections, over TLS if `tls` is set.
+///
+/// `None` allows all origins or hosts. Clients are identified by the peer
+/// address of their connection, or by the `X-Forwarded-For` header of the
+/// trusted proxies of `clients`.
+pub fn start_ws<S: Middleware<Metadata>>(
+    addr: &SocketAddr,
+    handler: MetaIoHandler<Metadata, S>,
+    executor: TaskExecutor,
+    allowed_origins: Option<Vec<AccessControlAllowOrigin>>,
+    allowed_hosts: Option<Vec<Host>>,
+    max_connections: usize,
+    api_keys: Option<Arc<ApiKeys>>,
+    stats: WsStats,
+    clients: Arc<WsClients>,
+    tls: Option<Arc<TlsAcceptor>>,
+) -> ws::Result<WsServer> {
+    let logger = get_logger("gateway/servers/websocket");
+    let factory = Factory {
+        shared: Arc::new(Shared {
+            logger: logger.clone(),
+            handler,
+            executor,
+            allowed_origins,
+            allowed_hosts,
+            api_keys,
+            stats,
+            clients,
+            tls: tls.clone(),
+        }),
+        next_id: 0,
+    };
+    let server = ws::Builder::new()
+        .with_settings(ws::Settings {
+            max_connections,
+            method_strict: true,
+            panic_on_internal: false,
+            shutdown_on_interrupt: false,
+            encrypt_server: tls.is_some(),
+            ..Default::default()
+        })
+        .build(factory)?
+        .bind(addr)?;
+    let local_addr = server.local_addr()?;
+    let broadcaster = server.broadcaster();
+
+    let handle = thread::Builder::new()
+        .name("ws-server".into())
+        .spawn(move || {
+            if let Err(err) = server.run() {
+                error!(logger, "WebSocket server failed"; "err" => ?err);
+            }
+        })?;
+
+    Ok(WsServer {
+        local_addr,
+        broadcaster,
+        handle: Some(handle),
+    })
+}
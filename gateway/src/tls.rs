@@ -0,0 +1,305 @@
+//! TLS termination for the HTTP and WebSocket servers.
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Context, Error, Result};
+use futures::Future;
+use openssl::{
+    ssl::{SslAcceptor, SslFiletype, SslMethod, SslVerifyMode},
+    x509::X509Name,
+};
+use parking_lot::RwLock;
+use tokio::{net::TcpStream, timer::Timeout};
+use tokio_openssl::{SslAcceptorExt, SslStream};
+
+/// TLS configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsConfig {
+    /// PEM file with the server certificate chain.
+    pub cert: PathBuf,
+    /// PEM file with the server private key.
+    pub key: PathBuf,
+    /// PEM file with the CA certificates of clients. If set, clients must
+    /// present a certificate signed by one of them.
+    pub client_ca: Option<PathBuf>,
+}
+
+/// TLS acceptor whose certificates can be reloaded.
+pub struct TlsAcceptor {
+    config: TlsConfig,
+    acceptor: RwLock<Arc<SslAcceptor>>,
+}
+
+impl TlsAcceptor {
+    /// Create a TLS acceptor, loading its certificates.
+    pub fn new(config: TlsConfig) -> Result<Self> {
+        let acceptor = build_acceptor(&config)?;
+
+        Ok(TlsAcceptor {
+            config,
+            acceptor: RwLock::new(Arc::new(acceptor)),
+        })
+    }
+
+    /// Reload the certificates, which apply to new connections. The loaded
+    /// certificates are kept if the files are invalid.
+    pub fn reload(&self) -> Result<()> {
+        let acceptor = build_acceptor(&self.config)?;
+        *self.acceptor.write() = Arc::new(acceptor);
+
+        Ok(())
+    }
+
+    /// Returns the acceptor of new connections.
+    pub fn current(&self) -> Arc<SslAcceptor> {
+        self.acceptor.read().clone()
+    }
+
+    /// Accept a TLS connection, failing if the handshake does not complete
+    /// within `timeout`.
+    pub fn accept(
+        &self,
+        stream: TcpStream,
+        timeout: Duration,
+    ) -> impl Future<Item = SslStream<TcpStream>, Error = Error> {
+        Timeout::new(self.current().accept_async(stream), timeout).map_err(|err| {
+            if err.is_elapsed() {
+                anyhow!("TLS handshake timed out")
+            } else {
+                anyhow!("TLS handshake failed: {:?}", err)
+            }
+        })
+    }
+}
+
+fn build_acceptor(config: &TlsConfig) -> Result<SslAcceptor> {
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+    builder
+        .set_certificate_chain_file(&config.cert)
+        .with_context(|| {
+            format!(
+                "failed to load TLS certificate from {}",
+                config.cert.display()
+            )
+        })?;
+    builder
+        .set_private_key_file(&config.key, SslFiletype::PEM)
+        .with_context(|| format!("failed to load TLS key from {}", config.key.display()))?;
+    builder
+        .check_private_key()
+        .context("TLS key does not match the certificate")?;
+
+    if let Some(ref client_ca) = config.client_ca {
+        let context = || {
+            format!(
+                "failed to load TLS client CA certificates from {}",
+                client_ca.display()
+            )
+        };
+        builder.set_ca_file(client_ca).with_context(context)?;
+        let client_ca_list = X509Name::load_client_ca_file(client_ca).with_context(context)?;
+        builder.set_client_ca_list(client_ca_list);
+        builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+    }
+
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        fs,
+        io::{Read, Write},
+        net::{SocketAddr, TcpStream as StdTcpStream},
+        process, thread,
+    };
+
+    use futures::Stream;
+    use openssl::{
+        asn1::Asn1Time,
+        hash::MessageDigest,
+        pkey::{PKey, Private},
+        rsa::Rsa,
+        ssl::SslConnector,
+        x509::{extension::BasicConstraints, X509NameBuilder, X509},
+    };
+    use tokio::{io, net::TcpListener, runtime::Runtime};
+
+    type Certificate = (PKey<Private>, X509);
+
+    /// Create a certificate for `name`, signed by `issuer` or self-signed.
+    fn certificate(name: &str, issuer: Option<&Certificate>, ca: bool) -> Certificate {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_text("CN", name).unwrap();
+        let subject = subject.build();
+
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_subject_name(&subject).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        if ca {
+            cert.append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+                .unwrap();
+        }
+        match issuer {
+            Some(&(ref issuer_key, ref issuer_cert)) => {
+                cert.set_issuer_name(issuer_cert.subject_name()).unwrap();
+                cert.sign(issuer_key, MessageDigest::sha256()).unwrap();
+            }
+            None => {
+                cert.set_issuer_name(&subject).unwrap();
+                cert.sign(&key, MessageDigest::sha256()).unwrap();
+            }
+        }
+
+        (key, cert.build())
+    }
+
+    /// Create a TLS acceptor with a self-signed certificate for localhost,
+    /// requiring client certificates signed by `client_ca` if set.
+    fn acceptor(name: &str, client_ca: Option<&X509>) -> TlsAcceptor {
+        let (key, cert) = certificate("localhost", None, false);
+
+        let dir = std::env::temp_dir().join(format!("gateway-tls-{}-{}", process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let config = TlsConfig {
+            cert: dir.join("cert.pem"),
+            key: dir.join("key.pem"),
+            client_ca: client_ca.map(|_| dir.join("client-ca.pem")),
+        };
+        fs::write(&config.cert, cert.to_pem().unwrap()).unwrap();
+        fs::write(&config.key, key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        if let (Some(path), Some(client_ca)) = (&config.client_ca, client_ca) {
+            fs::write(path, client_ca.to_pem().unwrap()).unwrap();
+        }
+
+        TlsAcceptor::new(config).unwrap()
+    }
+
+    /// Connect to a TLS server without verifying its certificate, presenting
+    /// `client_cert` if set, and exchange a ping.
+    fn ping(stream: StdTcpStream, client_cert: Option<&Certificate>) {
+        let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+        connector.set_verify(SslVerifyMode::NONE);
+        if let Some(&(ref key, ref cert)) = client_cert {
+            connector.set_certificate(cert).unwrap();
+            connector.set_private_key(key).unwrap();
+        }
+        let mut stream = match connector.build().connect("localhost", stream) {
+            Ok(stream) => stream,
+            Err(_) => return,
+        };
+        let _ = stream.write_all(b"ping");
+        let _ = stream.read_exact(&mut [0u8; 4]);
+    }
+
+    /// Accept a single connection on a local listener, running `client`
+    /// against it on another thread.
+    fn accept<F>(
+        runtime: &mut Runtime,
+        acceptor: &TlsAcceptor,
+        timeout: Duration,
+        client: F,
+    ) -> Result<SslStream<TcpStream>>
+    where
+        F: FnOnce(StdTcpStream) + Send + 'static,
+    {
+        let listener = TcpListener::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || client(StdTcpStream::connect(addr).unwrap()));
+
+        let (stream, _) = runtime
+            .block_on(listener.incoming().into_future().map_err(|(err, _)| err))
+            .unwrap();
+        runtime.block_on(acceptor.accept(stream.unwrap(), timeout))
+    }
+
+    #[test]
+    fn should_accept_tls_connections() {
+        let mut runtime = Runtime::new().unwrap();
+        let acceptor = acceptor("accept", None);
+
+        let stream = accept(&mut runtime, &acceptor, Duration::from_secs(10), |stream| {
+            ping(stream, None)
+        })
+        .unwrap();
+
+        let (stream, request) = runtime.block_on(io::read_exact(stream, [0u8; 4])).unwrap();
+        assert_eq!(&request, b"ping");
+        runtime.block_on(io::write_all(stream, b"pong")).unwrap();
+    }
+
+    #[test]
+    fn should_reject_plaintext_connections() {
+        let mut runtime = Runtime::new().unwrap();
+        let acceptor = acceptor("plaintext", None);
+
+        let result = accept(
+            &mut runtime,
+            &acceptor,
+            Duration::from_secs(10),
+            |mut stream| {
+                let _ = stream.write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+                let _ = stream.read_to_end(&mut Vec::new());
+            },
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn should_time_out_handshakes() {
+        let mut runtime = Runtime::new().unwrap();
+        let acceptor = acceptor("timeout", None);
+
+        // The client connects but never starts the handshake.
+        let result = accept(
+            &mut runtime,
+            &acceptor,
+            Duration::from_millis(100),
+            |stream| {
+                thread::sleep(Duration::from_secs(1));
+                drop(stream);
+            },
+        );
+        let err = result.err().expect("handshake must time out");
+        assert_eq!(err.to_string(), "TLS handshake timed out");
+    }
+
+    #[test]
+    fn should_reject_clients_without_certificate() {
+        let mut runtime = Runtime::new().unwrap();
+        let (_, client_ca) = certificate("client-ca", None, true);
+        let acceptor = acceptor("no-client-cert", Some(&client_ca));
+
+        let result = accept(&mut runtime, &acceptor, Duration::from_secs(10), |stream| {
+            ping(stream, None)
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn should_accept_clients_with_certificate_signed_by_ca() {
+        let mut runtime = Runtime::new().unwrap();
+        let client_ca = certificate("client-ca", None, true);
+        let client_cert = certificate("client", Some(&client_ca), false);
+        let acceptor = acceptor("client-cert", Some(&client_ca.1));
+
+        let stream = accept(
+            &mut runtime,
+            &acceptor,
+            Duration::from_secs(10),
+            move |stream| ping(stream, Some(&client_cert)),
+        )
+        .unwrap();
+
+        let (stream, request) = runtime.block_on(io::read_exact(stream, [0u8; 4])).unwrap();
+        assert_eq!(&request, b"ping");
+        runtime.block_on(io::write_all(stream, b"pong")).unwrap();
+    }
+}
@@ -0,0 +1,88 @@
+//! Admin RPC interface.
+use std::net::IpAddr;
+
+use jsonrpc_core::Result;
+
+use parity_rpc::v1::types::{H256, U256};
+
+build_rpc_trait! {
+    pub trait Admin {
+        /// Returns the open WebSocket sessions.
+        #[rpc(name = "admin_sessions")]
+        fn sessions(&self) -> Result<Vec<RpcSession>>;
+
+        /// Returns the request statistics of the WebSocket server.
+        #[rpc(name = "admin_rpcStats")]
+        fn rpc_stats(&self) -> Result<RpcStatsReport>;
+
+        /// Returns the installed filters.
+        #[rpc(name = "admin_filters")]
+        fn filters(&self) -> Result<Vec<RpcFilter>>;
+
+        /// Returns the number of active subscriptions of each kind.
+        #[rpc(name = "admin_subscriptions")]
+        fn subscriptions(&self) -> Result<RpcSubscriptions>;
+
+        /// Closes a WebSocket session. Returns false if the session does not
+        /// exist.
+        #[rpc(name = "admin_closeSession")]
+        fn close_session(&self, H256) -> Result<bool>;
+
+        /// Sets the log level: off, error, warn, info, debug or trace.
+        #[rpc(name = "admin_setLogLevel")]
+        fn set_log_level(&self, String) -> Result<bool>;
+
+        /// Reloads the API keys, together with their permissions and rate
+        /// limits.
+        #[rpc(name = "admin_reload")]
+        fn reload(&self) -> Result<bool>;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcSession {
+    /// Session id.
+    pub id: H256,
+    /// Client address, if known.
+    pub ip: Option<IpAddr>,
+    /// Name of the API key the session authenticated with.
+    #[serde(rename = "apiKey")]
+    pub api_key: Option<String>,
+    /// Requests/second made on the session.
+    #[serde(rename = "requestsRate")]
+    pub requests_rate: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcStatsReport {
+    /// Number of open sessions.
+    pub sessions: usize,
+    /// Requests/second made on all sessions.
+    #[serde(rename = "requestsRate")]
+    pub requests_rate: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcFilter {
+    /// Filter id.
+    pub id: U256,
+    /// Filter kind: logs, block or pendingTransaction.
+    pub kind: String,
+    /// Seconds since the filter was last polled.
+    #[serde(rename = "idleSeconds")]
+    pub idle_seconds: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RpcSubscriptions {
+    /// Number of `newHeads` subscriptions.
+    #[serde(rename = "newHeads")]
+    pub new_heads: usize,
+    /// Number of `logs` subscriptions.
+    pub logs: usize,
+    /// Number of `completedTransaction` subscriptions.
+    #[serde(rename = "completedTransaction")]
+    pub completed_transaction: usize,
+    /// Number of `syncing` subscriptions.
+    pub syncing: usize,
+}
@@ -0,0 +1,175 @@
+//! Eth RPC interface extensions.
+use std::collections::BTreeMap;
+
+use jsonrpc_core::{BoxFuture, Result};
+use jsonrpc_macros::{pubsub::Subscriber, Trailing};
+use jsonrpc_pubsub::SubscriptionId;
+use parity_rpc::v1::types::{pubsub, BlockNumber, Bytes, CallRequest, Log, H160, H256, U256, U64};
+use serde::{Serialize, Serializer};
+
+use crate::nodes::SyncStatus;
+
+/// State override set for simulated calls, keyed by account address.
+pub type StateOverride = BTreeMap<H160, AccountOverride>;
+
+/// Account state override for simulated calls (geth-compatible).
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountOverride {
+    /// Fake balance to set for the account.
+    pub balance: Option<U256>,
+    /// Fake nonce to set for the account.
+    pub nonce: Option<U64>,
+    /// Fake code to set for the account.
+    pub code: Option<Bytes>,
+    /// Fake storage replacing the entire account storage.
+    pub state: Option<BTreeMap<H256, H256>>,
+    /// Fake storage slots to patch into the account storage.
+    #[serde(rename = "stateDiff")]
+    pub state_diff: Option<BTreeMap<H256, H256>>,
+}
+
+build_rpc_trait! {
+    pub trait EthCallMany {
+        type Metadata;
+
+        /// Executes a list of calls in order on top of the state of the given
+        /// block, with each call observing the effects of the preceding ones.
+        #[rpc(meta, name = "eth_callMany")]
+        fn call_many(&self, Self::Metadata, Vec<CallRequest>, Trailing<BlockNumber>) -> BoxFuture<Vec<RpcCallResult>>;
+    }
+}
+
+build_rpc_trait! {
+    pub trait EthPubSub {
+        type Metadata;
+
+        #[pubsub(name = "eth_subscription")] {
+            /// Subscribe to Eth subscription.
+            ///
+            /// Same as the `EthPubSub` interface of Parity, with notifications
+            /// which may also be sync states.
+            #[rpc(name = "eth_subscribe")]
+            fn subscribe(&self, Self::Metadata, Subscriber<RpcSubscriptionResult>, pubsub::Kind, Trailing<pubsub::Params>);
+
+            /// Unsubscribe from existing Eth subscription.
+            #[rpc(name = "eth_unsubscribe")]
+            fn unsubscribe(&self, SubscriptionId) -> Result<bool>;
+        }
+    }
+}
+
+build_rpc_trait! {
+    pub trait EthProof {
+        type Metadata;
+
+        /// Returns the account and storage values of an address with proofs.
+        ///
+        /// The response has the fields of EIP-1186, but the proofs are not
+        /// Merkle-Patricia trie proofs and cannot be verified by EIP-1186
+        /// clients. Each proof item is a CBOR-encoded `(key, value, proof)`
+        /// MKVS entry as returned by `oasis_getProof`, and the storage hash is
+        /// the MKVS state root, as accounts do not have separate storage tries.
+        #[rpc(name = "eth_getProof")]
+        fn proof(&self, H160, Vec<H256>, Trailing<BlockNumber>) -> BoxFuture<RpcEthAccountProof>;
+    }
+}
+
+/// Result of a single call in a simulated call bundle.
+#[derive(Debug, Serialize)]
+pub struct RpcCallResult {
+    /// Return value.
+    pub output: Bytes,
+    /// Gas used by the call.
+    #[serde(rename = "gasUsed")]
+    pub gas_used: U256,
+    /// Logs emitted by the call.
+    pub logs: Vec<Log>,
+    /// Revert reason, if the call was reverted with one.
+    #[serde(rename = "revertReason", skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
+    /// Error message, if the call failed or was reverted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Account proof in the shape of EIP-1186.
+#[derive(Debug, Serialize)]
+pub struct RpcEthAccountProof {
+    pub address: H160,
+    #[serde(rename = "accountProof")]
+    pub account_proof: Vec<Bytes>,
+    pub balance: U256,
+    #[serde(rename = "codeHash")]
+    pub code_hash: H256,
+    pub nonce: U256,
+    #[serde(rename = "storageHash")]
+    pub storage_hash: H256,
+    #[serde(rename = "storageProof")]
+    pub storage_proof: Vec<RpcEthStorageProof>,
+}
+
+/// Storage proof in the shape of EIP-1186.
+#[derive(Debug, Serialize)]
+pub struct RpcEthStorageProof {
+    pub key: H256,
+    pub value: H256,
+    pub proof: Vec<Bytes>,
+}
+
+/// Sync state, serialized as `false` when synced.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RpcSyncStatus {
+    Synced,
+    Syncing(RpcSyncInfo),
+}
+
+impl Serialize for RpcSyncStatus {
+    fn serialize<S: Serializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+        match *self {
+            RpcSyncStatus::Synced => false.serialize(serializer),
+            RpcSyncStatus::Syncing(ref info) => info.serialize(serializer),
+        }
+    }
+}
+
+impl From<SyncStatus> for RpcSyncStatus {
+    fn from(status: SyncStatus) -> Self {
+        match status {
+            SyncStatus::Synced => RpcSyncStatus::Synced,
+            SyncStatus::Syncing {
+                starting,
+                current,
+                highest,
+            } => RpcSyncStatus::Syncing(RpcSyncInfo {
+                starting_block: starting.into(),
+                current_block: current.into(),
+                highest_block: highest.into(),
+            }),
+        }
+    }
+}
+
+/// Progress of syncing.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RpcSyncInfo {
+    /// Round at which syncing started.
+    #[serde(rename = "startingBlock")]
+    pub starting_block: U256,
+    /// Latest round of the synced state.
+    #[serde(rename = "currentBlock")]
+    pub current_block: U256,
+    /// Highest known round.
+    #[serde(rename = "highestBlock")]
+    pub highest_block: U256,
+}
+
+/// Notification of an Eth subscription.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum RpcSubscriptionResult {
+    /// Notification of a subscription kind supported by Parity.
+    Eth(pubsub::Result),
+    /// Sync state, of `syncing` subscriptions.
+    Syncing(RpcSyncStatus),
+}
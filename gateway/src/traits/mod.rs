@@ -1,5 +1,11 @@
 //! RPC traits for the client.
 
+pub mod admin;
+pub mod eth;
 pub mod oasis;
 
-pub use self::oasis::Oasis;
+pub use self::{
+    admin::Admin,
+    eth::{EthCallMany, EthProof, EthPubSub},
+    oasis::Oasis,
+};
@@ -3,7 +3,7 @@ use ethereum_types::Address;
 use jsonrpc_core::BoxFuture;
 use jsonrpc_macros::Trailing;
 
-use parity_rpc::v1::types::{BlockNumber, Bytes, H160, H256, U64};
+use parity_rpc::v1::types::{BlockNumber, Bytes, H160, H256, U256, U64};
 
 build_rpc_trait! {
     pub trait Oasis {
@@ -21,6 +21,11 @@ build_rpc_trait! {
         /// status code and return value.
         #[rpc(name = "oasis_invoke")]
         fn invoke(&self, Bytes) -> BoxFuture<RpcExecutionPayload>;
+
+        /// Returns the account and storage values of an address, together with
+        /// MKVS proofs of the storage entries they were read from.
+        #[rpc(name = "oasis_getProof")]
+        fn get_proof(&self, H160, Vec<H256>, Trailing<BlockNumber>) -> BoxFuture<RpcAccountProof>;
     }
 }
 
@@ -46,3 +51,45 @@ pub struct RpcPublicKeyPayload {
     /// i.e., Sign(ssk, (pk, t).
     pub signature: Bytes,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcAccountProof {
+    /// Address of the account.
+    pub address: H160,
+    /// Balance of the account.
+    pub balance: U256,
+    /// Nonce of the account.
+    pub nonce: U256,
+    /// Hash of the account code.
+    #[serde(rename = "codeHash")]
+    pub code_hash: H256,
+    /// MKVS state root the proofs are against.
+    #[serde(rename = "stateRoot")]
+    pub state_root: H256,
+    /// Proofs of the entries the account values were read from.
+    #[serde(rename = "accountProof")]
+    pub account_proof: Vec<RpcProofEntry>,
+    /// Requested storage values with their proofs.
+    #[serde(rename = "storageProof")]
+    pub storage_proof: Vec<RpcStorageProof>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcStorageProof {
+    /// Storage key.
+    pub key: H256,
+    /// Storage value.
+    pub value: H256,
+    /// Proofs of the entries the value was read from.
+    pub proof: Vec<RpcProofEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcProofEntry {
+    /// MKVS key.
+    pub key: Bytes,
+    /// MKVS value, null if the key does not exist.
+    pub value: Option<Bytes>,
+    /// CBOR-encoded MKVS proof.
+    pub proof: Bytes,
+}
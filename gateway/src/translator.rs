@@ -1,6 +1,16 @@
 //! Translator which translates between an Oasis Core chain running the Eth/WASI
 //! runtime and an Ethereum chain exposed to clients.
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    any::Any,
+    cmp,
+    collections::BTreeMap,
+    mem,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, ensure, Error, Result};
 use ethcore::{
@@ -9,67 +19,241 @@ use ethcore::{
     filter::Filter,
     log_entry::{LocalizedLogEntry, LogEntry},
     receipt::{LocalizedReceipt, TransactionOutcome},
-    state::State,
+    state::{CleanupMode, State},
     transaction::{Action, LocalizedTransaction, SignedTransaction, UnverifiedTransaction},
     types::ids::BlockId,
-    vm::EnvInfo,
+    vm::{EnvInfo, Error as VmError},
 };
-use ethereum_types::{H256, H64, U256};
+use ethereum_types::{Address, H256, H64, U256};
 use futures::{future, prelude::*};
+use grpcio::{CallOption, RpcStatusCode};
 use hash::KECCAK_EMPTY_LIST_RLP;
 use io_context::Context;
 use lazy_static::lazy_static;
 use oasis_core_client::{
     transaction::{
+        api::storage::StorageClient,
         snapshot::{BlockSnapshot, TransactionSnapshot},
         Query, QueryCondition, ROUND_LATEST,
     },
     BoxFuture,
 };
 use oasis_core_runtime::{
-    common::{cbor, crypto::hash::Hash, logger::get_logger},
-    storage::MKVS,
+    common::{cbor, crypto::hash::Hash, roothash::Block},
+    storage::{
+        mkvs::{
+            sync::{
+                GetPrefixesRequest, GetRequest, IterateRequest, Proof, ProofResponse, ReadSync,
+                TreeID,
+            },
+            Root, RootType, Tree,
+        },
+        MKVS,
+    },
     transaction::types::{TxnCall, TxnOutput},
 };
 use oasis_ethwasi_runtime_api::{ExecutionResult, TransactionError, METHOD_TX};
 use oasis_ethwasi_runtime_common::{
-    genesis, parity::NullBackend, TAG_ETH_LOG_ADDRESS, TAG_ETH_LOG_TOPICS, TAG_ETH_TX_HASH,
+    genesis, parity::NullBackend, BLOCK_GAS_LIMIT, TAG_ETH_LOG_ADDRESS, TAG_ETH_LOG_TOPICS,
+    TAG_ETH_TX_HASH,
 };
 use parity_rpc::v1::types::{
     Block as EthRpcBlock, BlockTransactions as EthRpcBlockTransactions, Header as EthRpcHeader,
     RichBlock as EthRpcRichBlock, RichHeader as EthRpcRichHeader, Transaction as EthRpcTransaction,
 };
+use parking_lot::{Mutex, RwLock};
 
+use prometheus::{labels, register_int_counter, register_int_gauge, IntCounter, IntGauge};
 use serde_bytes::ByteBuf;
-use slog::{error, info, Logger};
+use slog::{error, info, warn, Logger};
+use thiserror::Error;
+use tokio::timer::Timeout;
 use tokio_threadpool::{Builder as ThreadPoolBuilder, ThreadPool};
 
-use crate::EthereumRuntimeClient;
+use crate::{
+    cache::{ByteSize, CacheConfig, Coalescer, Lru, CACHE_HITS, CACHE_MISSES},
+    context,
+    logger::get_logger,
+    nodes::{NodeClient, Nodes},
+    traits::eth::StateOverride,
+    util::decode_revert_reason,
+    validation, EthereumRuntimeClient,
+};
+
+// Metrics.
+lazy_static! {
+    static ref SIMULATOR_QUEUE_DEPTH: IntGauge = register_int_gauge!(
+        "web3_gateway_simulator_queue_depth",
+        "Number of queued and running simulations"
+    )
+    .unwrap();
+    static ref SIMULATOR_REJECTED: IntCounter = register_int_counter!(
+        "web3_gateway_simulator_rejected",
+        "Number of simulations rejected due to a full simulator queue"
+    )
+    .unwrap();
+    static ref SIMULATOR_TIMEOUTS: IntCounter = register_int_counter!(
+        "web3_gateway_simulator_timeouts",
+        "Number of simulations that exceeded the simulation timeout"
+    )
+    .unwrap();
+}
+
+/// Time for which the latest block is served from the cache. The cache is
+/// also refreshed by the pub/sub broker on every poll.
+const LATEST_BLOCK_TTL: Duration = Duration::from_secs(1);
+
+/// Transaction simulator configuration.
+#[derive(Debug, Clone)]
+pub struct SimulatorConfig {
+    /// Maximum amount of gas a simulated transaction may use.
+    pub gas_cap: U256,
+    /// Wall-clock time after which a simulation is abandoned.
+    pub timeout: Duration,
+    /// Maximum number of queued and running simulations.
+    pub max_queue_size: usize,
+    /// Maximum number of transactions in a single simulated bundle.
+    pub max_bundle_size: usize,
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        Self {
+            gas_cap: BLOCK_GAS_LIMIT.into(),
+            timeout: Duration::from_secs(5),
+            max_queue_size: 128,
+            max_bundle_size: 32,
+        }
+    }
+}
+
+/// Transaction simulation error.
+#[derive(Debug, Error)]
+pub enum SimulationError {
+    #[error("{0}")]
+    Call(CallError),
+    #[error("too many pending simulations")]
+    QueueFull,
+    #[error("simulation timed out")]
+    Timeout,
+    #[error("invalid state override: {0}")]
+    InvalidOverride(String),
+    #[error("too many transactions in bundle (max {max})")]
+    TooManyTransactions { max: usize },
+    #[error("{}", revert_message(.reason))]
+    Reverted {
+        reason: Option<String>,
+        output: Vec<u8>,
+    },
+    #[error("gas required exceeds allowance ({cap})")]
+    GasRequiredExceedsCap { cap: U256 },
+    #[error("execution failed: {0}")]
+    ExecutionFailed(String),
+}
+
+fn revert_message(reason: &Option<String>) -> String {
+    match reason {
+        Some(reason) => format!("execution reverted: {}", reason),
+        None => "execution reverted".to_owned(),
+    }
+}
+
+impl From<CallError> for SimulationError {
+    fn from(err: CallError) -> Self {
+        SimulationError::Call(err)
+    }
+}
+
+/// A slot in the simulator queue, released when dropped.
+struct SimulatorSlot(Arc<AtomicUsize>);
+
+impl SimulatorSlot {
+    /// Try to acquire a slot, failing if the queue is full.
+    fn acquire(queue: &Arc<AtomicUsize>, max_queue_size: usize) -> Option<Self> {
+        let depth = queue.fetch_add(1, Ordering::SeqCst) + 1;
+        if depth > max_queue_size {
+            queue.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        SIMULATOR_QUEUE_DEPTH.set(depth as i64);
+
+        Some(SimulatorSlot(queue.clone()))
+    }
+}
+
+impl Drop for SimulatorSlot {
+    fn drop(&mut self) {
+        let depth = self.0.fetch_sub(1, Ordering::SeqCst) - 1;
+        SIMULATOR_QUEUE_DEPTH.set(depth as i64);
+    }
+}
 
 /// Translator that enables exposing the Eth/WASI runtime on Oasis Core as an
 /// Ethereum chain.
 pub struct Translator {
     logger: Logger,
-    client: Arc<EthereumRuntimeClient>,
+    nodes: Arc<Nodes>,
     gas_price: U256,
     simulator_pool: Arc<ThreadPool>,
+    simulator_config: SimulatorConfig,
+    simulator_queue: Arc<AtomicUsize>,
+    latest_block: Arc<RwLock<Option<(BlockData, Instant)>>>,
+    block_lookups: Coalescer<BlockKey, Option<BlockData>>,
+    blocks_by_round: Arc<Lru<u64, BlockData>>,
+    /// Rounds of the blocks with a given hash.
+    blocks_by_hash: Arc<Lru<H256, u64>>,
+    transactions: Arc<Lru<H256, LocalizedTransaction>>,
+    receipts: Arc<Lru<H256, LocalizedReceipt>>,
+}
+
+/// Key of a block lookup.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum BlockKey {
+    Latest,
+    Round(u64),
+    Hash(H256),
 }
 
 impl Translator {
     /// Create new translator.
-    pub fn new(client: EthereumRuntimeClient, gas_price: U256) -> Self {
+    pub fn new(
+        nodes: Arc<Nodes>,
+        gas_price: U256,
+        simulator_config: SimulatorConfig,
+        cache_config: CacheConfig,
+    ) -> Self {
         Self {
             logger: get_logger("gateway/translator"),
-            client: Arc::new(client),
+            nodes,
             gas_price,
             simulator_pool: Arc::new(
                 ThreadPoolBuilder::new()
                     .name_prefix("simulator-pool-")
                     .build(),
             ),
+            simulator_config,
+            simulator_queue: Arc::new(AtomicUsize::new(0)),
+            latest_block: Default::default(),
+            block_lookups: Coalescer::new("block"),
+            blocks_by_round: Arc::new(Lru::new("block_by_round", cache_config.blocks)),
+            // Rounds are much smaller than blocks, so the index keeps more
+            // entries than the block cache within a smaller size.
+            blocks_by_hash: Arc::new(Lru::new("block_by_hash", cache_config.blocks / 16)),
+            transactions: Arc::new(Lru::new("transaction", cache_config.transactions)),
+            receipts: Arc::new(Lru::new("receipt", cache_config.receipts)),
         }
     }
 
+    /// Oasis Core nodes the translator reads from.
+    pub fn nodes(&self) -> &Arc<Nodes> {
+        &self.nodes
+    }
+
+    /// Client of the next healthy node.
+    fn client(&self) -> Arc<EthereumRuntimeClient> {
+        self.nodes.select().client.clone()
+    }
+
     /// Gas price.
     pub fn gas_price(&self) -> U256 {
         self.gas_price
@@ -104,12 +288,52 @@ impl Translator {
     }
 
     /// Retrieve the latest Ethereum block.
+    ///
+    /// The block may be up to `LATEST_BLOCK_TTL` old.
     pub fn get_latest_block(&self) -> impl Future<Item = EthereumBlock, Error = Error> {
-        let client = self.client.clone();
-        self.client
-            .txn_client()
-            .get_latest_block()
-            .map(|snapshot| EthereumBlock::new(snapshot, client))
+        if let Some((ref data, fetched)) = *self.latest_block.read() {
+            if fetched.elapsed() < LATEST_BLOCK_TTL {
+                CACHE_HITS.with(&labels! {"cache" => "latest_block",}).inc();
+                let block = EthereumBlock::new(data.clone(), self.nodes.select());
+                return future::Either::A(future::ok(block));
+            }
+        }
+
+        CACHE_MISSES
+            .with(&labels! {"cache" => "latest_block",})
+            .inc();
+        future::Either::B(self.refresh_latest_block())
+    }
+
+    /// Retrieve the latest Ethereum block from the node, updating the cached
+    /// latest block.
+    pub fn refresh_latest_block(&self) -> impl Future<Item = EthereumBlock, Error = Error> {
+        let node = self.nodes.select();
+        let client = node.client.clone();
+        let latest_block = self.latest_block.clone();
+        let blocks_by_round = self.blocks_by_round.clone();
+        let blocks_by_hash = self.blocks_by_hash.clone();
+        self.block_lookups
+            .get(BlockKey::Latest, move || {
+                client.txn_client().get_latest_block().map(move |snapshot| {
+                    let data = BlockData::new(snapshot);
+                    blocks_by_round.insert(data.round(), data.clone());
+                    blocks_by_hash.insert(data.hash(), data.round());
+
+                    // Never replace the cached block with an older one.
+                    let mut latest_block = latest_block.write();
+                    match *latest_block {
+                        Some((ref cached, _)) if cached.round() > data.round() => {}
+                        _ => *latest_block = Some((data.clone(), Instant::now())),
+                    }
+
+                    Some(data)
+                })
+            })
+            .and_then(move |data| match data {
+                Some(data) => Ok(EthereumBlock::new(data, node)),
+                None => Err(anyhow!("latest block not found")),
+            })
     }
 
     /// Retrieve a specific Ethereum block, identified by its round number.
@@ -117,11 +341,28 @@ impl Translator {
         &self,
         round: u64,
     ) -> impl Future<Item = Option<EthereumBlock>, Error = Error> {
-        let client = self.client.clone();
-        self.client
-            .txn_client()
-            .get_block(round)
-            .map(|snapshot| snapshot.map(|snapshot| EthereumBlock::new(snapshot, client)))
+        let node = self.nodes.select();
+        if let Some(data) = self.blocks_by_round.get(&round) {
+            return future::Either::A(future::ok(Some(EthereumBlock::new(data, node))));
+        }
+
+        let client = node.client.clone();
+        let blocks_by_round = self.blocks_by_round.clone();
+        let blocks_by_hash = self.blocks_by_hash.clone();
+        future::Either::B(
+            self.block_lookups
+                .get(BlockKey::Round(round), move || {
+                    client.txn_client().get_block(round).map(move |snapshot| {
+                        let data = snapshot.map(BlockData::new);
+                        if let Some(ref data) = data {
+                            blocks_by_round.insert(data.round(), data.clone());
+                            blocks_by_hash.insert(data.hash(), data.round());
+                        }
+                        data
+                    })
+                })
+                .map(move |data| data.map(|data| EthereumBlock::new(data, node))),
+        )
     }
 
     /// Retrieve a specific Ethereum block, identified by its block hash.
@@ -129,11 +370,31 @@ impl Translator {
         &self,
         hash: H256,
     ) -> impl Future<Item = Option<EthereumBlock>, Error = Error> {
-        let client = self.client.clone();
-        self.client
-            .txn_client()
-            .get_block_by_hash(Hash::from(hash.as_ref() as &[u8]))
-            .map(|snapshot| snapshot.map(|snapshot| EthereumBlock::new(snapshot, client)))
+        if let Some(round) = self.blocks_by_hash.get(&hash) {
+            return future::Either::A(self.get_block_by_round(round));
+        }
+
+        let node = self.nodes.select();
+        let client = node.client.clone();
+        let blocks_by_round = self.blocks_by_round.clone();
+        let blocks_by_hash = self.blocks_by_hash.clone();
+        future::Either::B(
+            self.block_lookups
+                .get(BlockKey::Hash(hash), move || {
+                    client
+                        .txn_client()
+                        .get_block_by_hash(Hash::from(hash.as_ref() as &[u8]))
+                        .map(move |snapshot| {
+                            let data = snapshot.map(BlockData::new);
+                            if let Some(ref data) = data {
+                                blocks_by_round.insert(data.round(), data.clone());
+                                blocks_by_hash.insert(hash, data.round());
+                            }
+                            data
+                        })
+                })
+                .map(move |data| data.map(|data| EthereumBlock::new(data, node))),
+        )
     }
 
     /// Retrieve a specific Ethereum transaction, identified by its transaction hash.
@@ -141,12 +402,52 @@ impl Translator {
         &self,
         hash: H256,
     ) -> impl Future<Item = Option<EthereumTransaction>, Error = Error> {
-        self.client
+        self.client()
             .txn_client()
             .query_tx(TAG_ETH_TX_HASH, hash)
             .map(|txn| txn.map(EthereumTransaction::new))
     }
 
+    /// Retrieve a decoded Ethereum transaction, identified by its transaction
+    /// hash.
+    pub fn get_transaction(
+        &self,
+        hash: H256,
+    ) -> impl Future<Item = Option<LocalizedTransaction>, Error = Error> {
+        if let Some(transaction) = self.transactions.get(&hash) {
+            return future::Either::A(future::ok(Some(transaction)));
+        }
+
+        let transactions = self.transactions.clone();
+        future::Either::B(self.get_txn_by_hash(hash).and_then(move |txn| {
+            let transaction = txn.map(|txn| txn.transaction()).transpose()?;
+            if let Some(ref transaction) = transaction {
+                transactions.insert(hash, transaction.clone());
+            }
+            Ok(transaction)
+        }))
+    }
+
+    /// Retrieve a decoded Ethereum transaction receipt, identified by its
+    /// transaction hash.
+    pub fn get_receipt(
+        &self,
+        hash: H256,
+    ) -> impl Future<Item = Option<LocalizedReceipt>, Error = Error> {
+        if let Some(receipt) = self.receipts.get(&hash) {
+            return future::Either::A(future::ok(Some(receipt)));
+        }
+
+        let receipts = self.receipts.clone();
+        future::Either::B(self.get_txn_by_hash(hash).and_then(move |txn| {
+            let receipt = txn.map(|txn| txn.receipt()).transpose()?;
+            if let Some(ref receipt) = receipt {
+                receipts.insert(hash, receipt.clone());
+            }
+            Ok(receipt)
+        }))
+    }
+
     /// Retrieve a specific Ethereum transaction, identified by the block round and
     /// transaction index within the block.
     pub fn get_txn_by_round_and_index(
@@ -154,7 +455,7 @@ impl Translator {
         round: u64,
         index: u32,
     ) -> impl Future<Item = Option<EthereumTransaction>, Error = Error> {
-        self.client
+        self.client()
             .txn_client()
             .get_tx(round, index)
             .map(|txn| txn.map(EthereumTransaction::new))
@@ -167,7 +468,7 @@ impl Translator {
         block_hash: H256,
         index: u32,
     ) -> impl Future<Item = Option<EthereumTransaction>, Error = Error> {
-        self.client
+        self.client()
             .txn_client()
             .get_tx_by_block_hash(Hash::from(block_hash.as_ref() as &[u8]), index)
             .map(|txn| txn.map(EthereumTransaction::new))
@@ -191,32 +492,70 @@ impl Translator {
     }
 
     /// Submit a raw Ethereum transaction to the chain.
-    pub fn send_raw_transaction(&self, raw: Vec<u8>) -> BoxFuture<(H256, ExecutionResult)> {
+    pub fn send_raw_transaction(
+        &self,
+        ctx: Arc<Context>,
+        raw: Vec<u8>,
+    ) -> BoxFuture<(H256, ExecutionResult)> {
         // Try to decode the transaction.
         let decoded: UnverifiedTransaction = match rlp::decode(&raw) {
             Ok(decoded) => decoded,
             Err(err) => return Box::new(future::err(err.into())),
         };
 
+        // Check that gas < block gas limit.
+        if let Err(err) = validation::check_unverified(&decoded) {
+            return Box::new(future::err(err.into()));
+        }
+
         // Try to verify the signature.
         let signed = match SignedTransaction::new(decoded) {
             Ok(signed) => signed,
             Err(err) => return Box::new(future::err(err.into())),
         };
 
+        // Check gas price and chain ID.
+        if let Err(err) = validation::check_signed(&signed) {
+            return Box::new(future::err(err.into()));
+        }
+
+        // Check nonce and balance against the latest state.
+        let sender = signed.sender();
+        let validated = self.get_latest_block().and_then(move |blk| {
+            let state = blk.state(ctx)?;
+            validation::check_account(&signed, state.nonce(&sender)?, state.balance(&sender)?)?;
+
+            Ok(signed)
+        });
+
         // If we get a BlockGasLimitReached error, retry up to 5 times.
         const MAX_RETRIES: usize = 5;
 
-        Box::new(future::loop_fn(
-            (
-                MAX_RETRIES,
-                self.client.clone(),
-                ByteBuf::from(raw),
-                signed,
-                self.logger.clone(),
-            ),
-            move |(retries, client, payload, signed, logger)| {
-                client
+        let nodes = self.nodes.clone();
+        let logger = self.logger.clone();
+        Box::new(validated.and_then(move |signed| {
+            Self::submit_transaction(nodes, ByteBuf::from(raw), signed, MAX_RETRIES, logger)
+        }))
+    }
+
+    /// Submit a validated raw Ethereum transaction to the chain, failing over
+    /// to the next node if a node could not be reached.
+    fn submit_transaction(
+        nodes: Arc<Nodes>,
+        payload: ByteBuf,
+        signed: SignedTransaction,
+        max_retries: usize,
+        logger: Logger,
+    ) -> impl Future<Item = (H256, ExecutionResult), Error = Error> {
+        let mut candidates = nodes.failover_order();
+        candidates.reverse();
+        let node = candidates.pop().expect("there is at least one node");
+
+        future::loop_fn(
+            (max_retries, node, candidates, payload, signed, logger),
+            move |(retries, node, mut candidates, payload, signed, logger)| {
+                let nodes = nodes.clone();
+                node.client
                     .tx(payload.clone())
                     .then(move |maybe_result| match maybe_result {
                         Ok(result) => {
@@ -236,7 +575,20 @@ impl Translator {
                                     }
                                     let retries = retries - 1;
                                     return Ok(future::Loop::Continue((
-                                        retries, client, payload, signed, logger,
+                                        retries, node, candidates, payload, signed, logger,
+                                    )));
+                                }
+                            }
+                            if is_unreached(&err) {
+                                if let Some(next) = candidates.pop() {
+                                    warn!(logger, "send_raw_transaction failing over";
+                                        "node" => &node.address,
+                                        "next_node" => &next.address,
+                                        "err" => ?err,
+                                    );
+                                    nodes.mark_unhealthy(&node);
+                                    return Ok(future::Loop::Continue((
+                                        retries, next, candidates, payload, signed, logger,
                                     )));
                                 }
                             }
@@ -250,76 +602,218 @@ impl Translator {
                         }
                     })
             },
-        ))
+        )
+    }
+
+    /// Retrieve the account and storage values of an address at a given block,
+    /// together with MKVS proofs of the storage entries they were read from.
+    pub fn get_proof(
+        &self,
+        ctx: Arc<Context>,
+        address: Address,
+        storage_keys: Vec<H256>,
+        id: BlockId,
+    ) -> impl Future<Item = AccountProof, Error = Error> {
+        let call_option = context::call_option(&ctx);
+
+        self.get_block_unwrap(id)
+            .and_then(move |blk| -> Result<_> {
+                // Record the entries read while looking up each of the values,
+                // and prove them on the node they were read from.
+                let storage_client = blk.node.storage_client.clone();
+                let mkvs = RecordingMKVS::new(blk.mkvs(ctx));
+                let state = blk.state_with_mkvs(Box::new(mkvs.clone()))?;
+
+                let balance = state.balance(&address)?;
+                let nonce = state.nonce(&address)?;
+                let code_hash = state.code_hash(&address)?;
+                let account_reads = mkvs.take_reads();
+
+                let mut storage = Vec::with_capacity(storage_keys.len());
+                for key in storage_keys {
+                    let value = state.storage_at(&address, &key)?;
+                    storage.push((key, value, mkvs.take_reads()));
+                }
+
+                let root = blk.state_root();
+                let account_proof =
+                    prove_entries(&storage_client, &call_option, &root, account_reads);
+                let storage_proof = future::join_all(
+                    storage
+                        .into_iter()
+                        .map(|(key, value, reads)| {
+                            prove_entries(&storage_client, &call_option, &root, reads)
+                                .map(move |proof| (key, value, proof))
+                        })
+                        .collect::<Vec<_>>(),
+                );
+
+                Ok(account_proof
+                    .join(storage_proof)
+                    .map(move |(account_proof, storage_proof)| AccountProof {
+                        address,
+                        balance,
+                        nonce,
+                        code_hash,
+                        state_root: root.hash.as_ref().into(),
+                        account_proof,
+                        storage_proof,
+                    }))
+            })
+            .flatten()
     }
 
     /// Simulate a transaction against a given block.
     ///
-    /// The simulated transaction is executed in a dedicated thread pool to
-    /// avoid blocking I/O processing.
+    /// Uses `simulate_transactions` internally.
     ///
     /// # Notes
     ///
     /// Confidential contracts are not supported.
     pub fn simulate_transaction(
         &self,
+        ctx: Arc<Context>,
         transaction: SignedTransaction,
         id: BlockId,
-    ) -> impl Future<Item = Executed, Error = CallError> {
-        let simulator_pool = self.simulator_pool.clone();
+        state_override: Option<StateOverride>,
+    ) -> impl Future<Item = Executed, Error = SimulationError> {
+        self.simulate_transactions(ctx, vec![transaction], id, state_override)
+            .and_then(|mut results| results.remove(0).map_err(SimulationError::from))
+    }
 
-        self.get_block(id)
-            .map_err(|_| CallError::StateCorrupt)
-            .and_then(|blk| match blk {
-                Some(blk) => Ok(blk),
-                None => Err(CallError::StatePruned),
-            })
-            .and_then(move |blk| {
-                // Execute simulation in a dedicated thread pool to avoid blocking
-                // I/O processing with simulations.
-                simulator_pool.spawn_handle(future::lazy(move || {
-                    let mut state = blk.state().map_err(|_| CallError::StateCorrupt)?;
-                    let env_info = EnvInfo {
-                        number: blk.snapshot.block.header.round + 1,
-                        author: Default::default(),
-                        timestamp: blk.snapshot.block.header.timestamp,
-                        difficulty: Default::default(),
-                        // TODO: Get 256 last hashes.
-                        last_hashes: Arc::new(vec![blk
-                            .snapshot
-                            .block
-                            .header
-                            .previous_hash
-                            .as_ref()
-                            .into()]),
-                        gas_used: Default::default(),
-                        gas_limit: U256::max_value(),
-                    };
-                    let machine = genesis::SPEC.engine.machine();
-                    let options = TransactOptions::with_no_tracing()
-                        .dont_check_nonce()
-                        .save_output_from_contract();
-
-                    Ok(Executive::new(&mut state, &env_info, machine)
-                        .transact_virtual(&transaction, options)?)
-                }))
-            })
+    /// Simulate a sequence of transactions against a given block.
+    ///
+    /// The transactions are executed in order on the same state, so each
+    /// transaction observes the effects of the preceding ones. A failing
+    /// transaction does not prevent the following ones from executing, but
+    /// all of them share the gas limit of a single block.
+    ///
+    /// Uses `simulate` internally.
+    ///
+    /// # Notes
+    ///
+    /// Confidential contracts are not supported.
+    pub fn simulate_transactions(
+        &self,
+        ctx: Arc<Context>,
+        transactions: Vec<SignedTransaction>,
+        id: BlockId,
+        state_override: Option<StateOverride>,
+    ) -> Box<
+        dyn Future<Item = Vec<std::result::Result<Executed, CallError>>, Error = SimulationError>
+            + Send,
+    > {
+        let max_bundle_size = self.simulator_config.max_bundle_size;
+        if transactions.len() > max_bundle_size {
+            return Box::new(future::err(SimulationError::TooManyTransactions {
+                max: max_bundle_size,
+            }));
+        }
+
+        self.simulate(ctx, id, state_override, move |state, env_info| {
+            execute_bundle(state, env_info, transactions)
+        })
     }
 
     /// Estimates gas against a given block.
     ///
-    /// Uses `simulate_transaction` internally.
+    /// Binary-searches the lowest gas limit at which the transaction executes
+    /// successfully, up to the lower of the RPC gas cap and the block gas limit.
+    /// Uses `simulate` internally.
     ///
     /// # Notes
     ///
     /// Confidential contracts are not supported.
     pub fn estimate_gas(
         &self,
+        ctx: Arc<Context>,
         transaction: SignedTransaction,
         id: BlockId,
-    ) -> impl Future<Item = U256, Error = CallError> {
-        self.simulate_transaction(transaction, id)
-            .map(|executed| executed.gas_used + executed.refunded)
+        state_override: Option<StateOverride>,
+    ) -> impl Future<Item = U256, Error = SimulationError> {
+        let cap = cmp::min(self.simulator_config.gas_cap, BLOCK_GAS_LIMIT.into());
+
+        self.simulate(ctx, id, state_override, move |state, env_info| {
+            estimate_transaction_gas(state, env_info, &transaction, cap)
+        })
+    }
+
+    /// Run a simulation against the state of a given block.
+    ///
+    /// The simulation is executed in a dedicated thread pool to avoid blocking
+    /// I/O processing. The block gas limit is set to the configured RPC gas cap
+    /// and simulations are rejected when too many are already pending. If a
+    /// state override set is given, it is applied to the block state before
+    /// the simulation runs.
+    fn simulate<T, F>(
+        &self,
+        ctx: Arc<Context>,
+        id: BlockId,
+        state_override: Option<StateOverride>,
+        simulation: F,
+    ) -> Box<dyn Future<Item = T, Error = SimulationError> + Send>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut State<NullBackend>, &EnvInfo) -> std::result::Result<T, SimulationError>
+            + Send
+            + 'static,
+    {
+        let slot = match SimulatorSlot::acquire(
+            &self.simulator_queue,
+            self.simulator_config.max_queue_size,
+        ) {
+            Some(slot) => slot,
+            None => {
+                SIMULATOR_REJECTED.inc();
+                return Box::new(future::err(SimulationError::QueueFull));
+            }
+        };
+
+        let simulator_pool = self.simulator_pool.clone();
+        let gas_cap = self.simulator_config.gas_cap;
+
+        let simulation = self
+            .get_block(id)
+            .map_err(|_| SimulationError::from(CallError::StateCorrupt))
+            .and_then(|blk| match blk {
+                Some(blk) => Ok(blk),
+                None => Err(SimulationError::from(CallError::StatePruned)),
+            })
+            .and_then(move |blk| {
+                // Execute simulation in a dedicated thread pool to avoid blocking
+                // I/O processing with simulations.
+                simulator_pool.spawn_handle(future::lazy(
+                    move || -> std::result::Result<T, SimulationError> {
+                        // Keep the queue slot until the simulation completes.
+                        let _slot = slot;
+
+                        let mut state = blk.state(ctx).map_err(|_| CallError::StateCorrupt)?;
+                        if let Some(ref state_override) = state_override {
+                            apply_state_override(&mut state, state_override)?;
+                        }
+                        let env_info = EnvInfo {
+                            number: blk.data.block.header.round + 1,
+                            author: Default::default(),
+                            timestamp: blk.data.block.header.timestamp,
+                            difficulty: Default::default(),
+                            // TODO: Get 256 last hashes.
+                            last_hashes: Arc::new(vec![blk
+                                .data
+                                .block
+                                .header
+                                .previous_hash
+                                .as_ref()
+                                .into()]),
+                            gas_used: Default::default(),
+                            gas_limit: gas_cap,
+                        };
+
+                        simulation(&mut state, &env_info)
+                    },
+                ))
+            });
+
+        Box::new(with_timeout(simulation, self.simulator_config.timeout))
     }
 
     /// Looks up logs based on the given filter.
@@ -327,25 +821,26 @@ impl Translator {
         &self,
         filter: Filter,
     ) -> impl Future<Item = Vec<LocalizedLogEntry>, Error = Error> {
-        // Resolve starting and ending blocks.
-        let client = self.client.clone();
+        // Resolve starting and ending blocks. The transactions are looked up
+        // on the node that indexed the ending block.
+        let client = self.client();
+        let query_client = client.clone();
         let blocks = future::join_all(vec![
             Box::new(self.get_block_unwrap(filter.from_block)) as BoxFuture<EthereumBlock>,
             Box::new(self.get_block_unwrap(filter.to_block).and_then(move |blk| {
                 client
                     .txn_client()
-                    .wait_block_indexed(blk.snapshot.block.header.round)
+                    .wait_block_indexed(blk.data.block.header.round)
                     .map(move |()| blk)
             })),
         ]);
 
         // Look up matching transactions.
         let f = filter.clone();
-        let client = self.client.clone();
         let txns = blocks.and_then(move |blks| {
-            client.txn_client().query_txs(Query {
-                round_min: blks[0].snapshot.block.header.round,
-                round_max: blks[1].snapshot.block.header.round,
+            query_client.txn_client().query_txs(Query {
+                round_min: blks[0].data.block.header.round,
+                round_max: blks[1].data.block.header.round,
                 conditions: {
                     let mut c = vec![];
                     // Transaction must emit logs for any of the given addresses.
@@ -481,6 +976,305 @@ impl Translator {
     }
 }
 
+/// Fetch MKVS proofs of the given storage entries from the storage node.
+fn prove_entries(
+    storage_client: &StorageClient,
+    call_option: &CallOption,
+    root: &Root,
+    entries: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+) -> impl Future<Item = Vec<ProofEntry>, Error = Error> {
+    future::join_all(
+        entries
+            .into_iter()
+            .map(|(key, value)| {
+                let request = GetRequest {
+                    tree: TreeID {
+                        root: root.clone(),
+                        position: root.hash,
+                    },
+                    key: key.clone(),
+                    include_siblings: false,
+                };
+
+                future::result(storage_client.sync_get(&request, call_option.clone()))
+                    .flatten()
+                    .map_err(Error::from)
+                    .map(move |response| ProofEntry {
+                        key,
+                        value,
+                        proof: response.proof,
+                    })
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Returns whether a request failed before it reached the node, so that it
+/// can safely be sent to another node.
+///
+/// Other errors, such as deadlines, may occur after the node received the
+/// request.
+fn is_unreached(err: &Error) -> bool {
+    match err.downcast_ref::<grpcio::Error>() {
+        Some(grpcio::Error::RpcFailure(ref status)) => match status.status {
+            RpcStatusCode::Unavailable => true,
+            _ => false,
+        },
+        Some(grpcio::Error::CallFailure(_)) => true,
+        _ => false,
+    }
+}
+
+/// Fail a simulation that does not complete within the given timeout.
+///
+/// The simulation thread cannot be interrupted, so a timed out simulation
+/// keeps its queue slot until it runs out of gas or completes.
+fn with_timeout<F>(
+    simulation: F,
+    timeout: Duration,
+) -> impl Future<Item = F::Item, Error = SimulationError>
+where
+    F: Future<Error = SimulationError>,
+{
+    Timeout::new(simulation, timeout).map_err(|err| match err.into_inner() {
+        Some(err) => err,
+        None => {
+            SIMULATOR_TIMEOUTS.inc();
+            SimulationError::Timeout
+        }
+    })
+}
+
+/// Execute a transaction on the given state without checking its signature or nonce.
+fn execute_transaction(
+    state: &mut State<NullBackend>,
+    env_info: &EnvInfo,
+    transaction: &SignedTransaction,
+) -> std::result::Result<Executed, CallError> {
+    let machine = genesis::SPEC.engine.machine();
+    let options = TransactOptions::with_no_tracing()
+        .dont_check_nonce()
+        .save_output_from_contract();
+
+    Executive::new(state, env_info, machine)
+        .transact_virtual(transaction, options)
+        .map_err(CallError::from)
+}
+
+/// Binary-search the lowest gas limit, up to `cap`, at which a transaction
+/// executes successfully on the given state.
+fn estimate_transaction_gas(
+    state: &mut State<NullBackend>,
+    env_info: &EnvInfo,
+    transaction: &SignedTransaction,
+    cap: U256,
+) -> std::result::Result<U256, SimulationError> {
+    // Each attempt is reverted so that all of them run on the same state.
+    let mut execute_with_gas = |gas: U256| {
+        state.checkpoint();
+        let result = execute_transaction(state, env_info, &with_gas(transaction, gas));
+        state.revert_to_checkpoint();
+        result
+    };
+
+    // Fail early if the transaction does not succeed even at the cap.
+    let executed = execute_with_gas(cap)?;
+    if let Some(exception) = executed.exception {
+        return Err(match exception {
+            VmError::Reverted => SimulationError::Reverted {
+                reason: decode_revert_reason(&executed.output),
+                output: executed.output,
+            },
+            VmError::OutOfGas => SimulationError::GasRequiredExceedsCap { cap },
+            exception => SimulationError::ExecutionFailed(format!("{}", exception)),
+        });
+    }
+
+    // Any gas limit below the gas consumed before refunds must fail, but
+    // the required limit may be higher (e.g., due to the 63/64 rule).
+    let mut lo = executed.gas_used + executed.refunded - 1;
+    let mut hi = cap;
+    while hi - lo > 1.into() {
+        let mid = lo + (hi - lo) / 2;
+        match execute_with_gas(mid) {
+            Ok(ref executed) if executed.exception.is_none() => hi = mid,
+            _ => lo = mid,
+        }
+    }
+
+    Ok(hi)
+}
+
+/// Execute a sequence of transactions on the given state, within the gas
+/// limit of a single block.
+///
+/// Each transaction is capped at the gas left in the block, and the bundle
+/// fails if the block runs out of gas before all transactions are executed.
+fn execute_bundle(
+    state: &mut State<NullBackend>,
+    env_info: &EnvInfo,
+    transactions: Vec<SignedTransaction>,
+) -> std::result::Result<Vec<std::result::Result<Executed, CallError>>, SimulationError> {
+    let mut env_info = env_info.clone();
+    let mut results = Vec::with_capacity(transactions.len());
+
+    for transaction in transactions {
+        let gas_left = env_info.gas_limit - env_info.gas_used;
+        if gas_left.is_zero() {
+            return Err(SimulationError::GasRequiredExceedsCap {
+                cap: env_info.gas_limit,
+            });
+        }
+
+        let transaction = if transaction.gas > gas_left {
+            with_gas(&transaction, gas_left)
+        } else {
+            transaction
+        };
+
+        let result = execute_transaction(state, &env_info, &transaction);
+        if let Ok(ref executed) = result {
+            env_info.gas_used = env_info.gas_used + executed.gas_used;
+        }
+        results.push(result);
+    }
+
+    Ok(results)
+}
+
+/// Returns a copy of a (fake-signed) transaction with a different gas limit.
+fn with_gas(transaction: &SignedTransaction, gas: U256) -> SignedTransaction {
+    let mut unsigned = transaction.as_unsigned().clone();
+    unsigned.gas = gas;
+    unsigned.fake_sign(transaction.sender())
+}
+
+/// Apply a state override set to the given state.
+///
+/// Accounts whose nonce or full storage (`state`) is overridden are replaced
+/// by a new account with empty storage, keeping their balance, code and
+/// storage expiry. As contract storage cannot be enumerated, the nonce of a
+/// contract can only be overridden together with its full storage.
+fn apply_state_override(
+    state: &mut State<NullBackend>,
+    state_override: &StateOverride,
+) -> std::result::Result<(), SimulationError> {
+    fn state_corrupt<E>(_err: E) -> SimulationError {
+        CallError::StateCorrupt.into()
+    }
+
+    for (address, account) in state_override {
+        let address: Address = address.clone().into();
+
+        if account.state.is_some() && account.state_diff.is_some() {
+            return Err(SimulationError::InvalidOverride(format!(
+                "both state and stateDiff given for {:?}",
+                address
+            )));
+        }
+
+        if account.nonce.is_some() || account.state.is_some() {
+            let code = state.code(&address).map_err(state_corrupt)?;
+            let has_code = code.as_ref().map_or(false, |code| !code.is_empty());
+            if has_code && account.state.is_none() {
+                return Err(SimulationError::InvalidOverride(format!(
+                    "nonce of contract {:?} can only be overridden together with state",
+                    address
+                )));
+            }
+
+            let nonce = match account.nonce {
+                Some(nonce) => {
+                    let nonce: u64 = nonce.into();
+                    U256::from(nonce)
+                }
+                None => state.nonce(&address).map_err(state_corrupt)?,
+            };
+            let balance = state.balance(&address).map_err(state_corrupt)?;
+            let storage_expiry = state.storage_expiry(&address).map_err(state_corrupt)?;
+
+            state.new_contract(&address, balance, nonce, storage_expiry);
+            if let Some(code) = code.filter(|_| has_code) {
+                state
+                    .init_code(&address, (*code).clone())
+                    .map_err(state_corrupt)?;
+            }
+        }
+
+        if let Some(ref balance) = account.balance {
+            let balance: U256 = balance.clone().into();
+            let current = state.balance(&address).map_err(state_corrupt)?;
+            if balance > current {
+                state
+                    .add_balance(&address, &(balance - current), CleanupMode::NoEmpty)
+                    .map_err(state_corrupt)?;
+            } else if balance < current {
+                state
+                    .sub_balance(&address, &(current - balance), &mut CleanupMode::NoEmpty)
+                    .map_err(state_corrupt)?;
+            }
+        }
+
+        if let Some(ref code) = account.code {
+            state
+                .init_code(&address, code.0.clone())
+                .map_err(state_corrupt)?;
+        }
+
+        let storage = account.state.iter().chain(account.state_diff.iter());
+        for (key, value) in storage.flat_map(|storage| storage.iter()) {
+            state
+                .set_storage(&address, key.clone().into(), value.clone().into())
+                .map_err(state_corrupt)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl ByteSize for LocalizedTransaction {
+    fn byte_size(&self) -> usize {
+        mem::size_of::<Self>() + self.data.len()
+    }
+}
+
+impl ByteSize for LocalizedReceipt {
+    fn byte_size(&self) -> usize {
+        mem::size_of::<Self>()
+            + self
+                .logs
+                .iter()
+                .map(|log| {
+                    mem::size_of_val(log)
+                        + log.entry.data.len()
+                        + log.entry.topics.len() * mem::size_of::<H256>()
+                })
+                .sum::<usize>()
+    }
+}
+
+/// Account and storage values of an address with MKVS proofs.
+pub struct AccountProof {
+    pub address: Address,
+    pub balance: U256,
+    pub nonce: U256,
+    pub code_hash: H256,
+    /// State root the proofs are against.
+    pub state_root: H256,
+    /// Proofs of the entries the account values were read from.
+    pub account_proof: Vec<ProofEntry>,
+    /// Storage keys and values with proofs of the entries they were read from.
+    pub storage_proof: Vec<(H256, H256, Vec<ProofEntry>)>,
+}
+
+/// MKVS storage entry with a proof of its value.
+pub struct ProofEntry {
+    pub key: Vec<u8>,
+    /// Value of the entry, `None` if the entry does not exist.
+    pub value: Option<Vec<u8>>,
+    pub proof: Proof,
+}
+
 /// A wrapper that exposes an Oasis Core transaction against the Eth/WASI runtime
 /// as an Ethereum transaction.
 pub struct EthereumTransaction {
@@ -574,38 +1368,89 @@ impl EthereumTransaction {
     }
 }
 
+/// Block data which does not depend on the node it was fetched from, and can
+/// be cached.
+#[derive(Clone)]
+struct BlockData {
+    block: Block,
+    block_hash: Hash,
+}
+
+impl BlockData {
+    fn new(snapshot: BlockSnapshot) -> Self {
+        Self {
+            block: snapshot.block,
+            block_hash: snapshot.block_hash,
+        }
+    }
+
+    fn round(&self) -> u64 {
+        self.block.header.round
+    }
+
+    fn hash(&self) -> H256 {
+        self.block_hash.as_ref().into()
+    }
+}
+
+impl ByteSize for BlockData {
+    /// Transactions and state of a block are read from the node on demand,
+    /// so they are not counted.
+    fn byte_size(&self) -> usize {
+        mem::size_of::<Self>()
+    }
+}
+
 /// A wrapper that exposes an Oasis Core block generated by the Eth/WASI runtime
-/// as an Ethereum block.
+/// as an Ethereum block, whose transactions and state are read from the node
+/// it is bound to.
+#[derive(Clone)]
 pub struct EthereumBlock {
-    snapshot: BlockSnapshot,
-    client: Arc<EthereumRuntimeClient>,
+    data: BlockData,
+    node: Arc<NodeClient>,
 }
 
 impl EthereumBlock {
-    /// Create a new Ethereum block from an Oasis Core block snapshot.
-    pub fn new(snapshot: BlockSnapshot, client: Arc<EthereumRuntimeClient>) -> Self {
-        Self { snapshot, client }
+    fn new(data: BlockData, node: Arc<NodeClient>) -> Self {
+        Self { data, node }
     }
 
     /// Ethereum block number.
     pub fn number(&self) -> U256 {
-        self.snapshot.block.header.round.into()
+        self.data.round().into()
     }
 
     /// Ethereum block number as an u64.
     pub fn number_u64(&self) -> u64 {
-        self.snapshot.block.header.round
+        self.data.round()
     }
 
     /// Block hash.
     pub fn hash(&self) -> H256 {
-        self.snapshot.block_hash.as_ref().into()
+        self.data.hash()
+    }
+
+    /// Block timestamp (seconds since the epoch).
+    pub fn timestamp(&self) -> u64 {
+        self.data.block.header.timestamp
+    }
+
+    /// Ethereum state snapshot at given block, whose reads are done within
+    /// the given context.
+    pub fn state(&self, ctx: Arc<Context>) -> Result<State<NullBackend>> {
+        self.state_with_mkvs(Box::new(self.mkvs(ctx)))
+    }
+
+    /// MKVS of the state at given block, whose reads are done within the
+    /// given context.
+    fn mkvs(&self, ctx: Arc<Context>) -> BlockStateMKVS {
+        BlockStateMKVS::new(self.node.storage_client.clone(), self.state_root(), ctx)
     }
 
-    /// Ethereum state snapshot at given block.
-    pub fn state(&self) -> Result<State<NullBackend>> {
+    /// Ethereum state snapshot at given block, backed by the given MKVS.
+    fn state_with_mkvs(&self, mkvs: Box<dyn ethcore::mkvs::MKVS>) -> Result<State<NullBackend>> {
         Ok(State::from_existing(
-            Box::new(BlockSnapshotMKVS(self.snapshot.clone())),
+            mkvs,
             NullBackend,
             U256::zero(),       /* account_start_nonce */
             Default::default(), /* factories */
@@ -613,16 +1458,26 @@ impl EthereumBlock {
         )?)
     }
 
+    /// MKVS state root at given block.
+    fn state_root(&self) -> Root {
+        let header = &self.data.block.header;
+
+        Root {
+            namespace: header.namespace,
+            version: header.round,
+            root_type: RootType::State,
+            hash: header.state_root,
+        }
+    }
+
     /// Raw Oasis Core transactions in a block corresponding to Ethereum transactions.
     pub fn raw_transactions(
         &self,
     ) -> impl Future<Item = impl Iterator<Item = TxnCall>, Error = Error> {
-        self.client
+        self.node
+            .client
             .txn_client()
-            .get_txs(
-                self.snapshot.block.header.round,
-                self.snapshot.block.header.io_root,
-            )
+            .get_txs(self.data.round(), self.data.block.header.io_root)
             .map(|txns| {
                 txns.0.into_iter().filter_map(|txn| {
                     let txn: TxnCall = cbor::from_slice(&txn).ok()?;
@@ -651,8 +1506,8 @@ impl EthereumBlock {
 
     /// Retrieve an Ethereum header with additional metadata.
     pub fn rich_header(&self) -> EthRpcRichHeader {
-        let header = self.snapshot.block.header.clone();
-        let block_hash = self.snapshot.block_hash;
+        let header = self.data.block.header.clone();
+        let block_hash = self.data.block_hash;
 
         // Generate header metadata.
         EthRpcRichHeader {
@@ -699,8 +1554,8 @@ impl EthereumBlock {
         &self,
         include_txns: bool,
     ) -> impl Future<Item = EthRpcRichBlock, Error = Error> {
-        let header = self.snapshot.block.header.clone();
-        let block_hash = self.snapshot.block_hash;
+        let header = self.data.block.header.clone();
+        let block_hash = self.data.block_hash;
         let rich_header = self.rich_header();
 
         self.transactions().and_then(move |txns| {
@@ -753,26 +1608,650 @@ impl EthereumBlock {
     }
 }
 
+/// Read syncer fetching state from the storage of a node.
+struct StorageReadSyncer(StorageClient);
+
+impl ReadSync for StorageReadSyncer {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn sync_get(&mut self, ctx: Context, request: GetRequest) -> Result<ProofResponse> {
+        Ok(self
+            .0
+            .sync_get(&request, context::call_option(&ctx))?
+            .wait()?)
+    }
+
+    fn sync_get_prefixes(
+        &mut self,
+        ctx: Context,
+        request: GetPrefixesRequest,
+    ) -> Result<ProofResponse> {
+        Ok(self
+            .0
+            .sync_get_prefixes(&request, context::call_option(&ctx))?
+            .wait()?)
+    }
+
+    fn sync_iterate(&mut self, ctx: Context, request: IterateRequest) -> Result<ProofResponse> {
+        Ok(self
+            .0
+            .sync_iterate(&request, context::call_option(&ctx))?
+            .wait()?)
+    }
+}
+
+/// MKVS of the state of a block, read from the storage of a node within the
+/// context of the request that started the reads.
+struct BlockStateMKVS {
+    tree: Tree,
+    storage_client: StorageClient,
+    root: Root,
+    ctx: Arc<Context>,
+}
+
+impl BlockStateMKVS {
+    fn new(storage_client: StorageClient, root: Root, ctx: Arc<Context>) -> Self {
+        let tree = Tree::make()
+            .with_root(root.clone())
+            .new(Box::new(StorageReadSyncer(storage_client.clone())));
+
+        Self {
+            tree,
+            storage_client,
+            root,
+            ctx,
+        }
+    }
+}
+
+impl Clone for BlockStateMKVS {
+    fn clone(&self) -> Self {
+        Self::new(
+            self.storage_client.clone(),
+            self.root.clone(),
+            self.ctx.clone(),
+        )
+    }
+}
+
+impl ethcore::mkvs::MKVS for BlockStateMKVS {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        MKVS::get(&self.tree, Context::create_child(&self.ctx), key)
+    }
+
+    fn insert(&mut self, key: &[u8], value: &[u8]) -> Option<Vec<u8>> {
+        MKVS::insert(&mut self.tree, Context::create_child(&self.ctx), key, value)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        MKVS::remove(&mut self.tree, Context::create_child(&self.ctx), key)
+    }
+
+    fn boxed_clone(&self) -> Box<dyn ethcore::mkvs::MKVS> {
+        Box::new(self.clone())
+    }
+}
+
+/// MKVS wrapper that records the entries read through it.
 #[derive(Clone)]
-struct BlockSnapshotMKVS(BlockSnapshot);
+struct RecordingMKVS {
+    inner: BlockStateMKVS,
+    reads: Arc<Mutex<BTreeMap<Vec<u8>, Option<Vec<u8>>>>>,
+}
+
+impl RecordingMKVS {
+    fn new(inner: BlockStateMKVS) -> Self {
+        Self {
+            inner,
+            reads: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
 
-impl ethcore::mkvs::MKVS for BlockSnapshotMKVS {
+    /// Take the entries read since the last call.
+    fn take_reads(&self) -> BTreeMap<Vec<u8>, Option<Vec<u8>>> {
+        mem::replace(&mut *self.reads.lock(), BTreeMap::new())
+    }
+}
+
+impl ethcore::mkvs::MKVS for RecordingMKVS {
     fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
-        // TODO: Use proper context.
-        MKVS::get(&self.0, Context::background(), key)
+        let value = ethcore::mkvs::MKVS::get(&self.inner, key);
+        self.reads.lock().insert(key.to_vec(), value.clone());
+        value
     }
 
     fn insert(&mut self, key: &[u8], value: &[u8]) -> Option<Vec<u8>> {
-        // TODO: Use proper context.
-        MKVS::insert(&mut self.0, Context::background(), key, value)
+        ethcore::mkvs::MKVS::insert(&mut self.inner, key, value)
     }
 
     fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
-        // TODO: Use proper context.
-        MKVS::remove(&mut self.0, Context::background(), key)
+        ethcore::mkvs::MKVS::remove(&mut self.inner, key)
     }
 
     fn boxed_clone(&self) -> Box<dyn ethcore::mkvs::MKVS> {
         Box::new(self.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{collections::HashMap, sync::mpsc};
+
+    use ethcore::transaction::Transaction;
+    use grpcio::Environment;
+    use hex;
+    use oasis_core_runtime::common::namespace::Namespace;
+    use serde_json;
+    use tokio::runtime::Runtime;
+
+    /// In-memory MKVS backing the state of simulation tests.
+    #[derive(Clone, Default)]
+    struct MemoryMKVS(Arc<Mutex<HashMap<Vec<u8>, Vec<u8>>>>);
+
+    impl ethcore::mkvs::MKVS for MemoryMKVS {
+        fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+            self.0.lock().get(key).cloned()
+        }
+
+        fn insert(&mut self, key: &[u8], value: &[u8]) -> Option<Vec<u8>> {
+            self.0.lock().insert(key.to_vec(), value.to_vec())
+        }
+
+        fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+            self.0.lock().remove(key)
+        }
+
+        fn boxed_clone(&self) -> Box<dyn ethcore::mkvs::MKVS> {
+            Box::new(self.clone())
+        }
+    }
+
+    /// Token contract: a call with data approves the spender, and a call
+    /// without data swaps, reverting unless approved.
+    ///
+    /// ```text
+    /// CALLDATASIZE ISZERO PUSH1 0x0b JUMPI     ; no data => swap
+    /// PUSH1 1 PUSH1 0 SSTORE STOP              ; approve
+    /// JUMPDEST PUSH1 0 SLOAD PUSH1 0x17 JUMPI  ; swap, if approved
+    /// PUSH1 0 PUSH1 0 REVERT
+    /// JUMPDEST STOP
+    /// ```
+    const TOKEN_CODE: &str = "3615600b576001600055005b600054601757600060006000fd5b00";
+
+    /// Contract setting and then clearing a storage slot, which is refunded.
+    ///
+    /// ```text
+    /// PUSH1 1 PUSH1 0 SSTORE PUSH1 0 PUSH1 0 SSTORE STOP
+    /// ```
+    const REFUND_CODE: &str = "6001600055600060005500";
+
+    /// Contract reverting unless at least 30000 gas is left.
+    ///
+    /// ```text
+    /// PUSH2 30000 GAS LT PUSH1 0x09 JUMPI STOP
+    /// JUMPDEST PUSH1 0 PUSH1 0 REVERT
+    /// ```
+    const GAS_CHECK_CODE: &str = "6175305a10600957005b60006000fd";
+
+    /// Contract reverting unless at least 78995 gas is left, which only
+    /// happens with a gas limit of at least 100000.
+    ///
+    /// ```text
+    /// PUSH3 78995 GAS LT PUSH1 0x0a JUMPI STOP
+    /// JUMPDEST PUSH1 0 PUSH1 0 REVERT
+    /// ```
+    const HIGH_GAS_CHECK_CODE: &str = "620134935a10600a57005b60006000fd";
+
+    fn sender() -> Address {
+        Address::from(0x1000)
+    }
+
+    fn contract() -> Address {
+        Address::from(0x2000)
+    }
+
+    fn empty_state() -> State<NullBackend> {
+        State::from_existing(
+            Box::new(MemoryMKVS::default()),
+            NullBackend,
+            U256::zero(),       /* account_start_nonce */
+            Default::default(), /* factories */
+            None,               /* confidential_ctx */
+        )
+        .unwrap()
+    }
+
+    /// Returns a state with the given code deployed at `contract()`.
+    fn state_with_code(code: &str) -> State<NullBackend> {
+        let mut state = empty_state();
+        state
+            .init_code(&contract(), hex::decode(code).unwrap())
+            .unwrap();
+        state
+    }
+
+    fn env_info(gas_limit: u64) -> EnvInfo {
+        EnvInfo {
+            number: 1,
+            author: Default::default(),
+            timestamp: 0,
+            difficulty: Default::default(),
+            last_hashes: Arc::new(vec![]),
+            gas_used: Default::default(),
+            gas_limit: gas_limit.into(),
+        }
+    }
+
+    fn call(to: Address, data: Vec<u8>, gas: u64) -> SignedTransaction {
+        Transaction {
+            nonce: Default::default(),
+            gas_price: Default::default(),
+            gas: gas.into(),
+            action: Action::Call(to),
+            value: Default::default(),
+            data,
+        }
+        .fake_sign(sender())
+    }
+
+    fn is_success(result: &std::result::Result<Executed, CallError>) -> bool {
+        match result {
+            Ok(executed) => executed.exception.is_none(),
+            Err(_) => false,
+        }
+    }
+
+    #[test]
+    fn should_reject_simulations_when_queue_is_full() {
+        let queue = Arc::new(AtomicUsize::new(0));
+
+        let first = SimulatorSlot::acquire(&queue, 2).expect("queue must have room");
+        let second = SimulatorSlot::acquire(&queue, 2).expect("queue must have room");
+        assert!(SimulatorSlot::acquire(&queue, 2).is_none());
+        assert_eq!(queue.load(Ordering::SeqCst), 2);
+
+        // Completed simulations release their slots.
+        drop(first);
+        assert_eq!(queue.load(Ordering::SeqCst), 1);
+        let third = SimulatorSlot::acquire(&queue, 2).expect("queue must have room");
+        assert!(SimulatorSlot::acquire(&queue, 2).is_none());
+
+        drop(second);
+        drop(third);
+        assert_eq!(queue.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn should_time_out_simulations() {
+        let queue = Arc::new(AtomicUsize::new(0));
+        let pool = ThreadPoolBuilder::new().pool_size(1).build();
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+
+        // Simulation blocking until it is released.
+        let slot = SimulatorSlot::acquire(&queue, 1).unwrap();
+        let simulation = pool.spawn_handle(future::lazy(
+            move || -> std::result::Result<(), SimulationError> {
+                let _slot = slot;
+                let _ = done_rx.recv();
+                Ok(())
+            },
+        ));
+
+        let timeouts = SIMULATOR_TIMEOUTS.get();
+        let mut runtime = Runtime::new().unwrap();
+        let result = runtime.block_on(with_timeout(simulation, Duration::from_millis(50)));
+        match result {
+            Err(SimulationError::Timeout) => (),
+            result => panic!("expected simulation to time out, got {:?}", result),
+        }
+        assert!(SIMULATOR_TIMEOUTS.get() > timeouts);
+
+        // The timed out simulation keeps its slot until it completes.
+        assert!(SimulatorSlot::acquire(&queue, 1).is_none());
+        done_tx.send(()).unwrap();
+        pool.shutdown_on_idle().wait().unwrap();
+        assert_eq!(queue.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn should_not_time_out_completed_simulations() {
+        let mut runtime = Runtime::new().unwrap();
+        let result = runtime.block_on(with_timeout(
+            future::ok::<_, SimulationError>(42),
+            Duration::from_secs(5),
+        ));
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    #[test]
+    fn should_execute_dependent_transactions_in_order() {
+        let mut state = state_with_code(TOKEN_CODE);
+        let approve = call(contract(), vec![1], 100_000);
+        let swap = call(contract(), vec![], 100_000);
+
+        // The swap only succeeds after the approval.
+        let results = execute_bundle(&mut state, &env_info(1_000_000), vec![swap.clone()]).unwrap();
+        assert!(!is_success(&results[0]));
+
+        let results =
+            execute_bundle(&mut state, &env_info(1_000_000), vec![approve, swap]).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(is_success));
+    }
+
+    #[test]
+    fn should_share_block_gas_limit_in_bundle() {
+        let mut state = empty_state();
+        let transfer = call(Address::from(0x3000), vec![], 100_000);
+
+        // Each transfer uses 21000 gas and is capped at the gas left.
+        let results = execute_bundle(
+            &mut state,
+            &env_info(40_000),
+            vec![transfer.clone(), transfer.clone()],
+        )
+        .unwrap();
+        assert!(is_success(&results[0]));
+        assert_eq!(results[0].as_ref().unwrap().gas_used, 21_000.into());
+        assert!(!is_success(&results[1]));
+
+        // The bundle fails once the block is out of gas.
+        match execute_bundle(
+            &mut state,
+            &env_info(42_000),
+            vec![transfer.clone(), transfer.clone(), transfer],
+        ) {
+            Err(SimulationError::GasRequiredExceedsCap { cap }) => assert_eq!(cap, 42_000.into()),
+            result => panic!("expected bundle to run out of gas, got {:?}", result),
+        }
+    }
+
+    /// Check that the call succeeds at the estimated gas, but not below it.
+    fn assert_lowest_gas(
+        state: &mut State<NullBackend>,
+        transaction: &SignedTransaction,
+        gas: U256,
+    ) {
+        let env_info = env_info(1_000_000);
+        let executed = execute_transaction(state, &env_info, &with_gas(transaction, gas)).unwrap();
+        assert!(executed.exception.is_none());
+        let executed =
+            execute_transaction(state, &env_info, &with_gas(transaction, gas - 1)).unwrap();
+        assert!(executed.exception.is_some());
+    }
+
+    #[test]
+    fn should_estimate_gas_of_refunded_calls() {
+        let mut state = state_with_code(REFUND_CODE);
+        let transaction = call(contract(), vec![], 0);
+
+        let gas = estimate_transaction_gas(
+            &mut state,
+            &env_info(1_000_000),
+            &transaction,
+            1_000_000.into(),
+        )
+        .unwrap();
+
+        // The gas limit must cover the gas used before the refund.
+        let executed = execute_transaction(
+            &mut state,
+            &env_info(1_000_000),
+            &with_gas(&transaction, gas),
+        )
+        .unwrap();
+        assert!(executed.refunded > U256::zero());
+        assert!(gas >= executed.gas_used + executed.refunded);
+        assert_lowest_gas(&mut state, &transaction, gas);
+    }
+
+    #[test]
+    fn should_estimate_gas_of_calls_reverting_at_lower_gas() {
+        let mut state = state_with_code(GAS_CHECK_CODE);
+        let transaction = call(contract(), vec![], 0);
+
+        let gas = estimate_transaction_gas(
+            &mut state,
+            &env_info(1_000_000),
+            &transaction,
+            1_000_000.into(),
+        )
+        .unwrap();
+
+        // The call uses far less gas than it requires to succeed.
+        let executed = execute_transaction(
+            &mut state,
+            &env_info(1_000_000),
+            &with_gas(&transaction, gas),
+        )
+        .unwrap();
+        assert!(gas > executed.gas_used + 20_000);
+        assert_lowest_gas(&mut state, &transaction, gas);
+    }
+
+    #[test]
+    fn should_estimate_gas_of_calls_succeeding_at_cap() {
+        let mut state = state_with_code(HIGH_GAS_CHECK_CODE);
+        let transaction = call(contract(), vec![], 0);
+
+        let gas = estimate_transaction_gas(
+            &mut state,
+            &env_info(1_000_000),
+            &transaction,
+            100_000.into(),
+        )
+        .unwrap();
+        assert_eq!(gas, 100_000.into());
+        assert_lowest_gas(&mut state, &transaction, gas);
+
+        // Below the cap, the call always reverts.
+        match estimate_transaction_gas(
+            &mut state,
+            &env_info(1_000_000),
+            &transaction,
+            99_999.into(),
+        ) {
+            Err(SimulationError::Reverted { .. }) => (),
+            result => panic!("expected call to revert, got {:?}", result),
+        }
+    }
+
+    fn apply(
+        state: &mut State<NullBackend>,
+        state_override: &str,
+    ) -> std::result::Result<(), SimulationError> {
+        apply_state_override(state, &serde_json::from_str(state_override).unwrap())
+    }
+
+    #[test]
+    fn should_override_balance_and_code() {
+        let mut state = empty_state();
+        state
+            .add_balance(&sender(), &1000.into(), CleanupMode::NoEmpty)
+            .unwrap();
+
+        apply(
+            &mut state,
+            r#"{
+                "0x0000000000000000000000000000000000001000": {"balance": "0x10"},
+                "0x0000000000000000000000000000000000002000": {"balance": "0x2000", "code": "0x6001"}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(state.balance(&sender()).unwrap(), 0x10.into());
+        assert_eq!(state.balance(&contract()).unwrap(), 0x2000.into());
+        assert_eq!(*state.code(&contract()).unwrap().unwrap(), vec![0x60, 0x01]);
+    }
+
+    #[test]
+    fn should_override_storage() {
+        let mut state = state_with_code(TOKEN_CODE);
+        state
+            .set_storage(&contract(), H256::from(1), H256::from(1))
+            .unwrap();
+        state
+            .set_storage(&contract(), H256::from(2), H256::from(2))
+            .unwrap();
+        state.commit().unwrap();
+
+        // stateDiff only patches the given slots.
+        apply(
+            &mut state,
+            r#"{"0x0000000000000000000000000000000000002000": {"stateDiff": {
+                "0x0000000000000000000000000000000000000000000000000000000000000001":
+                "0x0000000000000000000000000000000000000000000000000000000000000005"
+            }}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            state.storage_at(&contract(), &H256::from(1)).unwrap(),
+            H256::from(5)
+        );
+        assert_eq!(
+            state.storage_at(&contract(), &H256::from(2)).unwrap(),
+            H256::from(2)
+        );
+
+        // state replaces the whole storage, keeping the code.
+        apply(
+            &mut state,
+            r#"{"0x0000000000000000000000000000000000002000": {"state": {
+                "0x0000000000000000000000000000000000000000000000000000000000000003":
+                "0x0000000000000000000000000000000000000000000000000000000000000003"
+            }}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            state.storage_at(&contract(), &H256::from(1)).unwrap(),
+            H256::zero()
+        );
+        assert_eq!(
+            state.storage_at(&contract(), &H256::from(2)).unwrap(),
+            H256::zero()
+        );
+        assert_eq!(
+            state.storage_at(&contract(), &H256::from(3)).unwrap(),
+            H256::from(3)
+        );
+        assert_eq!(
+            *state.code(&contract()).unwrap().unwrap(),
+            hex::decode(TOKEN_CODE).unwrap()
+        );
+
+        assert!(apply(
+            &mut state,
+            r#"{"0x0000000000000000000000000000000000002000": {"state": {}, "stateDiff": {}}}"#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn should_override_nonce() {
+        let mut state = empty_state();
+        state
+            .add_balance(&sender(), &1000.into(), CleanupMode::NoEmpty)
+            .unwrap();
+        state.inc_nonce(&sender()).unwrap();
+
+        // Nonces are set directly, whether they increase or decrease.
+        apply(
+            &mut state,
+            r#"{"0x0000000000000000000000000000000000001000": {"nonce": "0xffffffffff"}}"#,
+        )
+        .unwrap();
+        assert_eq!(state.nonce(&sender()).unwrap(), 0xff_ffff_ffffu64.into());
+        assert_eq!(state.balance(&sender()).unwrap(), 1000.into());
+
+        apply(
+            &mut state,
+            r#"{"0x0000000000000000000000000000000000001000": {"nonce": "0x0"}}"#,
+        )
+        .unwrap();
+        assert_eq!(state.nonce(&sender()).unwrap(), U256::zero());
+
+        // Contract storage would be lost when replacing the account.
+        let mut state = state_with_code(TOKEN_CODE);
+        match apply(
+            &mut state,
+            r#"{"0x0000000000000000000000000000000000002000": {"nonce": "0x5"}}"#,
+        ) {
+            Err(SimulationError::InvalidOverride(_)) => (),
+            result => panic!("expected invalid override, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn should_fail_over_only_unreached_requests() {
+        let rpc_failure = |code| {
+            Error::from(grpcio::Error::RpcFailure(grpcio::RpcStatus::new(
+                code, None,
+            )))
+        };
+
+        assert!(is_unreached(&rpc_failure(RpcStatusCode::Unavailable)));
+        assert!(!is_unreached(&rpc_failure(RpcStatusCode::DeadlineExceeded)));
+        assert!(!is_unreached(&rpc_failure(RpcStatusCode::Cancelled)));
+        assert!(!is_unreached(&rpc_failure(RpcStatusCode::Internal)));
+        assert!(!is_unreached(&Error::from(grpcio::Error::RemoteStopped)));
+        assert!(!is_unreached(&anyhow!("transaction error")));
+    }
+
+    #[test]
+    fn should_read_cached_blocks_from_healthy_nodes() {
+        // The nodes are not reached, as the blocks are cached.
+        let env = Arc::new(Environment::new(1));
+        let nodes = Arc::new(
+            Nodes::new(
+                (0..2)
+                    .map(|i| {
+                        let address = format!("127.0.0.1:{}", 10000 + i);
+                        NodeClient::connect(env.clone(), &address, Namespace::default())
+                    })
+                    .collect(),
+            )
+            .unwrap(),
+        );
+        let (original, other) = (nodes.all()[0].clone(), nodes.all()[1].clone());
+        nodes.set_healthy(&original, true);
+        nodes.set_healthy(&other, true);
+        let translator = Translator::new(
+            nodes.clone(),
+            U256::zero(),
+            Default::default(),
+            Default::default(),
+        );
+
+        // The block was fetched from the original node, which is now down.
+        let mut block = Block::default();
+        block.header.round = 5;
+        let data = BlockData {
+            block,
+            block_hash: Hash::default(),
+        };
+        translator.blocks_by_round.insert(5, data.clone());
+        translator.blocks_by_hash.insert(data.hash(), 5);
+        *translator.latest_block.write() = Some((data.clone(), Instant::now()));
+        nodes.mark_unhealthy(&original);
+
+        for _ in 0..4 {
+            let blocks = vec![
+                translator.get_block_by_round(5).wait().unwrap().unwrap(),
+                translator
+                    .get_block_by_hash(data.hash())
+                    .wait()
+                    .unwrap()
+                    .unwrap(),
+                translator.get_latest_block().wait().unwrap(),
+            ];
+            for block in blocks {
+                assert_eq!(block.number_u64(), 5);
+                assert!(Arc::ptr_eq(&block.node, &other));
+            }
+        }
+    }
+}
@@ -1,10 +1,25 @@
-use std::fmt;
+use std::{convert::TryFrom, fmt};
 
 use anyhow::Error;
 use ethcore::ids::BlockId;
 use ethereum_types::U256;
 use jsonrpc_core::{self, ErrorCode, Value};
-use parity_rpc::v1::{helpers::errors::codes, types::BlockNumber};
+use parity_rpc::v1::{
+    helpers::errors::{self, codes},
+    types::BlockNumber,
+};
+
+use crate::{translator::SimulationError, validation::ValidationError};
+
+/// Function selector of `Error(string)`, used to encode revert reasons.
+const REVERT_REASON_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// EIP-1474 error code for invalid input.
+pub const ERROR_INVALID_INPUT: i64 = -32000;
+/// EIP-1474 error code for rejected transactions.
+pub const ERROR_TRANSACTION_REJECTED: i64 = -32003;
+/// EIP-1474 error code for exceeded limits.
+pub const ERROR_LIMIT_EXCEEDED: i64 = -32005;
 
 pub fn gwei_to_wei(gwei: u64) -> U256 {
     U256::from(gwei).saturating_mul(U256::from(1_000_000_000))
@@ -39,3 +54,113 @@ pub fn execution_error<T: fmt::Display>(data: T) -> jsonrpc_core::Error {
         data: Some(Value::String(format!("{}", data))),
     }
 }
+
+/// Constructs a JSON-RPC error for a failed transaction submission.
+///
+/// Transactions rejected by local validation get an EIP-1474 error code,
+/// all other errors are reported as execution errors.
+pub fn send_transaction_error(err: Error) -> jsonrpc_core::Error {
+    match err.downcast_ref::<ValidationError>() {
+        Some(validation_err) => jsonrpc_core::Error {
+            code: ErrorCode::ServerError(validation_err.code()),
+            message: format!("{}", validation_err),
+            data: None,
+        },
+        None => execution_error(err),
+    }
+}
+
+/// Constructs a JSON-RPC error for a failed transaction simulation.
+pub fn simulation_error(err: SimulationError) -> jsonrpc_core::Error {
+    match err {
+        SimulationError::Call(err) => errors::call(err),
+        SimulationError::InvalidOverride(_) => {
+            jsonrpc_core::Error::invalid_params(format!("{}", err))
+        }
+        SimulationError::Reverted { ref output, .. } => jsonrpc_core::Error {
+            code: ErrorCode::ServerError(codes::EXECUTION_ERROR),
+            message: format!("{}", err),
+            data: Some(Value::String(format!("0x{}", hex::encode(output)))),
+        },
+        SimulationError::ExecutionFailed(_) => jsonrpc_core::Error {
+            code: ErrorCode::ServerError(codes::EXECUTION_ERROR),
+            message: format!("{}", err),
+            data: None,
+        },
+        err => jsonrpc_core::Error {
+            code: ErrorCode::ServerError(ERROR_LIMIT_EXCEEDED),
+            message: format!("{}", err),
+            data: None,
+        },
+    }
+}
+
+/// Decodes a Solidity revert reason (`Error(string)`) from call output.
+pub fn decode_revert_reason(output: &[u8]) -> Option<String> {
+    if output.len() < 4 || output[..4] != REVERT_REASON_SELECTOR {
+        return None;
+    }
+
+    // The ABI-encoded string is an offset to its length, followed by its bytes.
+    let data = &output[4..];
+    let offset = read_abi_usize(data, 0)?;
+    let len = read_abi_usize(data, offset)?;
+    let start = offset.checked_add(32)?;
+    let bytes = data.get(start..start.checked_add(len)?)?;
+
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// Reads an ABI-encoded 256-bit word at the given position as an usize.
+fn read_abi_usize(data: &[u8], pos: usize) -> Option<usize> {
+    let word = data.get(pos..pos.checked_add(32)?)?;
+    if word[..24].iter().any(|b| *b != 0) {
+        return None;
+    }
+
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&word[24..]);
+    usize::try_from(u64::from_be_bytes(value)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn abi_word(value: usize) -> Vec<u8> {
+        let mut word = vec![0u8; 24];
+        word.extend_from_slice(&(value as u64).to_be_bytes());
+        word
+    }
+
+    #[test]
+    fn test_decode_revert_reason() {
+        let mut output = REVERT_REASON_SELECTOR.to_vec();
+        output.extend(abi_word(32));
+        output.extend(abi_word(4));
+        output.extend_from_slice(b"boom");
+        output.extend(vec![0u8; 28]);
+
+        assert_eq!(decode_revert_reason(&output), Some("boom".to_owned()));
+    }
+
+    #[test]
+    fn test_decode_revert_reason_invalid() {
+        // No output.
+        assert_eq!(decode_revert_reason(&[]), None);
+
+        // Wrong selector.
+        let mut output = vec![0u8; 4];
+        output.extend(abi_word(32));
+        output.extend(abi_word(4));
+        output.extend_from_slice(b"boom");
+        assert_eq!(decode_revert_reason(&output), None);
+
+        // Truncated string.
+        let mut output = REVERT_REASON_SELECTOR.to_vec();
+        output.extend(abi_word(32));
+        output.extend(abi_word(8));
+        output.extend_from_slice(b"boom");
+        assert_eq!(decode_revert_reason(&output), None);
+    }
+}
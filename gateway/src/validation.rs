@@ -0,0 +1,172 @@
+//! Local transaction validation.
+//!
+//! Performs the same checks as the runtime before a raw transaction is
+//! submitted, so that obviously invalid transactions are rejected without
+//! a round trip through consensus.
+use ethcore::transaction::{SignedTransaction, UnverifiedTransaction};
+use ethereum_types::U256;
+use oasis_ethwasi_runtime_common::{genesis, BLOCK_GAS_LIMIT, MIN_GAS_PRICE_GWEI};
+use thiserror::Error;
+
+use crate::util::{ERROR_INVALID_INPUT, ERROR_LIMIT_EXCEEDED, ERROR_TRANSACTION_REJECTED};
+
+/// Transaction validation error.
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    #[error("requested gas {gas} greater than block gas limit {limit}")]
+    TooMuchGas { gas: U256, limit: U256 },
+    #[error("insufficient gas price: got {got}, minimum is {minimum}")]
+    GasPrice { got: U256, minimum: U256 },
+    #[error("invalid chain id: got {got}, expected {expected}")]
+    ChainId { got: u64, expected: u64 },
+    #[error("nonce too low: got {got}, expected {expected}")]
+    NonceTooLow { got: U256, expected: U256 },
+    #[error("insufficient funds: balance {balance}, cost {cost}")]
+    InsufficientFunds { balance: U256, cost: U256 },
+}
+
+impl ValidationError {
+    /// EIP-1474 JSON-RPC error code for the validation error.
+    pub fn code(&self) -> i64 {
+        match self {
+            ValidationError::TooMuchGas { .. } => ERROR_LIMIT_EXCEEDED,
+            ValidationError::ChainId { .. } => ERROR_INVALID_INPUT,
+            ValidationError::GasPrice { .. }
+            | ValidationError::NonceTooLow { .. }
+            | ValidationError::InsufficientFunds { .. } => ERROR_TRANSACTION_REJECTED,
+        }
+    }
+}
+
+/// Check an unverified transaction before its signature is verified.
+pub fn check_unverified(txn: &UnverifiedTransaction) -> Result<(), ValidationError> {
+    // Check that gas < block gas limit.
+    let limit = U256::from(BLOCK_GAS_LIMIT);
+    let gas = txn.as_unsigned().gas;
+    if gas > limit {
+        return Err(ValidationError::TooMuchGas { gas, limit });
+    }
+
+    Ok(())
+}
+
+/// Check a signed transaction independently of any state.
+pub fn check_signed(txn: &SignedTransaction) -> Result<(), ValidationError> {
+    // Check gas price. This uses the same comparison as the runtime so that
+    // the gateway never rejects a transaction the runtime would accept.
+    let minimum = U256::from(MIN_GAS_PRICE_GWEI);
+    if txn.gas_price < minimum {
+        return Err(ValidationError::GasPrice {
+            got: txn.gas_price,
+            minimum,
+        });
+    }
+
+    // Check chain ID. Transactions without replay protection are accepted.
+    let expected = genesis::SPEC.chain_id();
+    match txn.chain_id() {
+        Some(got) if got != expected => Err(ValidationError::ChainId { got, expected }),
+        _ => Ok(()),
+    }
+}
+
+/// Check a signed transaction against the sender's account nonce and balance.
+///
+/// Nonces above the account nonce are accepted, as preceding transactions may
+/// still be in flight.
+pub fn check_account(
+    txn: &SignedTransaction,
+    nonce: U256,
+    balance: U256,
+) -> Result<(), ValidationError> {
+    if txn.nonce < nonce {
+        return Err(ValidationError::NonceTooLow {
+            got: txn.nonce,
+            expected: nonce,
+        });
+    }
+
+    let cost = txn
+        .gas
+        .saturating_mul(txn.gas_price)
+        .saturating_add(txn.value);
+    if balance < cost {
+        return Err(ValidationError::InsufficientFunds { balance, cost });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ethcore::transaction::{Action, Transaction};
+    use ethereum_types::Address;
+
+    use super::*;
+
+    fn make_transaction(nonce: u64, gas: U256, gas_price: U256, value: u64) -> SignedTransaction {
+        Transaction {
+            nonce: nonce.into(),
+            gas_price,
+            gas,
+            action: Action::Call(Address::default()),
+            value: value.into(),
+            data: vec![],
+        }
+        .fake_sign(Address::default())
+    }
+
+    #[test]
+    fn test_check_gas_limit() {
+        let txn = make_transaction(0, U256::from(BLOCK_GAS_LIMIT), 1.into(), 0);
+        assert!(check_unverified(&txn).is_ok());
+
+        let txn = make_transaction(0, U256::from(BLOCK_GAS_LIMIT) + 1, 1.into(), 0);
+        match check_unverified(&txn) {
+            Err(err @ ValidationError::TooMuchGas { .. }) => {
+                assert_eq!(err.code(), ERROR_LIMIT_EXCEEDED)
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_check_gas_price() {
+        let txn = make_transaction(0, 21000.into(), U256::from(MIN_GAS_PRICE_GWEI), 0);
+        assert!(check_signed(&txn).is_ok());
+
+        let txn = make_transaction(0, 21000.into(), U256::from(MIN_GAS_PRICE_GWEI) - 1, 0);
+        match check_signed(&txn) {
+            Err(err @ ValidationError::GasPrice { .. }) => {
+                assert_eq!(err.code(), ERROR_TRANSACTION_REJECTED)
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_check_account() {
+        let txn = make_transaction(5, 21000.into(), 2.into(), 100);
+
+        // Exact nonce and balance.
+        assert!(check_account(&txn, 5.into(), 42100.into()).is_ok());
+        // Future nonce.
+        assert!(check_account(&txn, 4.into(), 42100.into()).is_ok());
+
+        match check_account(&txn, 6.into(), 42100.into()) {
+            Err(ValidationError::NonceTooLow { got, expected }) => {
+                assert_eq!(got, 5.into());
+                assert_eq!(expected, 6.into());
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        match check_account(&txn, 5.into(), 42099.into()) {
+            Err(ValidationError::InsufficientFunds { balance, cost }) => {
+                assert_eq!(balance, 42099.into());
+                assert_eq!(cost, 42100.into());
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+}